@@ -0,0 +1,1662 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::markdown::{checkbox, list_item};
+
+// how many undo steps we keep around before dropping the oldest ones
+const UNDO_LIMIT: usize = 500;
+
+/*
+Explanation of the code:
+A `Buffer` is one open file. The app can have several of these open at once (one per tab),
+and each one keeps its own text, cursor, scroll position, undo history, and so on.
+All the editing logic lives here, the app just figures out which key was pressed
+and calls the matching method on the active buffer.
+*/
+#[derive(Debug, Clone)]
+pub struct Buffer {
+    // vars related to the text and the file it came from
+    pub text: Vec<String>,                    // text that is displayed, one line is one string
+    pub file_name: Option<String>,            // file this buffer is saved to, if None, we use the default.txt
+    pub dirty: bool,                          // if true, the text has changed since the last save
+    pub saved_text: Vec<String>,              // the text as it was when last loaded or saved, to mark changed lines against
+    pub line_ending: LineEnding,              // the line ending the file uses, so saving doesn't change it
+    pub trailing_newline: bool,               // whether the file ends with a line ending after the last line
+    pub read_only: bool,                      // if true, the text can't be edited, only looked at
+    pub not_utf8: bool,                       // if true, the file wasn't valid UTF-8 and bad bytes were replaced, so it's never saved
+    pub disk_modified: Option<SystemTime>,    // when the file was last changed on disk as far as we know (from loading or saving)
+    pub uses_hard_tabs: bool,                 // if true, Tab inserts a real tab instead of spaces (detected when loading)
+
+    // vars related to cursor position
+    pub cursor_x: usize,
+    pub cursor_y: usize,
+    pub selection_anchor: Option<(usize, usize)>, // where the selection started as (line, char), the other end is the cursor
+    pub goal_x: usize,                        // the column Up/Down try to get back to after passing through shorter lines
+    pub goal_at: Option<(usize, usize)>,      // where the last Up/Down left the cursor, goal_x only counts while it's still there
+
+    // vars related to scrolling, this is the first line/column that is visible in the editor
+    pub scroll_x: usize,
+    pub scroll_y: usize,
+
+    // vars related to undo/redo, each entry is a snapshot of (text, cursor_x, cursor_y)
+    pub undo_stack: Vec<(Vec<String>, usize, usize)>,
+    pub redo_stack: Vec<(Vec<String>, usize, usize)>,
+    pub insert_group_open: bool,              // if true, the last key typed a char, so more chars join the same undo step
+}
+
+// How a line differs from the last saved version, for the markers next to the line numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Added,
+    Modified,
+}
+
+/*
+Which line ending a file uses.
+We remember it when opening a file so saving writes the same thing back,
+instead of turning a Windows file into a Unix one (or the other way around).
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    // If a file has any \r\n in it we treat it as a CRLF file
+    fn detect(content: &str) -> Self {
+        if content.contains("\r\n") { LineEnding::CrLf } else { LineEnding::Lf }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+impl Default for LineEnding {
+    // New files use whatever is normal for the platform
+    fn default() -> Self {
+        if cfg!(windows) { LineEnding::CrLf } else { LineEnding::Lf }
+    }
+}
+
+impl Buffer {
+    // An empty buffer with one blank line, saved to `file_name`
+    pub fn new(file_name: Option<String>) -> Self {
+        Buffer {
+            text: vec!["".to_string()],
+            file_name,
+            dirty: false,
+            saved_text: vec!["".to_string()],
+            line_ending: LineEnding::default(),
+            trailing_newline: true,
+            read_only: false,
+            not_utf8: false,
+            disk_modified: None,
+            uses_hard_tabs: false,
+
+            cursor_x: 0,
+            cursor_y: 0,
+            selection_anchor: None,
+            goal_x: 0,
+            goal_at: None,
+
+            scroll_x: 0,
+            scroll_y: 0,
+
+            undo_stack: vec![],
+            redo_stack: vec![],
+            insert_group_open: false,
+        }
+    }
+
+    /*
+    Replaces the text with the contents of the file at `path`.
+    The cursor goes back to the top and the undo history is cleared,
+    since history from what was here before doesn't apply to this file.
+    A file that isn't valid UTF-8 (probably not text at all) is still shown, with the bad bytes as �,
+    but it's read-only and can't be saved, since writing it back would destroy those bytes.
+     */
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+        self.load_bytes(bytes);
+        // a read-only file can't be saved either
+        self.read_only |= file.metadata()?.permissions().readonly();
+        self.disk_modified = file.metadata()?.modified().ok();
+        Ok(())
+    }
+
+    // Same as `load`, for a note's contents that came from somewhere other than a file (see store.rs)
+    pub fn load_bytes(&mut self, bytes: Vec<u8>) {
+        let content = match String::from_utf8(bytes) {
+            Ok(content) => {
+                self.not_utf8 = false;
+                content
+            }
+            Err(e) => {
+                self.not_utf8 = true;
+                String::from_utf8_lossy(e.as_bytes()).into_owned()
+            }
+        };
+        // if we couldn't save it anyway, start out in read-only mode
+        self.read_only = self.not_utf8;
+        self.text = content.lines().map(|line| line.to_string()).collect();
+        // remember how the file was written so saving it puts it back the same way
+        self.line_ending = if content.is_empty() { LineEnding::default() } else { LineEnding::detect(&content) };
+        self.trailing_newline = content.is_empty() || content.ends_with('\n');
+        // a file without any indentation keeps whatever we had (the default from the config)
+        if let Some(hard_tabs) = detect_hard_tabs(&self.text) {
+            self.uses_hard_tabs = hard_tabs;
+        }
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.keep_one_line(); // an empty file is one empty line
+        self.selection_anchor = None;
+        self.scroll_x = 0;
+        self.scroll_y = 0;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.dirty = false;
+        self.saved_text = self.text.clone();
+    }
+
+    // Saves the text to `path`, see `write_atomically` for how
+    pub fn save(&mut self, path: &Path) -> io::Result<()> {
+        write_atomically(path, self.contents()?.as_bytes())?;
+        self.mark_saved(fs::metadata(path).and_then(|metadata| metadata.modified()).ok());
+        Ok(())
+    }
+
+    /*
+    The text the way it's written to the file: joined with the file's own line ending,
+    and only ending with one if the file originally did.
+    A note that wasn't valid UTF-8 is an error, saving it would corrupt it.
+     */
+    pub fn contents(&self) -> io::Result<String> {
+        if self.not_utf8 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} isn't valid UTF-8, saving would corrupt it", self.display_name())));
+        }
+        let ending = self.line_ending.as_str();
+        let mut content = self.text.join(ending);
+        if self.trailing_newline {
+            content.push_str(ending);
+        }
+        Ok(content)
+    }
+
+    // After the contents were written somewhere, `modified` is when that happened as far as the storage knows
+    pub fn mark_saved(&mut self, modified: Option<SystemTime>) {
+        self.dirty = false; // everything is saved now
+        self.saved_text = self.text.clone();
+        self.disk_modified = modified;
+    }
+
+    // Whether the note was changed by something else since we loaded or saved it, `modified` is its time now
+    pub fn changed_since(&self, modified: Option<SystemTime>) -> bool {
+        matches!((self.disk_modified, modified), (Some(known), Some(modified)) if modified != known)
+    }
+
+    /*
+    Which lines changed since the last save, one entry per line (None if it's the same).
+    This isn't a real diff, it's cheap enough to run every frame: the lines that match at the start
+    and at the end are unchanged, and what's left in between is modified, or added where there are more lines than before.
+    So typing on a line marks just that line and inserting lines marks just the new ones.
+     */
+    pub fn line_changes(&self) -> Vec<Option<LineChange>> {
+        let prefix = self.text.iter().zip(&self.saved_text).take_while(|(line, saved)| line == saved).count();
+        let max_suffix = self.text.len().min(self.saved_text.len()) - prefix;
+        let suffix = self.text.iter().rev().zip(self.saved_text.iter().rev()).take(max_suffix).take_while(|(line, saved)| line == saved).count();
+        let changed_before = self.saved_text.len() - prefix - suffix; // how many saved lines the middle replaced
+        (0..self.text.len())
+            .map(|y| {
+                if y < prefix || y >= self.text.len() - suffix {
+                    None
+                } else if y - prefix < changed_before {
+                    Some(LineChange::Modified)
+                } else {
+                    Some(LineChange::Added)
+                }
+            })
+            .collect()
+    }
+
+    // The name shown in the tab, a buffer that was never given a name (it would be saved as default.txt) is [No Name]
+    pub fn tab_name(&self) -> &str {
+        self.file_name.as_deref().unwrap_or("[No Name]")
+    }
+
+    // The name shown for this buffer in the tab bar and popups
+    pub fn display_name(&self) -> &str {
+        self.file_name.as_deref().unwrap_or("default.txt")
+    }
+
+    // number of chars (not bytes) in a line
+    fn line_len(&self, y: usize) -> usize {
+        self.text[y].chars().count()
+    }
+
+    /*
+    Undo/redo works by snapshotting the whole buffer and cursor before an edit.
+    Notes are small so copying the text is cheap enough, and it keeps things simple.
+    Making a new edit clears the redo stack, since that history no longer applies.
+    Since this is called right before every edit, it also marks the buffer as dirty
+    and drops the selection (the text it pointed at is about to change).
+     */
+    pub fn push_undo(&mut self) {
+        self.undo_stack.push((self.text.clone(), self.cursor_x, self.cursor_y));
+        if self.undo_stack.len() > UNDO_LIMIT {
+            self.undo_stack.remove(0); // drop the oldest step
+        }
+        self.redo_stack.clear();
+        self.dirty = true;
+        self.selection_anchor = None;
+    }
+
+    pub fn undo(&mut self) {
+        if let Some((text, x, y)) = self.undo_stack.pop() {
+            let current = std::mem::replace(&mut self.text, text);
+            self.redo_stack.push((current, self.cursor_x, self.cursor_y));
+            self.cursor_x = x;
+            self.cursor_y = y;
+            self.selection_anchor = None;
+            self.dirty = true;
+            self.keep_one_line();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some((text, x, y)) = self.redo_stack.pop() {
+            let current = std::mem::replace(&mut self.text, text);
+            self.undo_stack.push((current, self.cursor_x, self.cursor_y));
+            self.cursor_x = x;
+            self.cursor_y = y;
+            self.selection_anchor = None;
+            self.dirty = true;
+            self.keep_one_line();
+        }
+    }
+
+    /*
+    The one thing everything else counts on: there's always at least one line (an empty note is one empty line)
+    and the cursor is on one of them, so `text[cursor_y]` and `text.len() - 1` are always fine.
+    Nothing should ever break this, but anything that removes or replaces lines calls it anyway,
+    a note that can't be edited beats a crash that loses everything that wasn't saved.
+     */
+    pub fn keep_one_line(&mut self) {
+        if self.text.is_empty() {
+            self.text.push(String::new());
+        }
+        self.clamp_cursor();
+    }
+
+    /*
+    Keeps the cursor (and the other end of the selection) on the text.
+    Everything indexes `text[cursor_y]`, so after the text is replaced from outside the buffer
+    (reloading a note that got shorter on disk, or a cursor remembered from last time) this puts it back in range,
+    on the last line or at the end of its line if it was past them. Needs at least one line, see `keep_one_line`.
+     */
+    pub fn clamp_cursor(&mut self) {
+        self.cursor_y = self.cursor_y.min(self.text.len() - 1);
+        self.cursor_x = self.cursor_x.min(self.line_len(self.cursor_y));
+        if let Some((y, x)) = self.selection_anchor {
+            let y = y.min(self.text.len() - 1);
+            self.selection_anchor = Some((y, x.min(self.line_len(y))));
+        }
+    }
+
+    /*
+    Types a char at the cursor.
+    Consecutive chars are undone together, so we only snapshot at the start of a run.
+    `continue_group` says whether the previous key also typed a char.
+     */
+    pub fn insert_char(&mut self, c: char, continue_group: bool) {
+        // typing over a selection replaces it
+        let selection = self.selection();
+        if !continue_group || selection.is_some() {
+            self.push_undo();
+        }
+        if let Some(selection) = selection {
+            self.remove_selection(selection);
+        }
+        self.insert_group_open = true;
+        self.dirty = true;
+
+        let idx = byte_offset(&self.text[self.cursor_y], self.cursor_x);
+        self.text[self.cursor_y].insert(idx, c);
+        self.cursor_x += 1;
+
+        // Ensure the cursor does not go out of bounds
+        let line_len = self.line_len(self.cursor_y);
+        if self.cursor_x > line_len {
+            self.cursor_x = line_len;
+        }
+    }
+
+    // Backspace, removes the char before the cursor (or the selection, if there is one)
+    pub fn delete_back(&mut self) {
+        self.keep_one_line();
+        if self.delete_selection() {
+            return;
+        }
+        if self.cursor_x > 0 && self.cursor_y < self.text.len() {
+            self.push_undo();
+            let idx = byte_offset(&self.text[self.cursor_y], self.cursor_x - 1);
+            self.text[self.cursor_y].remove(idx);
+            self.cursor_x -= 1;
+        } else if self.cursor_x == 0 && self.cursor_y > 0 {
+            // at the start of a line, so join it onto the end of the previous line (an empty line just goes away)
+            self.push_undo();
+            let current_line = self.text.remove(self.cursor_y);
+            self.cursor_y -= 1;
+            self.cursor_x = self.line_len(self.cursor_y); // the cursor ends up where the two lines meet
+            self.text[self.cursor_y].push_str(&current_line);
+        }
+    }
+
+    /*
+    Typing with `auto_pairs` on: an opening bracket or quote gets its closer typed after it, with the cursor in between,
+    and typing a closer that's already right after the cursor (most likely we added it) just steps over it.
+    Without `auto_pairs`, or over a selection, it's the same as `insert_char`.
+     */
+    pub fn type_char(&mut self, c: char, continue_group: bool, auto_pairs: bool) {
+        let (_, after) = self.chars_around_cursor();
+        if auto_pairs && self.selection().is_none() && after == Some(c) && is_closer(c) {
+            self.move_right();
+            self.insert_group_open = continue_group;
+            return;
+        }
+        self.insert_char(c, continue_group);
+        if let Some(closer) = closing_pair(c).filter(|_| auto_pairs) {
+            self.insert_char(closer, true);
+            self.move_left(); // back in between the two
+        }
+    }
+
+    // Backspace with `auto_pairs` on: in between an empty pair like () both halves go in one go, otherwise it's `delete_back`
+    pub fn delete_back_paired(&mut self, auto_pairs: bool) {
+        let (before, after) = self.chars_around_cursor();
+        let in_empty_pair = before.and_then(closing_pair).is_some_and(|closer| after == Some(closer));
+        if !auto_pairs || self.selection().is_some() || !in_empty_pair {
+            self.delete_back();
+            return;
+        }
+        self.push_undo();
+        let idx = byte_offset(&self.text[self.cursor_y], self.cursor_x - 1);
+        self.text[self.cursor_y].remove(idx);
+        self.text[self.cursor_y].remove(idx); // the closer moved into its place
+        self.cursor_x -= 1;
+    }
+
+    // The chars right before and right after the cursor on its line, if there are any
+    pub fn chars_around_cursor(&self) -> (Option<char>, Option<char>) {
+        let line = &self.text[self.cursor_y];
+        let before = self.cursor_x.checked_sub(1).and_then(|x| line.chars().nth(x));
+        (before, line.chars().nth(self.cursor_x))
+    }
+
+    // Delete, removes the char under the cursor and the cursor itself doesn't move (or the selection, if there is one)
+    pub fn delete(&mut self) {
+        self.keep_one_line();
+        if self.delete_selection() {
+            return;
+        }
+        if self.cursor_x < self.line_len(self.cursor_y) {
+            self.push_undo();
+            let idx = byte_offset(&self.text[self.cursor_y], self.cursor_x);
+            self.text[self.cursor_y].remove(idx);
+        } else if self.cursor_y < self.text.len() - 1 {
+            // at the end of the line, so pull the next line up onto this one
+            self.push_undo();
+            let next_line = self.text.remove(self.cursor_y + 1);
+            self.text[self.cursor_y].push_str(&next_line);
+        }
+    }
+
+    /*
+    Enter, splits the current line at the cursor and moves to the start of the new line.
+    With `auto_indent` the new line starts with the same indentation as this one,
+    and a list item ("- ", "* ", "1. ") carries on with the next marker.
+    Enter on an item with nothing after the marker ends the list instead, the marker is removed and no line is added.
+     */
+    pub fn split_line(&mut self, auto_indent: bool) {
+        let selection = self.selection();
+        self.push_undo();
+        if let Some(selection) = selection {
+            self.remove_selection(selection); // Enter over a selection replaces it
+        }
+        let mut current_line = self.text[self.cursor_y].clone();
+        let mut new_line = current_line.split_off(byte_offset(&current_line, self.cursor_x));
+        let (prefix, empty_item) = if auto_indent { continuation(&current_line) } else { (String::new(), false) };
+        if empty_item && new_line.is_empty() {
+            // an empty list item, so the list is done
+            current_line.truncate(current_line.len() - current_line.trim_start_matches([' ', '\t']).len());
+            self.cursor_x = current_line.chars().count();
+            self.text[self.cursor_y] = current_line;
+            return;
+        }
+        new_line.insert_str(0, &prefix);
+        self.text[self.cursor_y] = current_line; // update the current line
+        self.text.insert(self.cursor_y + 1, new_line); // insert the new line after the current line
+        self.cursor_y += 1;
+        self.cursor_x = prefix.chars().count();
+    }
+
+    /*
+    Makes the note end in exactly one line ending, dropping any blank lines at the end
+    (used before saving, if turned on in the config). Like trimming, it's its own undo step.
+     */
+    pub fn single_trailing_newline(&mut self) {
+        let blank_at_end = self.text.iter().rev().take_while(|line| line.is_empty()).count().min(self.text.len().saturating_sub(1));
+        if blank_at_end > 0 {
+            self.push_undo();
+            self.text.truncate(self.text.len() - blank_at_end);
+            self.keep_one_line();
+            self.selection_anchor = None;
+        }
+        self.trailing_newline = true;
+    }
+
+    /*
+    Strips spaces and tabs from the end of every line (used before saving, if turned on in the config).
+    It's its own undo step, and if the cursor was in the whitespace that got removed it moves back to the end of the line.
+     */
+    pub fn trim_trailing_whitespace(&mut self) {
+        if !self.text.iter().any(|line| line.ends_with([' ', '\t'])) {
+            return; // nothing to trim, so don't add an undo step
+        }
+        self.push_undo();
+        for line in self.text.iter_mut() {
+            line.truncate(line.trim_end_matches([' ', '\t']).len());
+        }
+        self.cursor_x = self.cursor_x.min(self.line_len(self.cursor_y));
+    }
+
+    // Ctrl+D, puts a copy of the current line right below it, the cursor stays on the original
+    pub fn duplicate_line(&mut self) {
+        self.push_undo();
+        let line = self.text[self.cursor_y].clone();
+        self.text.insert(self.cursor_y + 1, line);
+    }
+
+    /*
+    Flips the checkbox of the task on the cursor's line between [ ] and [x], however far it's indented.
+    Returns false if the line isn't a task (see `markdown::checkbox`), nothing changes then.
+     */
+    pub fn toggle_checkbox(&mut self) -> bool {
+        let Some((at, checked)) = checkbox(&self.text[self.cursor_y]) else {
+            return false;
+        };
+        self.push_undo();
+        self.text[self.cursor_y].replace_range(at + 1..at + 2, if checked { " " } else { "x" });
+        true
+    }
+
+    /*
+    Makes the cursor's line a task: "- [ ] " goes in after the indentation, or just "[ ] " after the marker
+    if it's a list item already. The cursor stays on the same text.
+    Returns false if the line is a task already, it's left alone then.
+     */
+    pub fn insert_checkbox(&mut self) -> bool {
+        let line = &self.text[self.cursor_y];
+        if checkbox(line).is_some() {
+            return false;
+        }
+        let rest = line.trim_start_matches([' ', '\t']);
+        let (at, inserted) = match list_item(rest) {
+            Some((_, item)) => (line.len() - item.len(), "[ ] "),
+            None => (line.len() - rest.len(), "- [ ] "),
+        };
+        let at_x = line[..at].chars().count();
+        self.push_undo();
+        self.text[self.cursor_y].insert_str(at, inserted);
+        if self.cursor_x >= at_x {
+            self.cursor_x += inserted.len();
+        }
+        true
+    }
+
+    // dd in vim mode, removes the whole line (the last one left just gets emptied)
+    pub fn delete_line(&mut self) {
+        self.push_undo();
+        if self.text.len() == 1 {
+            self.text[0].clear();
+        } else {
+            self.text.remove(self.cursor_y);
+        }
+        self.keep_one_line();
+        self.selection_anchor = None;
+    }
+
+    /*
+    Ctrl+K, deletes from the cursor to the end of the line.
+    If there's nothing after the cursor it joins the next line up instead, so pressing it again keeps going.
+     */
+    pub fn delete_to_end(&mut self) {
+        if self.cursor_x < self.line_len(self.cursor_y) {
+            self.push_undo();
+            let idx = byte_offset(&self.text[self.cursor_y], self.cursor_x);
+            self.text[self.cursor_y].truncate(idx);
+        } else if self.cursor_y < self.text.len() - 1 {
+            self.push_undo();
+            let next_line = self.text.remove(self.cursor_y + 1);
+            self.text[self.cursor_y].push_str(&next_line);
+        }
+    }
+
+    // Alt+Up/Alt+Down, swaps the current line with the one above/below, the cursor moves along with it
+    pub fn move_line(&mut self, up: bool) {
+        let target = if up { self.cursor_y.checked_sub(1) } else { Some(self.cursor_y + 1) };
+        let Some(target) = target.filter(|target| *target < self.text.len()) else {
+            return; // already at the top/bottom
+        };
+        self.push_undo();
+        self.text.swap(self.cursor_y, target);
+        self.cursor_y = target;
+    }
+
+    // Tab, indents by inserting spaces at the cursor, or every selected line when the selection covers more than one
+    pub fn indent(&mut self, tab_width: usize) {
+        let indent = if self.uses_hard_tabs { "\t".to_string() } else { " ".repeat(tab_width) };
+        if let Some((first, last)) = self.selected_lines() {
+            let anchor = self.selection_anchor;
+            self.push_undo();
+            self.selection_anchor = anchor; // kept, so Tab can be pressed again on the same lines
+            for y in first..=last {
+                if self.text[y].is_empty() {
+                    continue; // no point leaving whitespace on an empty line
+                }
+                self.text[y].insert_str(0, &indent);
+                // an end at the start of the line stays there, so the indent is part of the selection
+                self.shift_selection_ends(y, |x| if x == 0 { 0 } else { x + indent.len() });
+            }
+            return;
+        }
+        self.push_undo();
+        let idx = byte_offset(&self.text[self.cursor_y], self.cursor_x);
+        self.text[self.cursor_y].insert_str(idx, &indent);
+        self.cursor_x += indent.len(); // spaces and tabs are one byte each
+    }
+
+    /*
+    Shift+Tab, dedents by removing a leading tab or up to tab_width leading spaces, other characters are left alone.
+    With a selection covering more than one line every one of them is dedented as far as it can go.
+     */
+    pub fn dedent(&mut self, tab_width: usize) {
+        let lines = self.selected_lines().unwrap_or((self.cursor_y, self.cursor_y));
+        if (lines.0..=lines.1).all(|y| dedent_width(&self.text[y], tab_width) == 0) {
+            return; // nothing to remove
+        }
+        let anchor = self.selection_anchor;
+        self.push_undo();
+        self.selection_anchor = anchor;
+        for y in lines.0..=lines.1 {
+            let width = dedent_width(&self.text[y], tab_width);
+            self.text[y].drain(..width); // spaces and tabs are one byte each
+            self.shift_selection_ends(y, |x| x.saturating_sub(width));
+        }
+    }
+
+    /*
+    The lines Tab and Shift+Tab work on when the selection covers more than one line.
+    A selection ending right at the start of a line doesn't count that line, like selecting whole lines with Shift+Down.
+     */
+    fn selected_lines(&self) -> Option<(usize, usize)> {
+        let ((start_y, _), (end_y, end_x)) = self.selection()?;
+        if start_y == end_y {
+            return None;
+        }
+        Some((start_y, if end_x == 0 { end_y - 1 } else { end_y }))
+    }
+
+    // moves the column of the cursor and the selection anchor if they're on line `y`, after that line changed
+    fn shift_selection_ends(&mut self, y: usize, shift: impl Fn(usize) -> usize) {
+        if self.cursor_y == y {
+            self.cursor_x = shift(self.cursor_x);
+        }
+        if let Some((anchor_y, anchor_x)) = &mut self.selection_anchor
+            && *anchor_y == y
+        {
+            *anchor_x = shift(*anchor_x);
+        }
+    }
+
+    /*
+    Selections have two ends, the anchor (where Shift+movement started) and the cursor.
+    Either one can come first, so `selection` always gives back (start, end) in text order,
+    each as (line, char). An empty selection (anchor on the cursor) counts as no selection.
+     */
+    pub fn start_selection(&mut self) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some((self.cursor_y, self.cursor_x));
+        }
+    }
+
+    // Ctrl+A, selects everything, with the cursor at the very end
+    pub fn select_all(&mut self) {
+        self.selection_anchor = Some((0, 0));
+        self.cursor_y = self.text.len() - 1;
+        self.cursor_x = self.line_len(self.cursor_y);
+    }
+
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.selection_anchor?;
+        let cursor = (self.cursor_y, self.cursor_x);
+        match anchor.cmp(&cursor) {
+            std::cmp::Ordering::Less => Some((anchor, cursor)),
+            std::cmp::Ordering::Greater => Some((cursor, anchor)),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    // The selected text, with lines joined by \n
+    pub fn selected_text(&self) -> Option<String> {
+        let ((start_y, start_x), (end_y, end_x)) = self.selection()?;
+        if start_y == end_y {
+            return Some(self.text[start_y].chars().skip(start_x).take(end_x - start_x).collect());
+        }
+        let mut lines: Vec<String> = vec![self.text[start_y].chars().skip(start_x).collect()];
+        lines.extend(self.text[start_y + 1..end_y].iter().cloned());
+        lines.push(self.text[end_y].chars().take(end_x).collect());
+        Some(lines.join("\n"))
+    }
+
+    // Deletes the selection as its own undo step, returns false if nothing was selected
+    pub fn delete_selection(&mut self) -> bool {
+        let Some(selection) = self.selection() else {
+            return false;
+        };
+        self.push_undo();
+        self.remove_selection(selection);
+        true
+    }
+
+    // Cuts a selection out of the text and leaves the cursor where it started, the caller takes care of undo
+    fn remove_selection(&mut self, ((start_y, start_x), (end_y, end_x)): ((usize, usize), (usize, usize))) {
+        let tail = self.text[end_y][byte_offset(&self.text[end_y], end_x)..].to_string();
+        let start = byte_offset(&self.text[start_y], start_x);
+        self.text[start_y].truncate(start);
+        self.text[start_y].push_str(&tail);
+        self.text.drain(start_y + 1..=end_y);
+        self.cursor_y = start_y;
+        self.cursor_x = start_x;
+        self.selection_anchor = None;
+        self.keep_one_line();
+    }
+
+    /*
+    Pastes `text` at the cursor (replacing the selection, if there is one) as one undo step.
+    Each \n in it starts a new line, and the cursor ends up right after the pasted text.
+     */
+    pub fn insert_text(&mut self, text: &str) {
+        let selection = self.selection();
+        self.push_undo();
+        if let Some(selection) = selection {
+            self.remove_selection(selection);
+        }
+
+        let idx = byte_offset(&self.text[self.cursor_y], self.cursor_x);
+        let tail = self.text[self.cursor_y].split_off(idx); // whatever was after the cursor goes after the pasted text
+        let mut lines = text.split('\n');
+        let mut y = self.cursor_y;
+        self.text[y].push_str(lines.next().unwrap_or(""));
+        for line in lines {
+            y += 1;
+            self.text.insert(y, line.to_string());
+        }
+        self.cursor_y = y;
+        self.cursor_x = self.line_len(y);
+        self.text[y].push_str(&tail);
+    }
+
+    // handling cursor movement
+    pub fn move_left(&mut self) {
+        if self.cursor_x > 0 {
+            self.cursor_x -= 1;
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        // bounded by the number of chars (not bytes) in the line
+        if self.cursor_x < self.line_len(self.cursor_y) {
+            self.cursor_x += 1;
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        if self.cursor_y > 0 {
+            self.move_to_line(self.cursor_y - 1);
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if self.cursor_y < self.text.len() - 1 {
+            self.move_to_line(self.cursor_y + 1);
+        }
+    }
+
+    /*
+    Moves the cursor to line `y` for Up/Down and PageUp/PageDown, keeping the column it had.
+    A shorter line puts the cursor at its end, but the column is remembered (`goal_x`) so going on
+    to a longer line puts it back where it was. Once the cursor is moved any other way (typing, Left, a click...)
+    it's no longer where the last vertical move left it, and its new column becomes the one to keep.
+     */
+    fn move_to_line(&mut self, y: usize) {
+        let goal = if self.goal_at == Some((self.cursor_y, self.cursor_x)) { self.goal_x } else { self.cursor_x };
+        self.cursor_y = y;
+        // clamp against chars, not bytes, or "naïve" would let the cursor go one past the end
+        self.cursor_x = goal.min(self.line_len(y));
+        self.goal_x = goal;
+        self.goal_at = Some((self.cursor_y, self.cursor_x));
+    }
+
+    pub fn home(&mut self) {
+        self.cursor_x = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor_x = self.line_len(self.cursor_y);
+    }
+
+    /*
+    Home/End for wrapped lines, going to the start/end of the row the cursor is on (`width` columns per row).
+    Pressing it again when already there goes on to the start/end of the whole line.
+    The end of a row that isn't the last one is its last char, one further would be the start of the next row.
+     */
+    pub fn row_home(&mut self, width: usize, tab_width: usize) {
+        let line = &self.text[self.cursor_y];
+        let start = match width {
+            0 => 0,
+            _ => char_at_column(line, display_width(line, self.cursor_x, tab_width) / width * width, tab_width),
+        };
+        self.cursor_x = if self.cursor_x == start { 0 } else { start };
+    }
+
+    pub fn row_end(&mut self, width: usize, tab_width: usize) {
+        let line = &self.text[self.cursor_y];
+        let len = self.line_len(self.cursor_y);
+        let end = match width {
+            0 => len,
+            _ => char_at_column(line, display_width(line, self.cursor_x, tab_width) / width * width + width - 1, tab_width),
+        };
+        self.cursor_x = if self.cursor_x == end { len } else { end };
+    }
+
+    // Moves to the start of a line, `line` counts from 1 like the line numbers on screen (clamped to the text)
+    pub fn goto_line(&mut self, line: usize) {
+        self.cursor_y = line.clamp(1, self.text.len()) - 1;
+        self.cursor_x = 0;
+        self.selection_anchor = None;
+    }
+
+    // Where the cursor is, as (line, char)
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.cursor_y, self.cursor_x)
+    }
+
+    // Puts the cursor at (line, char), kept on the text, and drops the selection
+    pub fn set_cursor(&mut self, line: usize, char: usize) {
+        (self.cursor_y, self.cursor_x) = (line, char);
+        self.selection_anchor = None;
+        self.clamp_cursor();
+    }
+
+    // PageUp/PageDown, move a whole screen (`lines`) up or down
+    pub fn page_up(&mut self, lines: usize) {
+        self.move_to_line(self.cursor_y.saturating_sub(lines.max(1)));
+    }
+
+    pub fn page_down(&mut self, lines: usize) {
+        self.move_to_line((self.cursor_y + lines.max(1)).min(self.text.len() - 1));
+    }
+
+    /*
+    Word-wise movement (Ctrl+Left / Ctrl+Right).
+    We skip any whitespace first, then the run of non-whitespace after it.
+    If the cursor is already at the edge of the line, we just hop to the next/previous line.
+     */
+    pub fn word_right(&mut self) {
+        let chars: Vec<char> = self.text[self.cursor_y].chars().collect();
+        if self.cursor_x >= chars.len() {
+            if self.cursor_y < self.text.len() - 1 {
+                self.cursor_y += 1;
+                self.cursor_x = 0;
+            }
+            return;
+        }
+        let mut x = self.cursor_x;
+        while x < chars.len() && chars[x].is_whitespace() {
+            x += 1;
+        }
+        while x < chars.len() && !chars[x].is_whitespace() {
+            x += 1;
+        }
+        self.cursor_x = x;
+    }
+
+    pub fn word_left(&mut self) {
+        if self.cursor_x == 0 {
+            if self.cursor_y > 0 {
+                self.cursor_y -= 1;
+                self.cursor_x = self.line_len(self.cursor_y);
+            }
+            return;
+        }
+        let chars: Vec<char> = self.text[self.cursor_y].chars().collect();
+        let mut x = self.cursor_x.min(chars.len());
+        while x > 0 && chars[x - 1].is_whitespace() {
+            x -= 1;
+        }
+        while x > 0 && !chars[x - 1].is_whitespace() {
+            x -= 1;
+        }
+        self.cursor_x = x;
+    }
+
+    /*
+    Ctrl+Backspace and Ctrl+Delete, delete from the cursor to wherever Ctrl+Left/Ctrl+Right would go
+    (so the whitespace next to the cursor and then the word), as one undo step.
+    At the start (or end) of a line that's just the line break, joining it with the line above (or below).
+    A selection gets deleted instead, same as plain Backspace/Delete.
+     */
+    pub fn delete_word_left(&mut self) {
+        self.delete_word(Buffer::word_left);
+    }
+
+    pub fn delete_word_right(&mut self) {
+        self.delete_word(Buffer::word_right);
+    }
+
+    fn delete_word(&mut self, word_move: fn(&mut Buffer)) {
+        self.keep_one_line();
+        if self.delete_selection() {
+            return;
+        }
+        let cursor = (self.cursor_y, self.cursor_x);
+        word_move(self);
+        let target = (self.cursor_y, self.cursor_x);
+        (self.cursor_y, self.cursor_x) = cursor; // undo should put the cursor back where it was before the delete
+        if target != cursor {
+            self.push_undo();
+            self.remove_selection((cursor.min(target), cursor.max(target)));
+        }
+    }
+
+    /*
+    Where the cursor is on screen as (row, col), before scrolling.
+    Without wrapping that's the line and the column of the cursor (tabs before it take up to `tab_width` columns),
+    but with wrapping on every line above can take up several rows, and the cursor itself may be on a later row of its line.
+     */
+    pub fn cursor_visual(&self, width: usize, wrap: bool, tab_width: usize) -> (usize, usize) {
+        let col = display_width(&self.text[self.cursor_y], self.cursor_x, tab_width);
+        if !wrap || width == 0 {
+            return (self.cursor_y, col);
+        }
+        let rows_above: usize = self.text[..self.cursor_y].iter().map(|line| wrapped_rows(line, width, tab_width)).sum();
+        (rows_above + col / width, col % width)
+    }
+
+    /*
+    The line at the top of the editor, and how many of its rows are scrolled past (only ever more than 0 with wrapping),
+    so only the lines in view need to be drawn. Past the end of the text it's text.len().
+     */
+    pub fn first_visible_line(&self, width: usize, wrap: bool, tab_width: usize) -> (usize, usize) {
+        if !wrap || width == 0 {
+            return (self.scroll_y.min(self.text.len()), 0);
+        }
+        let mut rows_left = self.scroll_y;
+        for (y, line) in self.text.iter().enumerate() {
+            let rows = wrapped_rows(line, width, tab_width);
+            if rows_left < rows {
+                return (y, rows_left);
+            }
+            rows_left -= rows;
+        }
+        (self.text.len(), 0)
+    }
+
+    /*
+    The opposite of `cursor_visual`, turns a (row, col) on screen (after adding the scroll)
+    back into a (line, char) in the text. Anything past the end of a line or the text gets clamped.
+     */
+    pub fn logical_position(&self, row: usize, col: usize, width: usize, wrap: bool, tab_width: usize) -> (usize, usize) {
+        let (y, col) = if wrap && width > 0 {
+            // walk down the lines until we find the one this row belongs to
+            let mut rows_left = row;
+            let mut y = 0;
+            while y < self.text.len() - 1 && rows_left >= wrapped_rows(&self.text[y], width, tab_width) {
+                rows_left -= wrapped_rows(&self.text[y], width, tab_width);
+                y += 1;
+            }
+            (y, rows_left * width + col)
+        } else {
+            (row.min(self.text.len() - 1), col)
+        };
+        (y, char_at_column(&self.text[y], col, tab_width))
+    }
+
+    /*
+    Moves the scroll offsets just enough so the cursor is visible
+    in a viewport of `view_width` columns and `view_height` rows.
+     */
+    pub fn scroll_to_cursor(&mut self, view_width: usize, view_height: usize, wrap: bool, tab_width: usize) {
+        let (row, col) = self.cursor_visual(view_width, wrap, tab_width);
+        // when wrapping there's nothing to scroll sideways
+        self.scroll_x = if wrap { 0 } else { scroll_offset(self.scroll_x, col, view_width) };
+        self.scroll_y = scroll_offset(self.scroll_y, row, view_height);
+    }
+
+    /*
+    Every place `query` is in the text, as (line, char column) so they line up with the cursor, not byte offsets.
+    Matches on a line never overlap, we scan left to right and skip past each one.
+     */
+    pub fn find(&self, query: &str) -> Vec<(usize, usize)> {
+        if query.is_empty() {
+            return vec![];
+        }
+        let mut matches = vec![];
+        for (y, line) in self.text.iter().enumerate() {
+            for (byte_idx, _) in line.match_indices(query) {
+                matches.push((y, line[..byte_idx].chars().count()));
+            }
+        }
+        matches
+    }
+
+    /*
+    Replaces the `query` at `at` (a match from `find`) with `replacement`, as its own undo step.
+    The cursor goes after the replacement, so looking for the next match doesn't land inside it.
+     */
+    pub fn replace_at(&mut self, (y, col): (usize, usize), query: &str, replacement: &str) {
+        let start = byte_offset(&self.text[y], col);
+        let end = byte_offset(&self.text[y], col + query.chars().count());
+        self.push_undo();
+        self.text[y].replace_range(start..end, replacement);
+        self.cursor_y = y;
+        self.cursor_x = col + replacement.chars().count();
+    }
+
+    /*
+    Replaces every match from `find` in one undo step.
+    The cursor is shifted so it stays on the same text it was on before.
+     */
+    pub fn replace_all(&mut self, matches: &[(usize, usize)], query: &str, replacement: &str) {
+        if matches.is_empty() {
+            return;
+        }
+        self.push_undo();
+        let find_len = query.chars().count();
+        let replace_len = replacement.chars().count();
+        for (y, col) in matches.iter().rev() {
+            let start = byte_offset(&self.text[*y], *col);
+            let end = byte_offset(&self.text[*y], col + find_len);
+            self.text[*y].replace_range(start..end, replacement);
+
+            // going right to left, so earlier matches on the line don't affect this one
+            if *y == self.cursor_y && self.cursor_x > *col {
+                if self.cursor_x >= col + find_len {
+                    self.cursor_x = self.cursor_x - find_len + replace_len;
+                } else {
+                    self.cursor_x = col + replace_len; // was inside the match, move to the end of the replacement
+                }
+            }
+        }
+    }
+
+    /*
+    Counts (lines, words, chars) in the buffer.
+    Words are anything separated by whitespace, and chars are unicode chars, not bytes.
+     */
+    pub fn stats(&self) -> (usize, usize, usize) {
+        let words = self.text.iter().map(|line| word_count(line)).sum();
+        let chars = self.text.iter().map(|line| line.chars().count()).sum();
+        (self.text.len(), words, chars)
+    }
+}
+
+// What auto pairing closes `c` with, None if it isn't an opening bracket or quote
+fn closing_pair(c: char) -> Option<char> {
+    match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' => Some('"'),
+        '`' => Some('`'),
+        _ => None,
+    }
+}
+
+fn is_closer(c: char) -> bool {
+    matches!(c, ')' | ']' | '}' | '"' | '`')
+}
+
+// The same as split_whitespace().count(), but an ASCII line (most of them) is counted by bytes, which is a lot quicker on a huge one
+fn word_count(line: &str) -> usize {
+    if !line.is_ascii() {
+        return line.split_whitespace().count();
+    }
+    // the whitespace split_whitespace knows about in ASCII: \t \n \x0b \x0c \r and space
+    let space = |b: u8| b == b' ' || (b'\t'..=b'\r').contains(&b);
+    let bytes = line.as_bytes();
+    // a word starts wherever a non-space comes right after a space (or at the start of the line)
+    let starts = bytes.windows(2).filter(|pair| space(pair[0]) && !space(pair[1])).count();
+    starts + usize::from(bytes.first().is_some_and(|b| !space(*b)))
+}
+
+/*
+Writes `content` to `path` without ever leaving a half written note behind.
+We write everything to a hidden temp file next to it (`.note.txt.tmp`) and only once that's
+fully on disk do we rename it over the original. A rename on the same filesystem is atomic,
+so if we crash or the disk fills up part way through, the original is still intact.
+The new file keeps the original's permissions, and a read-only file is refused instead of replaced.
+ */
+pub fn write_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    let permissions = match fs::metadata(path) {
+        Ok(metadata) if metadata.permissions().readonly() => {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} is read-only", path.display())));
+        }
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(_) => None, // new file, so there's nothing to keep
+    };
+
+    let temp_path = temp_path(path);
+    let result = write_file(&temp_path, content, permissions).and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path); // don't leave the temp file lying around
+    }
+    result
+}
+
+// The temp file `write_atomically` writes first, a hidden file next to the note (note.txt -> .note.txt.tmp)
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+// Writes `content` to a fresh file and makes sure it actually reached the disk
+fn write_file(path: &Path, content: &[u8], permissions: Option<fs::Permissions>) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    if let Some(permissions) = permissions {
+        fs::set_permissions(path, permissions)?;
+    }
+    Ok(())
+}
+
+/*
+`cursor_x` is a character index, but `String` is indexed by bytes.
+This converts a character index into the byte offset of that character in the line,
+so things like "é" or "漢" (more than one byte) don't panic when we insert or remove.
+If the index is past the end, we just return the length of the line.
+ */
+pub fn byte_offset(line: &str, char_idx: usize) -> usize {
+    if line.is_ascii() {
+        return char_idx.min(line.len()); // every char is one byte
+    }
+    line.char_indices()
+        .nth(char_idx)
+        .map(|(i, _)| i)
+        .unwrap_or(line.len())
+}
+
+// how many bytes Shift+Tab takes off the start of a line: a tab, or up to tab_width spaces
+fn dedent_width(line: &str, tab_width: usize) -> usize {
+    if line.starts_with('\t') {
+        return 1;
+    }
+    line.chars().take(tab_width).take_while(|c| *c == ' ').count()
+}
+
+/*
+Returns the new scroll offset so that `target` is inside the window `[offset, offset + view)`.
+If it's already visible we don't move, otherwise we scroll the least amount needed.
+ */
+pub fn scroll_offset(offset: usize, target: usize, view: usize) -> usize {
+    if target < offset {
+        target
+    } else if view > 0 && target >= offset + view {
+        target + 1 - view
+    } else {
+        offset
+    }
+}
+
+/*
+How many rows a line takes up when wrapped to `width` columns.
+A line that exactly fills its rows gets one more (empty) row, so there's somewhere to put the cursor at the end.
+ */
+pub fn wrapped_rows(line: &str, width: usize, tab_width: usize) -> usize {
+    display_width(line, usize::MAX, tab_width).checked_div(width).unwrap_or(0) + 1
+}
+
+/*
+How many columns the first `chars` chars of a line take up on screen.
+A tab goes on to the next multiple of `tab_width`, everything else is one column.
+ */
+pub fn display_width(line: &str, chars: usize, tab_width: usize) -> usize {
+    // without tabs a column is a char, which is quick to count even on a huge line (this runs a few times every frame)
+    if !line.contains('\t') {
+        return if line.is_ascii() { chars.min(line.len()) } else { line.chars().take(chars).count() };
+    }
+    line.chars().take(chars).fold(0, |col, c| if c == '\t' { next_tab_stop(col, tab_width) } else { col + 1 })
+}
+
+// The column a tab starting at `col` ends at
+pub fn next_tab_stop(col: usize, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    (col / tab_width + 1) * tab_width
+}
+
+// The opposite of `display_width`, which char is drawn at column `col` (a tab covers several), clamped to the end of the line
+pub fn char_at_column(line: &str, col: usize, tab_width: usize) -> usize {
+    if !line.contains('\t') {
+        return col.min(if line.is_ascii() { line.len() } else { line.chars().count() });
+    }
+    let mut end = 0; // column right after the char
+    for (x, c) in line.chars().enumerate() {
+        end = if c == '\t' { next_tab_stop(end, tab_width) } else { end + 1 };
+        if end > col {
+            return x;
+        }
+    }
+    line.chars().count()
+}
+
+/*
+What a new line after `line` starts with when auto indenting: the same leading spaces/tabs,
+plus the next list marker if `line` is a list item ("- " stays "- ", "3. " becomes "4. ").
+The bool is whether `line` is a list item with nothing after the marker.
+ */
+fn continuation(line: &str) -> (String, bool) {
+    let rest = line.trim_start_matches([' ', '\t']);
+    let indent = &line[..line.len() - rest.len()];
+    if let Some(marker) = ["- ", "* ", "+ "].into_iter().find(|marker| rest.starts_with(marker)) {
+        return (format!("{}{}", indent, marker), rest.len() == marker.len());
+    }
+    let digits = rest.chars().take_while(char::is_ascii_digit).count(); // digits are one byte each
+    if digits > 0
+        && let Some(item) = rest[digits..].strip_prefix(". ")
+        && let Ok(number) = rest[..digits].parse::<u64>()
+    {
+        return (format!("{}{}. ", indent, number + 1), item.is_empty());
+    }
+    (indent.to_string(), false)
+}
+
+/*
+Whether a file is indented with tabs, going by what most indented lines start with.
+None if nothing is indented, so there's no way to tell.
+ */
+fn detect_hard_tabs(text: &[String]) -> Option<bool> {
+    let tabs = text.iter().filter(|line| line.starts_with('\t')).count();
+    let spaces = text.iter().filter(|line| line.starts_with(' ')).count();
+    if tabs == 0 && spaces == 0 { None } else { Some(tabs > spaces) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn byte_offset_handles_multibyte_chars() {
+        assert_eq!(byte_offset("café", 0), 0);
+        assert_eq!(byte_offset("café", 3), 3);
+        assert_eq!(byte_offset("café", 4), 5); // "é" is two bytes
+        assert_eq!(byte_offset("漢字", 1), 3);
+        assert_eq!(byte_offset("abc", 10), 3);
+    }
+
+    #[test]
+    fn scroll_offset_keeps_target_visible() {
+        assert_eq!(scroll_offset(0, 5, 10), 0);
+        assert_eq!(scroll_offset(0, 15, 10), 6);
+        assert_eq!(scroll_offset(6, 3, 10), 3);
+        assert_eq!(scroll_offset(6, 15, 10), 6);
+    }
+
+    #[test]
+    fn cutting_and_pasting_across_lines() {
+        let mut buffer = Buffer { text: vec!["abc".to_string(), "déf".to_string(), "ghi".to_string()], ..Buffer::new(None) };
+        buffer.cursor_x = 1;
+        buffer.start_selection();
+        buffer.cursor_y = 2;
+        buffer.cursor_x = 1;
+        assert_eq!(buffer.selected_text().as_deref(), Some("bc\ndéf\ng"));
+
+        let cut = buffer.selected_text().unwrap();
+        assert!(buffer.delete_selection());
+        assert_eq!(buffer.text, vec!["ahi".to_string()]);
+        assert_eq!((buffer.cursor_y, buffer.cursor_x), (0, 1));
+
+        buffer.insert_text(&cut);
+        assert_eq!(buffer.text, vec!["abc".to_string(), "déf".to_string(), "ghi".to_string()]);
+        assert_eq!((buffer.cursor_y, buffer.cursor_x), (2, 1));
+
+        // selecting backwards gives the same range
+        buffer.start_selection();
+        buffer.cursor_y = 1;
+        buffer.cursor_x = 2;
+        assert_eq!(buffer.selected_text().as_deref(), Some("f\ng"));
+    }
+
+    #[test]
+    fn saving_goes_through_a_temp_file_that_gets_cleaned_up() {
+        let folder = std::env::temp_dir().join(format!("trmnotes-test-atomic-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("note.txt");
+        fs::write(&path, "old\n").unwrap();
+
+        let mut buffer = Buffer { text: vec!["new".to_string()], dirty: true, ..Buffer::new(None) };
+        buffer.save(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert!(!buffer.dirty);
+        assert!(!temp_path(&path).exists());
+
+        // renaming over a folder fails, the temp file is removed and the folder is untouched
+        let blocked = folder.join("blocked.txt");
+        fs::create_dir(&blocked).unwrap();
+        buffer.dirty = true;
+        assert!(buffer.save(&blocked).is_err());
+        assert!(buffer.dirty);
+        assert!(blocked.is_dir());
+        assert!(!temp_path(&blocked).exists());
+    }
+
+    #[test]
+    fn trimming_trailing_whitespace_keeps_the_cursor_on_the_line() {
+        let mut buffer = Buffer {
+            text: vec!["keep".to_string(), "spaces   ".to_string(), "tabs\t \t".to_string(), "  indented".to_string()],
+            ..Buffer::new(None)
+        };
+        buffer.cursor_y = 1;
+        buffer.cursor_x = 8;
+        buffer.trim_trailing_whitespace();
+        assert_eq!(buffer.text, vec!["keep", "spaces", "tabs", "  indented"]);
+        assert_eq!(buffer.cursor_x, 6);
+
+        // already clean, so no extra undo step
+        let undo_steps = buffer.undo_stack.len();
+        buffer.trim_trailing_whitespace();
+        assert_eq!(buffer.undo_stack.len(), undo_steps);
+    }
+
+    #[test]
+    fn moving_up_and_down_clamps_to_chars_not_bytes() {
+        let mut buffer = Buffer {
+            text: vec!["naïve".to_string(), "a much longer ascii line".to_string(), "naïve".to_string()],
+            ..Buffer::new(None)
+        };
+        buffer.cursor_y = 1;
+        buffer.cursor_x = 20;
+        buffer.move_up();
+        assert_eq!((buffer.cursor_y, buffer.cursor_x), (0, 5)); // "naïve" is 5 chars but 6 bytes
+
+        buffer.cursor_y = 1;
+        buffer.cursor_x = 20;
+        buffer.move_down();
+        assert_eq!((buffer.cursor_y, buffer.cursor_x), (2, 5));
+    }
+
+    #[test]
+    fn row_home_and_end_stop_at_wrapped_rows() {
+        // 10 chars per row, so this line takes three rows: "0123456789", "abcdefghij", "ABCDE"
+        let mut buffer = Buffer { text: vec!["0123456789abcdefghijABCDE".to_string()], cursor_x: 14, ..Buffer::new(None) };
+        buffer.row_home(10, 4);
+        assert_eq!(buffer.cursor_x, 10);
+        buffer.row_home(10, 4);
+        assert_eq!(buffer.cursor_x, 0);
+
+        buffer.cursor_x = 14;
+        buffer.row_end(10, 4);
+        assert_eq!(buffer.cursor_x, 19);
+        assert_eq!(buffer.cursor_visual(10, true, 4), (1, 9));
+        buffer.row_end(10, 4);
+        assert_eq!(buffer.cursor_x, 25);
+
+        // on the last row the end of the row is the end of the line
+        buffer.cursor_x = 21;
+        buffer.row_end(10, 4);
+        assert_eq!(buffer.cursor_x, 25);
+        buffer.row_home(10, 4);
+        assert_eq!(buffer.cursor_x, 20);
+    }
+
+    #[test]
+    fn tabs_take_up_columns_to_the_next_tab_stop() {
+        let mut buffer = Buffer { text: vec!["\tab\tc".to_string()], cursor_x: 4, ..Buffer::new(None) };
+        // "→   ab→ c" with a tab width of 4, the cursor is after the second tab
+        assert_eq!(display_width("\tab\tc", 4, 4), 8);
+        assert_eq!(buffer.cursor_visual(80, false, 4), (0, 8));
+        assert_eq!(wrapped_rows("\tab\tc", 4, 4), 3);
+        assert_eq!(buffer.cursor_visual(4, true, 4), (2, 0));
+
+        // clicking anywhere on a tab puts the cursor before it
+        assert_eq!(buffer.logical_position(0, 2, 80, false, 4), (0, 0));
+        assert_eq!(buffer.logical_position(0, 6, 80, false, 4), (0, 3));
+        assert_eq!(buffer.logical_position(0, 99, 80, false, 4), (0, 5));
+
+        buffer.uses_hard_tabs = true;
+        buffer.cursor_x = 0;
+        buffer.indent(4);
+        assert_eq!((buffer.text[0].as_str(), buffer.cursor_x), ("\t\tab\tc", 1));
+        buffer.dedent(4);
+        buffer.dedent(4);
+        assert_eq!(buffer.text[0], "ab\tc");
+    }
+
+    #[test]
+    fn indentation_style_is_detected_from_most_lines() {
+        let lines = |text: &[&str]| text.iter().map(|line| line.to_string()).collect::<Vec<String>>();
+        assert_eq!(detect_hard_tabs(&lines(&["fn a() {", "\tb();", "\tc();", "    d();"])), Some(true));
+        assert_eq!(detect_hard_tabs(&lines(&["- a", "  - b", "\tc"])), Some(false));
+        assert_eq!(detect_hard_tabs(&lines(&["no", "indent"])), None);
+    }
+
+    #[test]
+    fn enter_keeps_the_indentation_and_continues_lists() {
+        let mut buffer = Buffer { text: vec!["    hello".to_string()], cursor_x: 9, ..Buffer::new(None) };
+        buffer.split_line(true);
+        assert_eq!(buffer.text, vec!["    hello", "    "]);
+        assert_eq!((buffer.cursor_y, buffer.cursor_x), (1, 4));
+
+        let mut buffer = Buffer { text: vec!["  9. ninth".to_string()], cursor_x: 10, ..Buffer::new(None) };
+        buffer.split_line(true);
+        assert_eq!(buffer.text, vec!["  9. ninth", "  10. "]);
+        assert_eq!(buffer.cursor_x, 6);
+        // Enter again on the empty item ends the list
+        buffer.split_line(true);
+        assert_eq!(buffer.text, vec!["  9. ninth", "  "]);
+        assert_eq!((buffer.cursor_y, buffer.cursor_x), (1, 2));
+
+        let mut buffer = Buffer { text: vec!["\t- item".to_string()], cursor_x: 7, ..Buffer::new(None) };
+        buffer.split_line(false);
+        assert_eq!(buffer.text, vec!["\t- item", ""]);
+        assert_eq!(buffer.cursor_x, 0);
+    }
+
+    #[test]
+    fn changed_lines_are_marked_until_saved() {
+        let mut buffer = Buffer { text: lines(&["a", "b", "c"]), saved_text: lines(&["a", "b", "c"]), ..Buffer::new(None) };
+        assert_eq!(buffer.line_changes(), vec![None, None, None]);
+
+        // a new line in the middle only marks itself, the ones below just moved
+        buffer.text.insert(1, "new".to_string());
+        assert_eq!(buffer.line_changes(), vec![None, Some(LineChange::Added), None, None]);
+        buffer.text[2].push('!');
+        assert_eq!(buffer.line_changes(), vec![None, Some(LineChange::Modified), Some(LineChange::Added), None]);
+
+        buffer.text = lines(&["a", "b", "c", "d"]);
+        assert_eq!(buffer.line_changes(), vec![None, None, None, Some(LineChange::Added)]);
+        buffer.text = lines(&["a"]);
+        assert_eq!(buffer.line_changes(), vec![None]);
+
+        let path = std::env::temp_dir().join(format!("trmnotes-test-line-changes-{}.txt", std::process::id()));
+        buffer.text = lines(&["x", "b"]);
+        buffer.save(&path).unwrap();
+        assert_eq!(buffer.line_changes(), vec![None, None]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn up_and_down_remember_the_column_through_short_lines() {
+        let mut buffer = Buffer { text: lines(&["0123456789", "ab", "0123456789"]), ..Buffer::new(None) };
+        buffer.cursor_x = 8;
+        buffer.move_down();
+        assert_eq!((buffer.cursor_y, buffer.cursor_x), (1, 2));
+        buffer.move_down();
+        assert_eq!((buffer.cursor_y, buffer.cursor_x), (2, 8));
+        buffer.move_up();
+        buffer.move_up();
+        assert_eq!((buffer.cursor_y, buffer.cursor_x), (0, 8));
+
+        // moving sideways on the short line makes that the new column
+        buffer.move_down();
+        buffer.move_left();
+        buffer.move_down();
+        assert_eq!((buffer.cursor_y, buffer.cursor_x), (2, 1));
+    }
+
+    #[test]
+    fn trailing_newlines_survive_a_round_trip_unless_normalized() {
+        let path = std::env::temp_dir().join(format!("trmnotes-test-trailing-{}.txt", std::process::id()));
+        for content in ["no newline", "two newlines\n\n", "one\n"] {
+            fs::write(&path, content).unwrap();
+            let mut buffer = Buffer::new(None);
+            buffer.load(&path).unwrap();
+            buffer.save(&path).unwrap();
+            assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        }
+
+        for content in ["no newline", "two newlines\n\n\n"] {
+            fs::write(&path, content).unwrap();
+            let mut buffer = Buffer::new(None);
+            buffer.load(&path).unwrap();
+            buffer.cursor_y = buffer.text.len() - 1;
+            buffer.single_trailing_newline();
+            buffer.save(&path).unwrap();
+            assert_eq!(fs::read_to_string(&path).unwrap(), format!("{}\n", content.trim_end()));
+            assert!(buffer.cursor_y < buffer.text.len());
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn deleting_everything_leaves_one_empty_line() {
+        let mut buffer = Buffer::new(None);
+        buffer.text = lines(&["first", "", "third line"]);
+        buffer.select_all();
+        buffer.delete();
+        assert_eq!(buffer.text, lines(&[""]));
+        assert_eq!((buffer.cursor_x, buffer.cursor_y), (0, 0));
+
+        buffer.text = lines(&["ab", "c"]);
+        buffer.delete_line();
+        buffer.delete_line();
+        assert_eq!(buffer.text, lines(&[""]));
+        buffer.delete_back();
+        buffer.delete();
+        assert_eq!(buffer.text, lines(&[""]));
+
+        // even if something did leave it empty, the next edit (or undo) puts the line back instead of panicking
+        buffer.text.clear();
+        buffer.cursor_y = 3;
+        buffer.delete_back();
+        assert_eq!(buffer.text, lines(&[""]));
+        assert_eq!(buffer.cursor_y, 0);
+        buffer.undo_stack.push((vec![], 5, 2));
+        buffer.undo();
+        assert_eq!(buffer.text, lines(&[""]));
+        assert_eq!((buffer.cursor_x, buffer.cursor_y), (0, 0));
+    }
+
+    #[test]
+    fn deleting_words_back_and_forward() {
+        let mut buffer = Buffer::new(None);
+        buffer.text = lines(&["first second  third", "next"]);
+        buffer.cursor_x = 14; // right before "third"
+        buffer.delete_word_left();
+        assert_eq!(buffer.text, lines(&["first third", "next"]));
+        assert_eq!(buffer.cursor_x, 6);
+        buffer.delete_word_right();
+        assert_eq!(buffer.text, lines(&["first ", "next"]));
+        assert_eq!(buffer.cursor_x, 6);
+
+        // at the end of a line it's the line break that goes, then the next word
+        buffer.delete_word_right();
+        assert_eq!(buffer.text, lines(&["first next"]));
+        buffer.undo();
+        buffer.undo();
+        assert_eq!(buffer.text, lines(&["first third", "next"]));
+        assert_eq!((buffer.cursor_x, buffer.cursor_y), (6, 0));
+
+        // at the start of a line it joins onto the line above, leaving the cursor where they meet
+        buffer.cursor_y = 1;
+        buffer.cursor_x = 0;
+        buffer.delete_word_left();
+        assert_eq!(buffer.text, lines(&["first thirdnext"]));
+        assert_eq!((buffer.cursor_x, buffer.cursor_y), (11, 0));
+
+        // multibyte chars are fine, everything counts in chars
+        buffer.text = lines(&["héllo wörld"]);
+        buffer.cursor_x = 11;
+        buffer.delete_word_left();
+        assert_eq!(buffer.text, lines(&["héllo "]));
+        buffer.cursor_x = 0;
+        buffer.delete_word_left(); // nothing before it, nothing to do
+        assert_eq!(buffer.text, lines(&["héllo "]));
+    }
+
+    #[test]
+    fn tab_indents_every_selected_line() {
+        let mut buffer = Buffer { text: lines(&["one", "", "three", "four"]), ..Buffer::new(None) };
+        buffer.selection_anchor = Some((0, 1));
+        buffer.cursor_y = 2;
+        buffer.cursor_x = 3;
+        buffer.indent(4);
+        assert_eq!(buffer.text, lines(&["    one", "", "    three", "four"]));
+        // still the same chars selected, just moved over by the indent
+        assert_eq!(buffer.selection(), Some(((0, 5), (2, 7))));
+        assert_eq!(buffer.selected_text().as_deref(), Some("ne\n\n    thr"));
+
+        // one undo step for the whole thing
+        buffer.undo();
+        assert_eq!(buffer.text, lines(&["one", "", "three", "four"]));
+
+        // a selection ending at the start of a line leaves that line alone
+        buffer.selection_anchor = Some((2, 0));
+        buffer.cursor_y = 3;
+        buffer.cursor_x = 0;
+        buffer.uses_hard_tabs = true;
+        buffer.indent(4);
+        assert_eq!(buffer.text, lines(&["one", "", "\tthree", "four"]));
+        assert_eq!(buffer.selection(), Some(((2, 0), (3, 0))));
+    }
+
+    #[test]
+    fn shift_tab_dedents_selected_lines_as_far_as_each_can_go() {
+        let mut buffer = Buffer { text: lines(&["      six", "  two", "none", "\t\ttabs"]), ..Buffer::new(None) };
+        buffer.selection_anchor = Some((3, 6));
+        buffer.cursor_y = 0;
+        buffer.cursor_x = 2; // in the middle of the indent
+        buffer.dedent(4);
+        assert_eq!(buffer.text, lines(&["  six", "two", "none", "\ttabs"]));
+        assert_eq!(buffer.selection(), Some(((0, 0), (3, 5))));
+
+        buffer.dedent(4);
+        buffer.dedent(4);
+        assert_eq!(buffer.text, lines(&["six", "two", "none", "tabs"]));
+        // nothing left to remove, so nothing was added to undo either
+        buffer.undo();
+        assert_eq!(buffer.text, lines(&["  six", "two", "none", "\ttabs"]));
+    }
+
+    #[test]
+    fn checkboxes_toggle_and_get_inserted_into_lines() {
+        let mut buffer = Buffer { text: vec!["  - [ ] milk".to_string(), "eggs".to_string()], ..Buffer::new(None) };
+        assert!(buffer.toggle_checkbox());
+        assert_eq!(buffer.text[0], "  - [x] milk");
+        assert!(buffer.dirty);
+        assert!(buffer.toggle_checkbox());
+        assert_eq!(buffer.text[0], "  - [ ] milk");
+        assert!(!buffer.insert_checkbox()); // it's a task already
+
+        // a line with text gets the whole marker, the cursor stays on the same char
+        buffer.set_cursor(1, 2);
+        assert!(!buffer.toggle_checkbox());
+        assert!(buffer.insert_checkbox());
+        assert_eq!(buffer.text[1], "- [ ] eggs");
+        assert_eq!(buffer.cursor(), (1, 8));
+        buffer.undo();
+        assert_eq!(buffer.text[1], "eggs");
+
+        // a list item just gets the box
+        buffer.text[1] = "\t3. bread".to_string();
+        buffer.set_cursor(1, 0);
+        assert!(buffer.insert_checkbox());
+        assert_eq!(buffer.text[1], "\t3. [ ] bread");
+        assert_eq!(buffer.cursor(), (1, 0));
+    }
+
+    #[test]
+    fn the_cursor_is_kept_on_text_that_got_shorter() {
+        let mut buffer = Buffer { text: vec!["one".to_string(), "two".to_string(), "three".to_string()], ..Buffer::new(None) };
+        buffer.cursor_y = 2;
+        buffer.cursor_x = 5;
+        buffer.selection_anchor = Some((1, 3));
+        buffer.text = vec!["a".to_string()];
+        buffer.clamp_cursor();
+        assert_eq!(buffer.cursor(), (0, 1));
+        assert_eq!(buffer.selection_anchor, Some((0, 1)));
+    }
+
+    #[test]
+    fn the_quick_paths_for_plain_lines_agree_with_the_slow_ones() {
+        for line in ["", "  two  words ", "a\x0bb\x0cc", "tab\there", "ünïcode and  spaces", "\t\tx"] {
+            assert_eq!(word_count(line), line.split_whitespace().count(), "{:?}", line);
+            let chars = line.chars().count();
+            for x in 0..chars + 2 {
+                assert_eq!(byte_offset(line, x), line.char_indices().nth(x).map_or(line.len(), |(i, _)| i));
+                let width = line.chars().take(x).fold(0, |col, c| if c == '\t' { next_tab_stop(col, 4) } else { col + 1 });
+                assert_eq!(display_width(line, x, 4), width, "{:?} {}", line, x);
+                assert_eq!(char_at_column(line, width, 4), x.min(chars), "{:?} {}", line, x);
+            }
+        }
+    }
+
+    #[test]
+    fn auto_pairs_close_brackets_and_step_over_and_delete_them_together() {
+        let mut buffer = Buffer::new(None);
+        buffer.type_char('(', false, true);
+        assert_eq!((buffer.text[0].as_str(), buffer.cursor_x), ("()", 1));
+        buffer.type_char('x', true, true);
+        buffer.type_char(')', true, true); // steps over the one that's already there
+        assert_eq!((buffer.text[0].as_str(), buffer.cursor_x), ("(x)", 3));
+        buffer.undo();
+        assert_eq!(buffer.text, lines(&[""])); // all of it was one undo step
+
+        buffer.type_char('[', false, true);
+        buffer.delete_back_paired(true);
+        assert_eq!(buffer.text, lines(&[""]));
+        buffer.type_char('[', false, false);
+        buffer.type_char(']', true, false);
+        buffer.move_left();
+        buffer.delete_back_paired(false);
+        assert_eq!(buffer.text, lines(&["]"]));
+    }
+
+    #[test]
+    fn find_and_replace_keep_the_cursor_on_its_text() {
+        let mut buffer = Buffer { text: lines(&["cat and cat", "no match", "écat"]), ..Buffer::new(None) };
+        let matches = buffer.find("cat");
+        assert_eq!(matches, vec![(0, 0), (0, 8), (2, 1)]);
+        assert!(buffer.find("").is_empty());
+
+        buffer.replace_at(matches[1], "cat", "dog");
+        assert_eq!(buffer.text[0], "cat and dog");
+        assert_eq!(buffer.cursor(), (0, 11));
+
+        buffer.set_cursor(0, 6); // on the "d" of "and"
+        let matches = buffer.find("a");
+        buffer.replace_all(&matches, "a", "AA");
+        assert_eq!(buffer.text, lines(&["cAAt AAnd dog", "no mAAtch", "écAAt"]));
+        assert_eq!(buffer.cursor(), (0, 8)); // still on the "d"
+        buffer.undo();
+        assert_eq!(buffer.text, lines(&["cat and dog", "no match", "écat"]));
+    }
+}
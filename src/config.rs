@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/*
+Explanation of the code:
+This is everything the user can change through the config file.
+It lives at `<config dir>/trmnotes/trmnotes.toml` (so `~/.config/trmnotes/trmnotes.toml` on Linux).
+Every field is optional in the file, anything left out just uses the default below.
+
+Example:
+    notes_folder = "~/notes/"
+    tab_width = 2
+    hard_tabs = false       # indent new notes with tabs instead of spaces, existing ones keep whatever they use
+    autosave_secs = 60      # 0 turns autosave off
+    theme = "dark"
+    title = "Editor"        # shown before the tabs at the top of the editor, "" for just the tabs
+    explorer_width = 13     # percent of the screen the explorer takes up, kept between 5 and 50
+    sort = "name"           # how notes are ordered: "name", "modified" (newest first) or "size" (biggest first)
+    extensions = ["txt", "md", "markdown"]  # what shows up in the explorer
+    trim_trailing_on_save = true            # strip spaces/tabs at the end of lines when saving
+    single_trailing_newline = true          # save notes ending in exactly one newline, otherwise the file's own ending is kept
+    welcome_note = true                     # on the first run (no notes yet) start with a default.txt explaining the basics
+    max_file_mb = 10                        # bigger files are refused instead of freezing the app, 0 means no limit
+    warn_lines = 500                        # say so in the status line when a note gets longer than this, leave out for no warning
+    warn_chars = 20000                      # same for chars, it's only a nudge to split the note, editing carries on
+    keep_backups = 2                        # on save, keep the last 2 versions as note.txt.bak.1 and .bak.2, 0 keeps none
+    smart_home_end = true                   # with wrapping on, Home/End go to the start/end of the row on screen
+    date_format = "%Y-%m-%d %H:%M"          # what Ctrl+T inserts, %Y %m %d %H %M %S are replaced
+    auto_indent = true                      # Enter keeps the indentation (and list marker) of the line above
+    auto_pairs = true                       # typing ( [ { " or ` adds the closing one too
+    vim_mode = false                        # start in a vim-like normal mode: h/j/k/l, x, dd, o/O, and i/a to type
+    visual_bell = true                      # flash the editor border when a key can't do anything (like Left at the very start)
+    relative_line_numbers = false           # start with line numbers counting from the cursor line (like vim's relativenumber), Alt+N switches
+    ruler_column = 80                       # shade the column after 80 chars as a guide, leave it out for no ruler
+    writing_width = 80                      # how wide the centered column of text is in writing mode (Alt+C)
+
+    [keys]                                  # change what keys do, see keymap.rs for every action and its default
+    save = "ctrl+w"
+*/
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub notes_folder: String,
+    pub tab_width: usize,
+    pub hard_tabs: bool,
+    pub autosave_secs: u64,
+    pub theme: String,
+    pub title: String,
+    pub explorer_width: u16,
+    pub sort: String,
+    pub extensions: Vec<String>,
+    pub trim_trailing_on_save: bool,
+    pub single_trailing_newline: bool,
+    pub welcome_note: bool,
+    pub max_file_mb: u64,
+    pub warn_lines: Option<usize>,
+    pub warn_chars: Option<usize>,
+    pub keep_backups: usize,
+    pub smart_home_end: bool,
+    pub date_format: String,
+    pub auto_indent: bool,
+    pub auto_pairs: bool,
+    pub vim_mode: bool,
+    pub visual_bell: bool,
+    pub relative_line_numbers: bool,
+    pub ruler_column: Option<usize>,
+    pub writing_width: usize,
+    pub keys: BTreeMap<String, KeySpecs>,
+}
+
+// The keys for one action under [keys], either one ("ctrl+s") or a list (["ctrl+tab", "alt+right"])
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum KeySpecs {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeySpecs {
+    pub fn specs(&self) -> Vec<&str> {
+        match self {
+            KeySpecs::One(spec) => vec![spec.as_str()],
+            KeySpecs::Many(specs) => specs.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+impl Default for Config {
+    // These are the same values the app used before there was a config file
+    fn default() -> Self {
+        Config {
+            notes_folder: String::from("./notes/"),
+            tab_width: 4,
+            hard_tabs: false,
+            autosave_secs: 30,
+            theme: String::from("dark"),
+            title: String::from("Editor"),
+            explorer_width: 13,
+            sort: String::from("name"),
+            extensions: vec![String::from("txt"), String::from("md"), String::from("markdown")],
+            trim_trailing_on_save: false,
+            single_trailing_newline: false,
+            welcome_note: true,
+            max_file_mb: 10,
+            warn_lines: None,
+            warn_chars: None,
+            keep_backups: 0,
+            smart_home_end: false,
+            date_format: String::from("%Y-%m-%d"),
+            auto_indent: true,
+            auto_pairs: false,
+            vim_mode: false,
+            visual_bell: true,
+            relative_line_numbers: false,
+            ruler_column: None,
+            writing_width: 80,
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    // Where the config file is expected to be, None if we can't figure out the config dir
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("trmnotes").join("trmnotes.toml"))
+    }
+
+    /*
+    Loads the config from the usual place.
+    A missing file is normal (defaults are used), but a broken one gets a warning printed.
+    This runs before the terminal is taken over, so the warning is actually visible.
+     */
+    pub fn load() -> Config {
+        let Some(path) = Config::path() else {
+            return Config::default();
+        };
+        match Config::load_from(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Warning: ignoring config file {}: {}", path.display(), e);
+                Config::default()
+            }
+        }
+    }
+
+    // Reads a config file, if there isn't one we just use the defaults
+    pub fn load_from(path: &Path) -> Result<Config, String> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Config::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Config, String> {
+        toml::from_str(content).map_err(|e| e.to_string())
+    }
+
+    // autosave_secs = 0 in the file means autosave is off
+    pub fn autosave(&self) -> Option<u64> {
+        if self.autosave_secs == 0 { None } else { Some(self.autosave_secs) }
+    }
+
+    // explorer_width, kept to something that leaves room for both panels
+    pub fn explorer_percent(&self) -> u16 {
+        self.explorer_width.clamp(5, 50)
+    }
+
+    // max_file_mb in bytes, None if there's no limit
+    pub fn max_file_bytes(&self) -> Option<u64> {
+        if self.max_file_mb == 0 { None } else { Some(self.max_file_mb * 1024 * 1024) }
+    }
+
+    // Expands a leading `~` in the notes folder to the home directory
+    pub fn folder(&self) -> String {
+        match (self.notes_folder.strip_prefix("~/"), dirs::home_dir()) {
+            (Some(rest), Some(home)) => home.join(rest).display().to_string(),
+            _ => self.notes_folder.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_sample_config() {
+        let config = Config::parse(
+            r#"
+            notes_folder = "/tmp/my-notes/"
+            tab_width = 2
+            autosave_secs = 0
+            theme = "light"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.notes_folder, "/tmp/my-notes/");
+        assert_eq!(config.tab_width, 2);
+        assert_eq!(config.autosave(), None);
+        assert_eq!(config.theme, "light");
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let config = Config::parse("tab_width = 8").unwrap();
+        assert_eq!(config.tab_width, 8);
+        assert_eq!(config.notes_folder, Config::default().notes_folder);
+        assert_eq!(config.autosave(), Some(30));
+        assert_eq!(config.extensions, vec!["txt", "md", "markdown"]);
+        assert_eq!(config.explorer_percent(), 13);
+        assert_eq!(Config::parse("explorer_width = 90").unwrap().explorer_percent(), 50);
+        assert_eq!(Config::parse("explorer_width = 0").unwrap().explorer_percent(), 5);
+    }
+
+    #[test]
+    fn missing_file_falls_back_to_defaults() {
+        let path = std::env::temp_dir().join("trmnotes-test-does-not-exist.toml");
+        assert_eq!(Config::load_from(&path), Ok(Config::default()));
+    }
+
+    #[test]
+    fn malformed_file_is_an_error() {
+        assert!(Config::parse("tab_width = \"four\"").is_err());
+        assert!(Config::parse("this is not toml").is_err());
+    }
+
+    #[test]
+    fn keys_can_be_one_or_a_list() {
+        let config = Config::parse("[keys]\nsave = \"ctrl+w\"\nnext_tab = [\"ctrl+tab\", \"alt+l\"]").unwrap();
+        assert_eq!(config.keys["save"].specs(), vec!["ctrl+w"]);
+        assert_eq!(config.keys["next_tab"].specs(), vec!["ctrl+tab", "alt+l"]);
+        assert!(Config::parse("[keys]\nsave = 4").is_err());
+    }
+}
@@ -0,0 +1,103 @@
+/*
+Explanation of the code:
+The matching behind the fuzzy note finder (Ctrl+P). A query matches a name if its chars show up in the name
+in the same order (ignoring case), not necessarily next to each other, so "wtd" matches "work/todo.txt".
+Every way the query could line up with the name gets a score and the best one wins:
+    - each matched char is worth a point
+    - a char right after the previous match is worth a lot more, so "todo" prefers "todo.txt" over "t_o_d_o.txt"
+    - so is a char starting a word (after / _ - . or a space, or a capital in camelCase)
+    - chars skipped between two matches cost a little
+It's small and simple, but notes folders aren't big enough for that to matter.
+*/
+
+const MATCH: i32 = 1;
+const CONSECUTIVE: i32 = 10;
+const WORD_START: i32 = 8;
+const MAX_GAP_PENALTY: i32 = 5;
+
+/*
+How well `query` matches `name`, and which chars (as char indexes into `name`) matched.
+None if it doesn't match at all, an empty query matches everything with a score of 0.
+ */
+pub fn fuzzy_match(query: &str, name: &str) -> Option<(i32, Vec<usize>)> {
+    let query: Vec<char> = query.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    if query.is_empty() {
+        return Some((0, vec![]));
+    }
+
+    // best[i][j] is the best score for the first i+1 query chars with the last one matched at j,
+    // together with where the one before it matched so the positions can be followed back
+    let mut best: Vec<Vec<Option<(i32, usize)>>> = vec![vec![None; name.len()]; query.len()];
+    for (i, &q) in query.iter().enumerate() {
+        for j in 0..name.len() {
+            if !same_char(q, name[j]) {
+                continue;
+            }
+            let bonus = MATCH + if is_word_start(&name, j) { WORD_START } else { 0 };
+            if i == 0 {
+                best[i][j] = Some((bonus, j));
+                continue;
+            }
+            best[i][j] = (0..j)
+                .filter_map(|k| best[i - 1][k].map(|(score, _)| (score, k)))
+                .map(|(score, k)| {
+                    let gap = (j - k - 1) as i32;
+                    let link = if gap == 0 { CONSECUTIVE } else { -gap.min(MAX_GAP_PENALTY) };
+                    (score + bonus + link, k)
+                })
+                .max_by_key(|&(score, k)| (score, std::cmp::Reverse(k)));
+        }
+    }
+
+    let last = query.len() - 1;
+    let (mut j, (score, _)) = best[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, entry)| entry.map(|entry| (j, entry)))
+        .max_by_key(|&(j, (score, _))| (score, std::cmp::Reverse(j)))?;
+    let mut positions = vec![0; query.len()];
+    for i in (0..query.len()).rev() {
+        positions[i] = j;
+        j = best[i][j].map_or(j, |(_, previous)| previous);
+    }
+    Some((score, positions))
+}
+
+fn same_char(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+// true if the char at `j` starts a word, so matching it counts for more
+fn is_word_start(name: &[char], j: usize) -> bool {
+    match j.checked_sub(1).map(|before| name[before]) {
+        None => true,
+        Some(before) => matches!(before, '/' | '_' | '-' | '.' | ' ') || (before.is_lowercase() && name[j].is_uppercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_chars_in_order_ignoring_case() {
+        assert_eq!(fuzzy_match("tdo", "todo.txt").map(|(_, positions)| positions), Some(vec![0, 2, 3]));
+        assert!(fuzzy_match("TODO", "todo.txt").is_some());
+        assert!(fuzzy_match("xd", "todo.txt").is_none()); // right chars, wrong order
+        assert!(fuzzy_match("x", "").is_none());
+        assert_eq!(fuzzy_match("", "anything.txt"), Some((0, vec![])));
+    }
+
+    #[test]
+    fn prefers_word_starts_and_runs_of_chars() {
+        // the t in "notes" comes first, but the one starting "todo" is the better match
+        assert_eq!(fuzzy_match("todo", "notes/todo.txt").map(|(_, positions)| positions), Some(vec![6, 7, 8, 9]));
+        assert_eq!(fuzzy_match("wt", "work/todo.txt").map(|(_, positions)| positions), Some(vec![0, 5]));
+        assert_eq!(fuzzy_match("mn", "meetingNotes.md").map(|(_, positions)| positions), Some(vec![0, 7]));
+
+        let score = |query, name| fuzzy_match(query, name).unwrap().0;
+        assert!(score("todo", "todo.txt") > score("todo", "t_o_d_o.txt"));
+        assert!(score("ab", "a-b.txt") > score("ab", "a-----------b.txt"));
+    }
+}
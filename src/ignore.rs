@@ -0,0 +1,179 @@
+/*
+Explanation of the code:
+A `.trmnotesignore` file in a folder hides files from the explorer without touching them,
+handy for folders shared with other tools. There's one glob pattern per line:
+    *.bak       # hides every .bak file
+    todo.txt    # hides just that one
+    drafts/     # a trailing / only matches folders
+`*` is any number of chars, `?` is one char and `[abc]`/`[a-z]` (or `[!abc]`) is one char from a set.
+Patterns match the name of each entry in that folder, blank lines and lines starting with `#` are skipped.
+*/
+pub const IGNORE_FILE: &str = ".trmnotesignore";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IgnoreList {
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pattern {
+    tokens: Vec<Token>,
+    only_folders: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Char(char),
+    AnyChar,  // ?
+    AnyChars, // *
+    Set { ranges: Vec<(char, char)>, negated: bool }, // [a-z], a single char is a range from itself to itself
+}
+
+impl IgnoreList {
+    /*
+    Reads the patterns from the contents of an ignore file.
+    Also gives back the lines that aren't valid patterns, so they can be reported.
+     */
+    pub fn parse(content: &str) -> (IgnoreList, Vec<String>) {
+        let mut list = IgnoreList::default();
+        let mut invalid = vec![];
+        for line in content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            match Pattern::parse(line) {
+                Some(pattern) => list.patterns.push(pattern),
+                None => invalid.push(line.to_string()),
+            }
+        }
+        (list, invalid)
+    }
+
+    // true if the entry called `name` should be hidden
+    pub fn is_ignored(&self, name: &str, is_folder: bool) -> bool {
+        let chars: Vec<char> = name.chars().collect();
+        self.patterns.iter().any(|pattern| (is_folder || !pattern.only_folders) && matches(&pattern.tokens, &chars))
+    }
+}
+
+impl Pattern {
+    // None if the pattern is broken, like an unclosed `[`
+    fn parse(line: &str) -> Option<Pattern> {
+        let (line, only_folders) = match line.strip_suffix('/') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let mut tokens = vec![];
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '*' => Token::AnyChars,
+                '?' => Token::AnyChar,
+                '[' => {
+                    let negated = chars.next_if(|c| *c == '!' || *c == '^').is_some();
+                    let mut ranges = vec![];
+                    loop {
+                        match chars.next()? {
+                            ']' if !ranges.is_empty() => break,
+                            ']' => return None, // [] can't match anything
+                            start => {
+                                // a `-` between two chars makes a range, anywhere else it's just a `-`
+                                let end = match chars.peek() {
+                                    Some('-') => {
+                                        chars.next();
+                                        match chars.next()? {
+                                            ']' => {
+                                                ranges.push((start, start));
+                                                ranges.push(('-', '-'));
+                                                break;
+                                            }
+                                            end => end,
+                                        }
+                                    }
+                                    _ => start,
+                                };
+                                if end < start {
+                                    return None;
+                                }
+                                ranges.push((start, end));
+                            }
+                        }
+                    }
+                    Token::Set { ranges, negated }
+                }
+                c => Token::Char(c),
+            });
+        }
+        if tokens.is_empty() { None } else { Some(Pattern { tokens, only_folders }) }
+    }
+}
+
+/*
+Whether `tokens` match all of `name`.
+A `*` first tries to match nothing, and if the rest doesn't fit we come back and let it take one more char.
+Only the last `*` ever needs to be retried, which keeps this linear-ish instead of exponential.
+ */
+fn matches(tokens: &[Token], name: &[char]) -> bool {
+    let (mut t, mut n) = (0, 0);
+    let mut retry: Option<(usize, usize)> = None; // (token after the last *, name position it started from)
+    while n < name.len() {
+        let fits = match tokens.get(t) {
+            Some(Token::AnyChars) => {
+                retry = Some((t + 1, n));
+                t += 1;
+                continue;
+            }
+            Some(Token::AnyChar) => true,
+            Some(Token::Char(c)) => *c == name[n],
+            Some(Token::Set { ranges, negated }) => {
+                ranges.iter().any(|(start, end)| (*start..=*end).contains(&name[n])) != *negated
+            }
+            None => false,
+        };
+        if fits {
+            t += 1;
+            n += 1;
+        } else if let Some((after_star, from)) = retry {
+            t = after_star;
+            n = from + 1;
+            retry = Some((after_star, from + 1));
+        } else {
+            return false;
+        }
+    }
+    tokens[t..].iter().all(|token| *token == Token::AnyChars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_and_literal_names_are_ignored() {
+        let (list, invalid) = IgnoreList::parse("# backups\n*.bak\n\ntodo.txt\ndrafts/\nnote-[0-9]?.md\n");
+        assert!(invalid.is_empty());
+        assert!(list.is_ignored("old.bak", false));
+        assert!(list.is_ignored(".bak", false));
+        assert!(!list.is_ignored("old.bak.txt", false));
+        assert!(list.is_ignored("todo.txt", false));
+        assert!(!list.is_ignored("todo.txt.md", false));
+        assert!(list.is_ignored("drafts", true));
+        assert!(!list.is_ignored("drafts", false));
+        assert!(list.is_ignored("note-12.md", false));
+        assert!(!list.is_ignored("note-a2.md", false));
+    }
+
+    #[test]
+    fn broken_patterns_are_skipped_and_reported() {
+        let (list, invalid) = IgnoreList::parse("[abc\n*.tmp\n[]\n[z-a]");
+        assert_eq!(invalid, vec!["[abc", "[]", "[z-a]"]);
+        assert!(list.is_ignored("x.tmp", false));
+        assert!(!list.is_ignored("a", false));
+    }
+
+    #[test]
+    fn stars_can_match_across_repeated_text() {
+        let (list, _) = IgnoreList::parse("a*b*c\n[!.]*");
+        assert!(list.is_ignored("aXbYbZc", false));
+        assert!(list.is_ignored("zzz", false));
+        let (list, _) = IgnoreList::parse("a*b*c");
+        assert!(!list.is_ignored("aXbYbZ", false));
+    }
+}
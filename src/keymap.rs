@@ -0,0 +1,411 @@
+use std::collections::BTreeMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::config::KeySpecs;
+
+/*
+Explanation of the code:
+Which key does what. Every command the editor has (saving, opening notes, undo, ...) is an `Action`,
+and the keymap says which keys run it. Moving the cursor and typing aren't in here, those keys are fixed.
+The defaults are in `DEFAULT_KEYS`, and any of them can be changed under `[keys]` in the config file:
+    [keys]
+    save = "ctrl+w"                     # one key
+    next_tab = ["ctrl+tab", "alt+l"]    # or a few
+    export_html = []                    # or none at all
+A key is modifiers and a key joined with +, like "ctrl+s", "alt+shift+up" or "f5".
+Giving an action keys replaces all of its default ones, and a key can only do one thing,
+so taking a key another action had by default takes it away from that action (with a warning).
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Save,
+    SaveAs,
+    Quit,
+    ToggleExplorer,
+    NewNote,
+    OpenNote,
+    FindNote,
+    CommandPalette,
+    Refresh,
+    NextTab,
+    PreviousTab,
+    PreviousNote,
+    SplitView,
+    SwitchPane,
+    Rename,
+    Revert,
+    DeleteNote,
+    Find,
+    Replace,
+    GoToLine,
+    Outline,
+    ReadOnly,
+    CycleSort,
+    ToggleWrap,
+    WritingMode,
+    ToggleMarkdown,
+    RelativeNumbers,
+    ShowWhitespace,
+    EditExternally,
+    ExportHtml,
+    Help,
+    Info,
+    Undo,
+    Redo,
+    Copy,
+    Cut,
+    Paste,
+    SelectAll,
+    DuplicateLine,
+    DeleteToEnd,
+    DeleteWordBack,
+    DeleteWordForward,
+    MoveLineUp,
+    MoveLineDown,
+    InsertDate,
+    InsertCheckbox,
+    ToggleCheckbox,
+}
+
+// Every action with its name in the config file, what the help calls it and its default keys, in the order they're listed in the help
+pub const DEFAULT_KEYS: &[(Action, &str, &str, &[&str])] = &[
+    (Action::Save, "save", "Save", &["ctrl+s"]),
+    // most terminals send Ctrl+Shift+S as plain Ctrl+S, so there's Alt+A too
+    (Action::SaveAs, "save_as", "Save As", &["ctrl+shift+s", "alt+a"]),
+    (Action::Quit, "quit", "Quit", &["ctrl+q"]),
+    (Action::ToggleExplorer, "toggle_explorer", "Toggle Explorer", &["ctrl+e"]),
+    (Action::NewNote, "new_note", "Create New Note", &["ctrl+n"]),
+    (Action::OpenNote, "open_note", "Open Note", &["ctrl+o"]),
+    (Action::FindNote, "find_note", "Find Note", &["ctrl+p"]),
+    // most terminals send Ctrl+Shift+P as plain Ctrl+P, so there's F2 too
+    (Action::CommandPalette, "command_palette", "Command Palette", &["ctrl+shift+p", "f2"]),
+    (Action::Refresh, "refresh", "Refresh Files", &["f5"]),
+    // some terminals never send Ctrl+Tab so Alt+Left/Right work too
+    (Action::NextTab, "next_tab", "Next Tab", &["ctrl+tab", "alt+right"]),
+    (Action::PreviousTab, "previous_tab", "Previous Tab", &["ctrl+shift+tab", "alt+left"]),
+    (Action::PreviousNote, "previous_note", "Previous Note", &["alt+o"]),
+    (Action::SplitView, "split_view", "Split View Side By Side", &["alt+v"]),
+    (Action::SwitchPane, "switch_pane", "Switch Pane", &["alt+p"]),
+    (Action::Rename, "rename", "Rename Note", &["ctrl+r"]),
+    (Action::Revert, "revert", "Revert To Saved", &["alt+r"]),
+    (Action::DeleteNote, "delete_note", "Delete Note", &["alt+delete"]),
+    (Action::Find, "find", "Find", &["ctrl+f"]),
+    (Action::Replace, "replace", "Find & Replace", &["ctrl+w"]),
+    (Action::GoToLine, "go_to_line", "Go To Line", &["ctrl+g"]),
+    (Action::Outline, "outline", "Outline (Headings)", &["alt+t"]),
+    (Action::ReadOnly, "read_only", "Toggle Read-Only", &["ctrl+l"]),
+    (Action::CycleSort, "cycle_sort", "Change File Order", &["alt+s"]),
+    (Action::ToggleWrap, "toggle_wrap", "Toggle Line Wrap", &["alt+z"]),
+    (Action::WritingMode, "writing_mode", "Writing Mode (Centered)", &["alt+c"]),
+    (Action::ToggleMarkdown, "toggle_markdown", "Toggle Markdown", &["alt+m"]),
+    (Action::RelativeNumbers, "relative_numbers", "Relative Numbers", &["alt+n"]),
+    (Action::ShowWhitespace, "show_whitespace", "Show Whitespace", &["alt+w"]),
+    (Action::EditExternally, "edit_externally", "Edit in $EDITOR", &["alt+e"]),
+    (Action::ExportHtml, "export_html", "Export to HTML", &["alt+x"]),
+    (Action::Help, "help", "Toggle Help Menu", &["ctrl+h"]),
+    // not Ctrl+I since terminals send that as Tab
+    (Action::Info, "info", "Note Info", &["alt+i"]),
+    (Action::Undo, "undo", "Undo", &["ctrl+z"]),
+    (Action::Redo, "redo", "Redo", &["ctrl+y"]),
+    (Action::Copy, "copy", "Copy", &["ctrl+c"]),
+    (Action::Cut, "cut", "Cut", &["ctrl+x"]),
+    (Action::Paste, "paste", "Paste", &["ctrl+v"]),
+    (Action::SelectAll, "select_all", "Select All", &["ctrl+a"]),
+    (Action::DuplicateLine, "duplicate_line", "Duplicate Line", &["ctrl+d"]),
+    (Action::DeleteToEnd, "delete_to_end", "Delete To Line End", &["ctrl+k"]),
+    // Alt+Backspace is there for terminals that don't send Ctrl+Backspace
+    (Action::DeleteWordBack, "delete_word_back", "Delete Word Back", &["ctrl+backspace", "alt+backspace"]),
+    (Action::DeleteWordForward, "delete_word_forward", "Delete Word Forward", &["ctrl+delete"]),
+    (Action::MoveLineUp, "move_line_up", "Move Line Up", &["alt+up"]),
+    (Action::MoveLineDown, "move_line_down", "Move Line Down", &["alt+down"]),
+    (Action::InsertDate, "insert_date", "Insert Date", &["ctrl+t"]),
+    (Action::InsertCheckbox, "insert_checkbox", "Make Line A Task", &["alt+b"]),
+    (Action::ToggleCheckbox, "toggle_checkbox", "Check/Uncheck Task", &["alt+d"]),
+];
+
+impl Action {
+    // The action called `name` in the config file
+    pub fn named(name: &str) -> Option<Action> {
+        DEFAULT_KEYS.iter().find(|(_, action_name, _, _)| *action_name == name).map(|(action, _, _, _)| *action)
+    }
+
+    pub fn name(self) -> &'static str {
+        DEFAULT_KEYS.iter().find(|(action, _, _, _)| *action == self).map_or("", |(_, name, _, _)| name)
+    }
+
+    /*
+    true if the action changes the text, those are ignored in read-only mode.
+    Replace is in here too since find and replace is all about changing the text,
+    and so is $EDITOR since the note gets saved before it opens.
+     */
+    pub fn edits(self) -> bool {
+        matches!(
+            self,
+            Action::Undo
+                | Action::Redo
+                | Action::Cut
+                | Action::Paste
+                | Action::Replace
+                | Action::DuplicateLine
+                | Action::DeleteToEnd
+                | Action::DeleteWordBack
+                | Action::DeleteWordForward
+                | Action::MoveLineUp
+                | Action::MoveLineDown
+                | Action::InsertDate
+                | Action::InsertCheckbox
+                | Action::ToggleCheckbox
+                | Action::EditExternally
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    bindings: Vec<(KeyCode, KeyModifiers, Action)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap::new(&BTreeMap::new()).0
+    }
+}
+
+impl KeyMap {
+    /*
+    The default keys with the ones from the config file (`[keys]`) on top.
+    Also gives back a warning for everything in there that couldn't be used (unknown actions, keys that
+    can't be read) and every key that was taken from another action, so they can be shown at startup.
+    An action only loses its default keys once one of its own keys could be read (or it's set to []),
+    so a typo can't leave something like quit without any key at all.
+     */
+    pub fn new(keys: &BTreeMap<String, KeySpecs>) -> (KeyMap, Vec<String>) {
+        let mut warnings = vec![];
+        let mut custom = vec![];
+        let mut replaced = vec![]; // the actions whose default keys make way for the custom ones
+        for (name, specs) in keys {
+            let Some(action) = Action::named(name) else {
+                warnings.push(format!("unknown action {:?} in [keys]", name));
+                continue;
+            };
+            let before = custom.len();
+            for spec in specs.specs() {
+                match parse_key(spec) {
+                    Ok((code, modifiers)) => custom.push((code, modifiers, action)),
+                    Err(e) => warnings.push(format!("can't use {:?} for {}: {}", spec, name, e)),
+                }
+            }
+            if specs.specs().is_empty() || custom.len() > before {
+                replaced.push(action);
+            } else {
+                warnings.push(format!("none of the keys for {} could be used, keeping its default keys", name));
+            }
+        }
+
+        // the defaults go first, then the custom keys take over any they collide with
+        let mut bindings: Vec<(KeyCode, KeyModifiers, Action)> = vec![];
+        for (action, _, _, specs) in DEFAULT_KEYS {
+            if !replaced.contains(action) {
+                bindings.extend(specs.iter().map(|spec| parse_key(spec).expect("default keys are valid")).map(|(code, modifiers)| (code, modifiers, *action)));
+            }
+        }
+        let mut defaults = bindings.len();
+        for (code, modifiers, action) in custom {
+            match bindings.iter().position(|binding| (binding.0, binding.1) == (code, modifiers)) {
+                Some(i) if i < defaults => {
+                    warnings.push(format!("{} was {} by default, now it's {}", key_label(code, modifiers), bindings[i].2.name(), action.name()));
+                    bindings.remove(i);
+                    defaults -= 1;
+                    bindings.push((code, modifiers, action));
+                }
+                Some(i) if bindings[i].2 != action => {
+                    warnings.push(format!("{} is set for both {} and {}, using {}", key_label(code, modifiers), bindings[i].2.name(), action.name(), bindings[i].2.name()));
+                }
+                Some(_) => {} // the same key twice for one action, no harm done
+                None => bindings.push((code, modifiers, action)),
+            }
+        }
+        (KeyMap { bindings }, warnings)
+    }
+
+    // The action a key runs, if it's bound to one
+    pub fn action(&self, key_event: &KeyEvent) -> Option<Action> {
+        let (code, modifiers) = normalize(key_event.code, key_event.modifiers);
+        let find = |modifiers: KeyModifiers| self.bindings.iter().find(|binding| (binding.0, binding.1) == (code, modifiers)).map(|binding| binding.2);
+        // Shift on its own doesn't make a different command, so Ctrl+Shift+S still saves (unless it's bound to something else)
+        find(modifiers).or_else(|| find(modifiers.difference(KeyModifiers::SHIFT)))
+    }
+
+    // The keys for an action as they're shown in the help, like "Ctrl+Tab / Alt+Right", or "(none)"
+    pub fn label(&self, action: Action) -> String {
+        let keys: Vec<String> = self.bindings.iter().filter(|binding| binding.2 == action).map(|binding| key_label(binding.0, binding.1)).collect();
+        if keys.is_empty() { "(none)".to_string() } else { keys.join(" / ") }
+    }
+}
+
+/*
+Reads a key like "ctrl+s" or "alt+shift+left" (ignoring case) into what crossterm would send for it.
+Only the F keys and Insert can go without Ctrl or Alt, anything else would get in the way of typing,
+and Esc is left alone since it's what closes every popup.
+ */
+pub fn parse_key(spec: &str) -> Result<(KeyCode, KeyModifiers), String> {
+    let spec = spec.trim().to_ascii_lowercase();
+    // "ctrl++" is Ctrl and the + key
+    let (modifier_part, key) = match spec.strip_suffix("++") {
+        Some(rest) => (rest, "+"),
+        None => spec.rsplit_once('+').unwrap_or(("", &spec)),
+    };
+    let mut modifiers = KeyModifiers::NONE;
+    for modifier in modifier_part.split('+').filter(|modifier| !modifier.is_empty()) {
+        modifiers |= match modifier {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" | "meta" | "option" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(format!("unknown modifier {:?}", modifier)),
+        };
+    }
+    let code = match key {
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "enter" | "return" => KeyCode::Enter,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "space" => KeyCode::Char(' '),
+        "esc" | "escape" => return Err("Esc always closes popups, it can't be bound".to_string()),
+        _ => match (key.chars().next(), key.chars().count()) {
+            (Some(c), 1) => KeyCode::Char(c),
+            _ => match key.strip_prefix('f').and_then(|number| number.parse::<u8>().ok()) {
+                Some(number @ 1..=12) => KeyCode::F(number),
+                _ => return Err(format!("unknown key {:?}", key)),
+            },
+        },
+    };
+    if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) && !matches!(code, KeyCode::F(_) | KeyCode::Insert) {
+        return Err("needs Ctrl or Alt, otherwise it would get in the way of typing".to_string());
+    }
+    Ok(normalize(code, modifiers))
+}
+
+/*
+Puts a key the way the keymap stores it, since terminals don't all send the same thing:
+Shift+Tab comes as BackTab (with or without Shift), and Shift+a as 'A' (with or without Shift).
+ */
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> (KeyCode, KeyModifiers) {
+    let modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+    match code {
+        KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => (KeyCode::BackTab, modifiers.difference(KeyModifiers::SHIFT)),
+        KeyCode::BackTab => (KeyCode::BackTab, modifiers.difference(KeyModifiers::SHIFT)),
+        KeyCode::Char(c) if c.is_uppercase() => (KeyCode::Char(c.to_lowercase().next().unwrap_or(c)), modifiers | KeyModifiers::SHIFT),
+        _ => (code, modifiers),
+    }
+}
+
+// How a key is shown to the user, like "Ctrl+S" or "Ctrl+Shift+Tab"
+pub fn key_label(code: KeyCode, modifiers: KeyModifiers) -> String {
+    let mut label = String::new();
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        label.push_str("Ctrl+");
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        label.push_str("Alt+");
+    }
+    if modifiers.contains(KeyModifiers::SHIFT) || code == KeyCode::BackTab {
+        label.push_str("Shift+");
+    }
+    let key = match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_uppercase().to_string(),
+        KeyCode::BackTab => "Tab".to_string(),
+        KeyCode::F(number) => format!("F{}", number),
+        code => format!("{:?}", code), // Tab, Backspace, Left, PageUp, ... are already named like that
+    };
+    label.push_str(&key);
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(config: &str) -> BTreeMap<String, KeySpecs> {
+        toml::from_str(config).unwrap()
+    }
+
+    #[test]
+    fn parses_key_specs() {
+        assert_eq!(parse_key("ctrl+s"), Ok((KeyCode::Char('s'), KeyModifiers::CONTROL)));
+        assert_eq!(parse_key(" Ctrl+Alt+Up "), Ok((KeyCode::Up, KeyModifiers::CONTROL | KeyModifiers::ALT)));
+        assert_eq!(parse_key("ctrl+shift+tab"), Ok((KeyCode::BackTab, KeyModifiers::CONTROL)));
+        assert_eq!(parse_key("alt+S"), Ok((KeyCode::Char('s'), KeyModifiers::ALT)));
+        assert_eq!(parse_key("ctrl++"), Ok((KeyCode::Char('+'), KeyModifiers::CONTROL)));
+        assert_eq!(parse_key("alt+space"), Ok((KeyCode::Char(' '), KeyModifiers::ALT)));
+        assert_eq!(parse_key("f5"), Ok((KeyCode::F(5), KeyModifiers::NONE)));
+        assert!(parse_key("ctl+s").is_err());
+        assert!(parse_key("ctrl+nope").is_err());
+        assert!(parse_key("f13").is_err());
+        assert!(parse_key("a").is_err()); // would stop you typing a's
+        assert!(parse_key("ctrl+esc").is_err());
+        assert!(DEFAULT_KEYS.iter().flat_map(|(_, _, _, specs)| specs.iter()).all(|spec| parse_key(spec).is_ok()));
+    }
+
+    #[test]
+    fn keys_are_looked_up_like_terminals_send_them() {
+        let keymap = KeyMap::default();
+        let action = |code, modifiers| keymap.action(&KeyEvent::new(code, modifiers));
+        assert_eq!(action(KeyCode::Char('s'), KeyModifiers::CONTROL), Some(Action::Save));
+        assert_eq!(action(KeyCode::Char('S'), KeyModifiers::CONTROL | KeyModifiers::SHIFT), Some(Action::SaveAs));
+        assert_eq!(action(KeyCode::Char('Q'), KeyModifiers::CONTROL | KeyModifiers::SHIFT), Some(Action::Quit));
+        assert_eq!(action(KeyCode::BackTab, KeyModifiers::CONTROL | KeyModifiers::SHIFT), Some(Action::PreviousTab));
+        assert_eq!(action(KeyCode::F(5), KeyModifiers::NONE), Some(Action::Refresh));
+        assert_eq!(action(KeyCode::Char('s'), KeyModifiers::NONE), None);
+        assert_eq!(action(KeyCode::Char('b'), KeyModifiers::CONTROL), None);
+        assert_eq!(keymap.label(Action::NextTab), "Ctrl+Tab / Alt+Right");
+        assert_eq!(keymap.label(Action::PreviousTab), "Ctrl+Shift+Tab / Alt+Left");
+    }
+
+    #[test]
+    fn config_keys_replace_the_defaults_and_warn_about_problems() {
+        let (keymap, warnings) = KeyMap::new(&keys(
+            r#"
+            save = "ctrl+w"
+            next_tab = ["ctrl+tab", "alt+l"]
+            export_html = []
+            sav = "ctrl+s"
+            quit = "ctl+q"
+            "#,
+        ));
+        let action = |code, modifiers| keymap.action(&KeyEvent::new(code, modifiers));
+        assert_eq!(action(KeyCode::Char('w'), KeyModifiers::CONTROL), Some(Action::Save));
+        assert_eq!(action(KeyCode::Char('s'), KeyModifiers::CONTROL), None); // save's default is gone
+        assert_eq!(action(KeyCode::Char('l'), KeyModifiers::ALT), Some(Action::NextTab));
+        assert_eq!(action(KeyCode::Right, KeyModifiers::ALT), None);
+        assert_eq!(action(KeyCode::Char('x'), KeyModifiers::ALT), None);
+        assert_eq!(keymap.label(Action::Replace), "(none)");
+        assert_eq!(keymap.label(Action::Quit), "Ctrl+Q"); // a typo keeps the default instead of leaving no way out
+        assert_eq!(action(KeyCode::Char('q'), KeyModifiers::CONTROL), Some(Action::Quit));
+        assert_eq!(
+            warnings,
+            vec![
+                "can't use \"ctl+q\" for quit: unknown modifier \"ctl\"",
+                "none of the keys for quit could be used, keeping its default keys",
+                "unknown action \"sav\" in [keys]",
+                "Ctrl+W was replace by default, now it's save",
+            ]
+        );
+
+        let (keymap, warnings) = KeyMap::new(&keys("find = \"ctrl+j\"\ngo_to_line = \"ctrl+j\""));
+        assert_eq!(keymap.action(&KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL)), Some(Action::Find));
+        assert_eq!(warnings, vec!["Ctrl+J is set for both find and go_to_line, using find"]);
+    }
+}
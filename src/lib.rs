@@ -0,0 +1,95 @@
+//! The editing core of trmnotes, usable without its terminal front-end.
+//!
+//! The binary (`main.rs`) is the app around all of this: the main loop, the panels, popups and key handling.
+//! Everything it edits with lives here, so another TUI can reuse it:
+//! - [`Buffer`] is one open note: its lines, the cursor and selection, every edit the keys make
+//!   (typing with auto pairs, `delete_back`, `split_line`, find and replace), undo/redo, and loading/saving
+//!   (keeping the file's line endings, trailing newline and indentation style).
+//! - [`markdown`] highlights lines and exports notes to HTML, [`fuzzy`] is the note finder's matching.
+//! - [`store`] is where notes are kept, the notes folder on disk or (for tests) in memory.
+//! - [`config`], [`session`], [`keymap`], [`theme`], [`ignore`] and [`watch`] are the app's settings and helpers.
+//!
+//! Positions are (line, char) counting from 0, where a char is a unicode char and not a byte,
+//! so "é" is one column. A buffer always has at least one line.
+//!
+//! ```
+//! use trmnotes::Buffer;
+//!
+//! let mut buffer = Buffer::new(None);
+//! for c in "hello".chars() {
+//!     buffer.insert_char(c, true);
+//! }
+//! buffer.split_line(false);
+//! buffer.insert_char('!', false);
+//! assert_eq!(buffer.text, vec!["hello", "!"]);
+//! assert_eq!(buffer.cursor(), (1, 1));
+//!
+//! buffer.delete_back();
+//! buffer.delete_back(); // joins the lines back together
+//! assert_eq!(buffer.text, vec!["hello"]);
+//! buffer.undo(); // every delete_back is its own undo step
+//! assert_eq!(buffer.text, vec!["hello", ""]);
+//! ```
+
+pub mod buffer;
+pub mod config;
+pub mod fuzzy;
+pub mod ignore;
+pub mod keymap;
+pub mod markdown;
+pub mod session;
+pub mod store;
+pub mod theme;
+pub mod watch;
+
+pub use buffer::{Buffer, LineEnding};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let folder = std::env::temp_dir().join(format!("trmnotes-test-lib-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        folder.join(name)
+    }
+
+    #[test]
+    fn edits_and_moves_through_the_public_api() {
+        let mut buffer = Buffer::new(Some("note.md".to_string()));
+        buffer.insert_text("# Title\nsome text");
+        assert_eq!(buffer.text, vec!["# Title", "some text"]);
+        assert_eq!(buffer.cursor(), (1, 9));
+        assert!(buffer.dirty);
+
+        // set_cursor keeps the cursor on the text
+        buffer.set_cursor(0, 100);
+        assert_eq!(buffer.cursor(), (0, 7));
+        buffer.set_cursor(9, 3);
+        assert_eq!(buffer.cursor(), (1, 3));
+
+        buffer.split_line(false);
+        buffer.move_up();
+        buffer.end();
+        assert_eq!(buffer.text, vec!["# Title", "som", "e text"]);
+        assert_eq!(buffer.cursor(), (1, 3));
+        buffer.delete(); // joins the next line on
+        assert_eq!(buffer.text, vec!["# Title", "some text"]);
+    }
+
+    #[test]
+    fn loads_and_saves_files_keeping_their_line_endings() {
+        let path = temp_path("crlf.txt");
+        std::fs::write(&path, "one\r\ntwo\r\n").unwrap();
+        let mut buffer = Buffer::new(None);
+        buffer.load(&path).unwrap();
+        assert_eq!(buffer.text, vec!["one", "two"]);
+        assert_eq!(buffer.line_ending, LineEnding::CrLf);
+
+        buffer.set_cursor(1, 3);
+        buffer.insert_char('!', false);
+        buffer.save(&path).unwrap();
+        assert!(!buffer.dirty);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\r\ntwo!\r\n");
+    }
+}
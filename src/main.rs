@@ -1,26 +1,125 @@
-use std::{default, path, vec};
-use std::fs::{self, File, OpenOptions};
-use std::io::{self, Read, Write};
+use std::{default, vec};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
 use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
 
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use time::OffsetDateTime;
+
+// the editing core is in the library (lib.rs), this is the terminal app around it
+use trmnotes::buffer::{byte_offset, char_at_column, display_width, LineChange, next_tab_stop, scroll_offset, wrapped_rows, Buffer};
+use trmnotes::config::Config;
+use trmnotes::fuzzy::fuzzy_match;
+use trmnotes::ignore::{IgnoreList, IGNORE_FILE};
+use trmnotes::keymap::{Action, KeyMap, DEFAULT_KEYS};
+use trmnotes::markdown::{fence_after, headings, highlight_part, to_html};
+use trmnotes::session::Session;
+use trmnotes::store::{back_up, FsNoteStore, NoteStore};
+use trmnotes::theme::Theme;
+use trmnotes::watch::FolderWatch;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use ratatui::{
-    buffer::Buffer,
-    layout::{Rect, Layout, Constraint, Direction},
-    style::Stylize,
+    layout::{Alignment, Rect, Layout, Constraint, Direction, Margin},
+    style::{Color, Style, Stylize},
     symbols::{border},
-    text::{Line, Text},
-    widgets::{Block, Paragraph, Widget},
+    text::{Line, Span, Text},
+    widgets::{Block, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Widget},
     DefaultTerminal, Frame,
 };
 
+const USAGE: &str = "\
+Usage: trmnotes [FILE]
+
+A simple terminal note taking app.
+
+Arguments:
+  [FILE]      Note to open, it's created if it doesn't exist.
+              If it's outside the notes folder, its own folder is used instead.
+
+Options:
+  -h, --help  Print this help";
+
 fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    // load the config before taking over the terminal, so any warning about it can be seen
+    let config = Config::load();
+    if Theme::named(&config.theme).is_none() {
+        eprintln!("Warning: unknown theme {:?}, using \"dark\"", config.theme);
+    }
+    if SortOrder::named(&config.sort).is_none() {
+        eprintln!("Warning: unknown sort {:?}, using \"name\"", config.sort);
+    }
+    for warning in KeyMap::new(&config.keys).1 {
+        eprintln!("Warning: {}", warning);
+    }
+    let mut app = App::new(&config);
+    app.ensure_folder();
+    let session = Session::load().unwrap_or_default();
+    app.restore_layout(&session);
+    // a file on the command line wins, otherwise pick up where the last session left off
+    match args.first() {
+        Some(path) => app.open_path(Path::new(path))?,
+        None => app.restore_session(&session)?,
+    }
+
+    // if anything panics, hand the terminal back before the message is printed so it can be read,
+    // otherwise the shell is left in raw mode on the alternate screen
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+
     let mut terminal = ratatui::init();
-    let app_result = App::default().run(&mut terminal);
+    // ask the terminal to tell us when it loses focus (so we can autosave), about the mouse,
+    // and to send pasted text in one go instead of as a pile of key presses
+    crossterm::execute!(io::stdout(), crossterm::event::EnableFocusChange, crossterm::event::EnableMouseCapture, crossterm::event::EnableBracketedPaste)?;
+    let app_result = app.run(&mut terminal);
+    restore_terminal();
+    app_result?;
+
+    // the only way out of run is quitting, so remember where we were for next time
+    // (done once the terminal is back, so a failure can actually be read)
+    if let Err(e) = app.session().save() {
+        eprintln!("Failed to save session: {}", e);
+    }
+    Ok(())
+}
+
+/*
+Undoes everything main asked of the terminal, for when we quit, hand it over to $EDITOR or panic.
+The extra modes get turned back off too, otherwise the shell gets garbage every time the mouse moves.
+Errors are ignored, there's nothing better to do with them at that point.
+ */
+fn restore_terminal() {
+    let _ = crossterm::execute!(io::stdout(), crossterm::event::DisableFocusChange, crossterm::event::DisableMouseCapture, crossterm::event::DisableBracketedPaste);
     ratatui::restore();
-    app_result
 }
 
+// below this terminal width the line/word/char counts are left out of the status line
+const MIN_WIDTH_FOR_COUNTS: u16 = 110;
+// how long we wait for an event before waking up anyway to check timers (like autosave)
+const POLL_TIMEOUT: Duration = Duration::from_millis(500);
+// how many lines one notch of the mouse wheel scrolls
+const MOUSE_SCROLL_LINES: usize = 3;
+// how long "saved" stays in the status line after an autosave
+const SAVED_FLASH: Duration = Duration::from_secs(2);
+// how long a message (like an error) stays in the status line
+const STATUS_DURATION: Duration = Duration::from_secs(4);
+// how long the editor border flashes when a key can't do anything
+const BELL_FLASH: Duration = Duration::from_millis(120);
+// lines longer than this (in bytes) only get the part that's on screen styled, see `render_text`
+const LONG_LINE: usize = 1000;
+// reading speed used for the reading time in the info popup
+const WORDS_PER_MINUTE: usize = 200;
+
 /*
 Explanation of the code:
 This represents the app as a whole.
@@ -28,55 +127,271 @@ It contains all the logic for handling events, and the struct itself hold any va
 */
 pub struct App {
     // vars related to text editing
-    text: Vec<String>,                    // text that is displayed, one line is one string
-    folder: String,                       // folder where notes are stored
-    files: Vec<String>,                   // all the files in that folder
-    current_file: Option<String>,         //current file that is being edited, if None, we use the default.txt
+    buffers: Vec<Buffer>,                 // every open file, one per tab (there's always at least one)
+    active: usize,                        // index of the buffer (tab) being edited
+    root: String,                         // folder where notes are stored, note names are relative to this
+    store: Box<dyn NoteStore>,            // what notes are actually read from and written to (see store.rs), normally `root` on disk
+    folder: String,                       // folder the explorer is showing, either the root or a subfolder of it
+    files: Vec<String>,                   // notes and subfolders in that folder, subfolders end in a `/` (and `..` goes up)
+    extensions: Vec<String>,              // only files ending in one of these show up in the explorer
+    tab_width: usize,                     // how many spaces Tab inserts (and Shift+Tab removes), and how wide a tab is drawn
+    hard_tabs: bool,                      // if true, new notes are indented with tabs (opened ones go by what they already use)
+    auto_indent: bool,                    // if true, Enter copies the indentation (and list marker) onto the new line
+    auto_pairs: bool,                     // if true, brackets and quotes get closed as they're typed
+    visual_bell: bool,                    // if true, keys that can't do anything flash the editor border (see `flash`)
+    flash_until: Option<Instant>,         // the editor border is drawn inverted until then
+    positions: HashMap<String, (usize, usize, usize)>, // note -> (cursor_x, cursor_y, scroll_y) from the last run, used when it's opened again
+    vim_mode: bool,                       // if true, editing is modal like vim, see `handle_normal_key`
+    vim_normal: bool,                     // with vim_mode on, true in normal mode (keys are commands) and false in insert mode
+    vim_pending_d: bool,                  // the first d of dd was pressed in normal mode
+    clipboard: String,                    // text copied or cut with Ctrl+C / Ctrl+X, shared between tabs
+    date_format: String,                  // how Ctrl+T writes the date, like %Y-%m-%d (see `format_date`)
+    previous_file: Option<String>,        // the note we were on before the current one, Alt+O goes back to it
+    trim_trailing_on_save: bool,          // if true, trailing spaces/tabs are stripped from every line when saving
+    single_trailing_newline: bool,        // if true, notes are saved ending in exactly one newline
+    max_file_bytes: Option<u64>,          // files bigger than this aren't opened, None means no limit
+    keep_backups: usize,                  // how many old versions of a note saving keeps around, 0 for none
+    welcome_note: bool,                   // if true, the first run starts with a default.txt explaining the basics
+    smart_home_end: bool,                 // if true, Home/End go to the start/end of the wrapped row instead of the line
+    autosave_secs: Option<u64>,           // how often unsaved changes get saved automatically, None turns it off
+    next_autosave: Instant,               // when the autosave timer goes off next
+    autosaved_at: Option<Instant>,        // when we last autosaved, so we can flash "saved" for a bit
+    status_message: Option<(String, Instant)>, // last error or info message for the status line, and when it was set
 
     // vars related to app state and menus
     exit: bool,                           // if true, stop running the app
+    external_edit: bool,                  // if true, the current note gets opened in $EDITOR before the next draw
     explorer_open: bool,                  // wehther or not we show the menu that displays the files
-    help_menu_open: bool,                 // wehther or not we display some keybinds
+    explorer_width: u16,                  // percent of the screen the explorer panel takes up
+    mode: Mode,                           // which popup has the keyboard, if any
 
-    note_create_mode: bool,               // if true, we are in the mode to create a new note
     new_file_name: String,                // name of the new file that is being created, if empty, we use the default.txt
+    saving_as: bool,                      // the create note popup is asking where to save the current note instead (save as)
 
-    file_select_mode: bool,
-    file_select_index: usize,             // index of the file that is selected in the file explorer
+    search_query: String,                 // the text we are searching for
+    search_matches: Vec<(usize, usize)>,  // every match as (line, char column)
+    search_index: Option<usize>,          // which match the cursor jumped to, if any
+    replace_query: String,                // the text matches get replaced with
+    replace_editing_find: bool,           // which field of the replace prompt we're typing into
+
+    rename_buffer: String,                // the new name being typed, starts as the current name
+    rename_error: Option<String>,         // shown in the rename modal if the rename didn't work
 
+    goto_buffer: String,                  // the line number being typed
+    goto_error: Option<String>,           // shown in the go to line modal if what was typed isn't a line number
+
+    file_select_index: usize,             // index of the file that is selected in the file explorer
+    find_query: String,                   // what's typed into the fuzzy finder (Ctrl+P)
+    find_notes: Vec<String>,              // every note the fuzzy finder can pick from, listed when it opens
+    find_index: usize,                    // which of the finder's results is selected, 0 is the best match
+    outline: Vec<(usize, u8, String)>,    // the headings of the current note as (line, level, title), read when the outline opens
+    outline_index: usize,                 // which heading is selected in the outline
+    sort_order: SortOrder,                // how the explorer and the file selection order notes, Alt+S cycles it
+    explorer_scroll: usize,               // index of the first file visible in the explorer panel
 
-    // vars related to cursor position
-    cursor_x: usize,
-    cursor_y: usize,
+    command_query: String,                // what's typed into the command palette (F2) to narrow down the commands
+    command_index: usize,                 // which of the matching commands is selected
 
+    // vars related to the view, the cursor and scroll position themselves live in each buffer
+    view_width: usize,                    // how many chars fit on a row of the editor, updated every draw (used by Home/End when wrapping)
+    view_height: usize,                   // how many lines fit in the editor, updated every draw (used by PageUp/PageDown)
+    wrap_enabled: bool,                   // if true, long lines wrap onto the next row instead of scrolling sideways
+    markdown_enabled: bool,               // if true, Markdown headings, emphasis and code get highlighted
+    relative_line_numbers: bool,          // if true, line numbers show how far each line is from the cursor
+    theme: Theme,                         // the colors everything is drawn with
+    keymap: KeyMap,                       // which keys run which commands, the defaults plus [keys] from the config
+    title: String,                        // shown in front of the tabs, can be empty
+    ruler_column: Option<usize>,          // if set, this column of the editor is shaded as a guide for line length
+    warn_lines: Option<usize>,            // if set, the status line mentions it when a note has more lines than this
+    warn_chars: Option<usize>,            // same, for chars
+    show_whitespace: bool,                // if true, spaces and tabs are drawn as dim · and →
+    split: Option<usize>,                 // with the view split, the buffer shown in the other pane (the focused one is `active`)
+    focus_right: bool,                    // if true, the focused pane is the right one of the split
+    writing_mode: bool,                   // if true, the text is kept to a column `writing_width` wide in the middle of the editor
+    writing_width: usize,                 // how wide that column is
+    follow_cursor: bool,                  // if false, the view was scrolled with the mouse wheel so don't snap back to the cursor
+    last_area: Rect,                      // the size of the screen last time we drew, so mouse clicks can be mapped to panels
+    folder_watch: Option<FolderWatch>,    // tells us when notes change in the folder outside the app, None if it couldn't be set up
 }
 
 impl default::Default for App {
-    // Default state of the app
+    // Default state of the app, same as having no config file
     fn default() -> Self {
+        App::new(&Config::default())
+    }
+}
+
+impl App {
+    // Starting state of the app, with anything configurable taken from the config
+    pub fn new(config: &Config) -> Self {
         App {
-            text: vec!["".to_string()],
-            folder: String::from("./notes/"),
+            buffers: vec![Buffer { uses_hard_tabs: config.hard_tabs, ..Buffer::new(None) }],
+            active: 0,
+            root: config.folder(),
+            store: Box::new(FsNoteStore::new(config.folder())),
+            folder: config.folder(),
             files: vec![],
-            current_file: "default.txt".to_string().into(),
+            extensions: config.extensions.clone(),
+            tab_width: config.tab_width,
+            hard_tabs: config.hard_tabs,
+            auto_indent: config.auto_indent,
+            auto_pairs: config.auto_pairs,
+            visual_bell: config.visual_bell,
+            vim_mode: config.vim_mode,
+            vim_normal: config.vim_mode, // vim starts out in normal mode
+            vim_pending_d: false,
+            clipboard: String::new(),
+            date_format: config.date_format.clone(),
+            previous_file: None,
+            trim_trailing_on_save: config.trim_trailing_on_save,
+            single_trailing_newline: config.single_trailing_newline,
+            max_file_bytes: config.max_file_bytes(),
+            keep_backups: config.keep_backups,
+            welcome_note: config.welcome_note,
+            smart_home_end: config.smart_home_end,
+            autosave_secs: config.autosave(),
+            next_autosave: Instant::now(),
+            autosaved_at: None,
+            flash_until: None,
+            positions: HashMap::new(),
+            status_message: None,
 
             exit: false,
+            external_edit: false,
             explorer_open: true,
-            help_menu_open: false,
+            explorer_width: config.explorer_percent(),
+            mode: Mode::Editing,
 
-            note_create_mode: false,
             new_file_name: String::new(),
+            saving_as: false,
+
+            search_query: String::new(),
+            search_matches: vec![],
+            search_index: None,
+            replace_query: String::new(),
+            replace_editing_find: true,
+
+            rename_buffer: String::new(),
+            rename_error: None,
+
+            goto_buffer: String::new(),
+            goto_error: None,
 
-            file_select_mode: false,
             file_select_index: 0,
+            find_query: String::new(),
+            find_notes: vec![],
+            find_index: 0,
+            outline: vec![],
+            outline_index: 0,
+            sort_order: SortOrder::named(&config.sort).unwrap_or_default(),
+            explorer_scroll: 0,
+
+            command_query: String::new(),
+            command_index: 0,
 
-            cursor_x: 0,
-            cursor_y: 0,
+            view_width: 0,
+            view_height: 0,
+            wrap_enabled: false,
+            markdown_enabled: true,
+            relative_line_numbers: config.relative_line_numbers,
+            theme: Theme::named(&config.theme).unwrap_or_default(),
+            keymap: KeyMap::new(&config.keys).0,
+            title: config.title.clone(),
+            ruler_column: config.ruler_column,
+            writing_width: config.writing_width.max(1),
+            warn_lines: config.warn_lines,
+            warn_chars: config.warn_chars,
+            show_whitespace: false,
+            split: None,
+            focus_right: false,
+            writing_mode: false,
+            follow_cursor: true,
+            last_area: Rect::default(),
+            folder_watch: None,
+        }
+    }
+}
+
+// What `create_note` ended up doing, so the create modal can tell the user
+#[derive(Debug, PartialEq, Eq)]
+enum CreateOutcome {
+    Created,                              // the note didn't exist, so a new empty one was made
+    AlreadyExists,                        // there was already a note with that name, nothing was touched
+}
+
+/*
+Which popup is open, if any. Only one can be open at a time, and while it is it gets every key.
+Esc always closes it and goes back to editing.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Editing,                              // no popup, keys go to the note
+    Help,                                 // the list of keybinds (Ctrl+H)
+    Info,                                 // stats about the current note (Alt+I)
+    QuitConfirm,                          // asking what to do with unsaved changes before quitting
+    DiskConflict,                         // the note changed on disk since we opened it, asking before saving over it
+    CreateNote,                           // typing the name of a new note (or where to save a copy of this one, see `saving_as`)
+    Search,                               // typing a search query
+    Replace,                              // find and replace (the find text is search_query)
+    Rename,                               // typing a new name for the current note
+    GoTo,                                 // typing a line number to jump to
+    SelectFile,                           // picking a file from the list
+    FindFile,                             // the fuzzy finder, picking a note by typing part of its name (Ctrl+P)
+    Commands,                             // the command palette, picking a command by typing part of its name (F2)
+    Outline,                              // the headings of the note, picking one jumps to it (Alt+T)
+    DeleteConfirm,                        // asking whether to delete the file picked in the file selection
+    RevertConfirm,                        // asking whether to throw away unsaved changes and reload the note (Alt+R)
+    DeleteNoteConfirm,                    // asking whether to delete the current note (Alt+Delete)
+    OverwriteConfirm,                     // asking whether save as should replace a note that's already there
+}
+
+// How notes are ordered in the explorer, folders always come first and are sorted by name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SortOrder {
+    #[default]
+    Name,                                 // alphabetically
+    Modified,                             // most recently changed first
+    Size,                                 // biggest first
+}
+
+impl SortOrder {
+    // The order called `name` in the config (ignoring case), None if there's no such order
+    fn named(name: &str) -> Option<SortOrder> {
+        match name.to_ascii_lowercase().as_str() {
+            "name" => Some(SortOrder::Name),
+            "modified" => Some(SortOrder::Modified),
+            "size" => Some(SortOrder::Size),
+            _ => None,
+        }
+    }
+
+    // What Alt+S switches to
+    fn next(self) -> SortOrder {
+        match self {
+            SortOrder::Name => SortOrder::Modified,
+            SortOrder::Modified => SortOrder::Size,
+            SortOrder::Size => SortOrder::Name,
+        }
+    }
 
+    fn label(self) -> &'static str {
+        match self {
+            SortOrder::Name => "name",
+            SortOrder::Modified => "modified time",
+            SortOrder::Size => "size",
         }
     }
 }
 
+// Where each panel of the app goes on screen, see `App::layout`
+struct AppLayout {
+    explorer: Rect,
+    editor: Rect,
+    text: Rect,                           // where the note's text goes inside the editor, everything about the cursor is counted from here
+    line_numbers: Rect,
+    other: Option<Rect>,                  // with the view split, the pane that isn't focused (`editor` is the focused one)
+}
+
 /*
 Explanation of the code:
 This is the implementation of the `App` struct.
@@ -85,522 +400,5362 @@ This is where we handle the events, draw the UI, and run the app.
  */
 impl App {
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
-        self.open_note( &self.current_file.clone().unwrap_or_else(|| "default.txt".to_string()))?;
+        // the file list is only refreshed when something changes it, not every frame
+        self.refresh_notes();
+        // that includes other programs, the watch is only started here so tests don't each get a thread for it
+        match FolderWatch::new(Path::new(&self.folder)) {
+            Ok(watch) => self.folder_watch = Some(watch),
+            Err(e) => self.set_status(format!("Not watching the notes folder ({}), {} refreshes", e, self.keymap.label(Action::Refresh))),
+        }
 
         while !self.exit {
-            self.get_notes()?;
             terminal.draw(|frame| self.draw(frame))?;
             self.handle_events()?;
+            // this needs the terminal, which key handlers don't have, so they just ask for it
+            if self.external_edit {
+                self.external_edit = false;
+                self.edit_externally(terminal)?;
+            }
         }
         Ok(())
     }
+
     /*
-    Draws the Widget we rendered into the terminal. 
-    Also draws the cursor at the current position.
+    Opens the current note in $EDITOR, for when you want a real editor for a big change.
+    Unsaved changes are saved first, since the note gets reloaded afterwards.
+    While the editor runs we hand the terminal over completely (leave the alternate screen, turn off raw mode and the mouse),
+    then take it back and redraw from scratch. The cursor stays about where it was.
      */
-    fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self, frame.area());
+    fn edit_externally(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let Some(editor) = std::env::var("EDITOR").ok().filter(|editor| !editor.trim().is_empty()) else {
+            self.set_status("$EDITOR isn't set".to_string());
+            return Ok(());
+        };
+        let file_name = self.current().display_name().to_string();
+        // $EDITOR needs a file, a store that isn't on disk has nothing to give it
+        let Some(path) = self.store.path_on_disk(&file_name) else {
+            self.set_status("Can't open $EDITOR, these notes aren't on disk".to_string());
+            return Ok(());
+        };
+        if self.current().dirty
+            && let Err(e) = self.save_note(&file_name)
+        {
+            self.set_status(format!("Not opening $EDITOR, failed to save note: {}", e));
+            return Ok(());
+        }
 
+        // $EDITOR can have arguments in it, like "code --wait"
+        let mut parts = editor.split_whitespace();
+        let program = parts.next().unwrap_or_default();
+        let mut command = Command::new(program);
+        command.args(parts).arg(path);
 
-        // render the cursor at the current position
-        let cursor_position = Rect {
-            x: if self.explorer_open {
-                self.cursor_x as u16 + 35 // The 40 offset is required because of the left panel width and the border
-            } else {
-                self.cursor_x as u16 + 1 // if it's not open, we don't need the large offset
-            },
+        restore_terminal();
+        let status = command.status();
+        *terminal = ratatui::init();
+        crossterm::execute!(io::stdout(), crossterm::event::EnableFocusChange, crossterm::event::EnableMouseCapture, crossterm::event::EnableBracketedPaste)?;
+        terminal.clear()?;
 
-            y: self.cursor_y as u16 + 1, // this is because of the border and title bar
-            width: 1,
-            height: 1,
-        };
-        frame.set_cursor_position((cursor_position.x, cursor_position.y));
+        match status {
+            Ok(status) if status.success() => self.reload_current()?,
+            Ok(status) => self.set_status(format!("{} exited with {}", program, status)),
+            Err(e) => self.set_status(format!("Failed to run {}: {}", program, e)),
+        }
+        Ok(())
     }
 
     /*
-    This is where we can handle the key that is pressed.
-    Each are handled through a match statement.
-    We can handle combinations of keys.
-    We have to handle certain keys seperately like arrow ketts, backspace, enter, etc.
-      - Arrow keys allow us to move the cursor around the text.
-      - Backspace allows us to delete the chararcter at the cursor pos
-      - Enter allows us to split the current line at the cursor position.
-    We also handle some special keys like Ctrl+S to save, Ctrl+E to toggle the explorer, and Ctrl+Q to quit.
-    Every other key gets checked if it can be trasnlated to a char, if so we then just insert it to the text at the cursor position.
+    Reads the current note from disk again, throwing away anything unsaved.
+    The cursor stays where it was, as far as the text on disk allows.
+    A note that's gone from disk is an error, the buffer is all that's left of it.
      */
-    fn handle_key_event(&mut self, key_event: KeyEvent) {
-        if self.note_create_mode {
-            // If we are in note creation mode, we handle the key events differently
-            if key_event.code == KeyCode::Enter {
-                // If Enter is pressed, we create a new note with the current file name
-                if !self.new_file_name.is_empty() {
-                    let file_name = self.new_file_name.clone();
-                    if let Err(e) = self.create_note(&file_name) {
-                        eprintln!("Failed to create note: {}", e);
-                    } else {
-                        self.current_file = Some(file_name);
-                        self.note_create_mode = false; // Exit note creation mode
-                        self.new_file_name.clear();    // Clear the new file name
-                    }
-                }
-            } else if key_event.code == KeyCode::Backspace {
-                // If Backspace is pressed, remove the last character from the new file name
-                if !self.new_file_name.is_empty() {
-                    self.new_file_name.pop();
-                }
-            } else if key_event.code == KeyCode::Esc {
-                // If Escape is pressed, exit note creation mode
-                self.note_create_mode = false;
-                self.new_file_name.clear(); // Clear the new file name
-            } else if let Some(c) = key_event.code.as_char() {
-                // If any other character is pressed, append it to the new file name
-                self.new_file_name.push(c);
-            }
-            return; // Exit early if in note creation mode
-        } else if self.file_select_mode {
-            // If we are in file selection mode, we handle the key events differently
-            if key_event.code == KeyCode::Enter {
-                // If Enter is pressed, open the selected file
-                if self.file_select_index < self.files.len() {
-                    let file_name = &self.files[self.file_select_index].clone();
-                    if let Err(e) = self.open_note(file_name) {
-                        eprintln!("Failed to open note: {}", e);
-                    } else {
-                        self.current_file = Some(file_name.clone());
-                        self.file_select_mode = false; // Exit file selection mode
-                        self.file_select_index = 0; // Reset the file selection index
-                    }
-                }
-            } else if key_event.code == KeyCode::Esc {
-                // If Escape is pressed, exit file selection mode
-                self.file_select_mode = false;
-            } else if key_event.code == KeyCode::Up || key_event.code == KeyCode::Char('w') {
-                // Move up in the file list
-                if self.file_select_index > 0 {
-                    self.file_select_index -= 1;
-                }
-            } else if key_event.code == KeyCode::Down || key_event.code == KeyCode::Char('s') {
-                // Move down in the file list
-                if self.file_select_index < self.files.len() - 1 {
-                    self.file_select_index += 1;
-                }
-            }
-            return; // Exit early if in file selection mode
+    fn reload_current(&mut self) -> io::Result<()> {
+        let file_name = self.current().display_name().to_string();
+        if !self.note_exists(&file_name) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} not found", file_name)));
         }
+        let (cursor_x, cursor_y) = (self.current().cursor_x, self.current().cursor_y);
+        self.open_note(&file_name)?;
+        let current = self.current_mut();
+        (current.cursor_x, current.cursor_y) = (cursor_x, cursor_y);
+        current.clamp_cursor();
+        Ok(())
+    }
 
-        match key_event.code {
-            // handling special key combinations
-            KeyCode::Char('s') if key_event.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
-                if let Some(file_name) = self.current_file.clone() {
-                    if let Err(e) = self.save_note(&file_name) {
-                        eprintln!("Failed to save note: {}", e);
-                    }
-                }
-            }
-            KeyCode::Char('e') if key_event.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
-                self.explorer_open = !self.explorer_open;
-            }
-            KeyCode::Char('q') if key_event.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
-                self.exit = true;
-            }
-            KeyCode::Char('n') if key_event.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
-                // create a new note
-                // Inside this loop we are going to display a prompt for the user to enter the name of the new note.
-                self.note_create_mode = true;
-            }
-            KeyCode::Char('o') if key_event.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
-                self.file_select_mode = true;
-                self.get_notes().expect("Failed to get notes");
-            }
-            KeyCode::Char('h') if key_event.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
-                // toggle help menu
-                self.help_menu_open = !self.help_menu_open;
-            }
-
-            // handling cursor movement
-            KeyCode::Left => {
-                // move cursor left
-                if self.cursor_x > 0 {
-                    self.cursor_x -= 1;
-                }
-            }
-            KeyCode::Right => {
-                // move cursor right
-                if self.cursor_x < self.text[self.cursor_y].len() {
-                    self.cursor_x += 1;
-                }
-            }
-            KeyCode::Up => {
-                // move cursor up
-                if self.cursor_y > 0 {
-                    self.cursor_y -= 1;
-                    if self.cursor_x > self.text[self.cursor_y].len() {
-                        self.cursor_x = self.text[self.cursor_y].len(); // move cursor to the end of the previous line
-                    }
-                }
-            }
-            KeyCode::Down => {
-                // move cursor down
-                if self.cursor_y < self.text.len() - 1 {
-                    self.cursor_y += 1;
-                    if self.cursor_x > self.text[self.cursor_y].len() {
-                        self.cursor_x = self.text[self.cursor_y].len(); // move cursor to the end of the next line
-                    }
-                }
-            }
-
-            // handling text editing    
-            KeyCode::Backspace => {
-                // remove the last character from the text
-                if self.cursor_x > 0 && self.cursor_y < self.text.len() {
-                    self.text[self.cursor_y].remove(self.cursor_x - 1);
-                    self.cursor_x -= 1;
-                } else if self.text[self.cursor_y].is_empty() && self.cursor_y > 0 {
-                    // if the current line is empty and cursor_y is greater than 0, remove the current line and go to the previous line
-                    self.text.remove(self.cursor_y);
-                    self.cursor_y -= 1;
-                    self.cursor_x = self.text[self.cursor_y].len(); // move cursor to the end of the previous line
-
-                } else if (self.cursor_x == 0) && (self.cursor_y > 0) {
-                    // if cursor_x is 0 and cursor_y is greater than 0, go to precipous line
-                    self.cursor_y -= 1;
-                    self.cursor_x = self.text[self.cursor_y].len(); // move cursor to the end of the previous line
-                }
-            }
-            KeyCode::Enter => {
-                // split the current line at the cursor position
-                let mut current_line = self.text[self.cursor_y].clone();
-                let new_line = current_line.split_off(self.cursor_x);
-                self.text[self.cursor_y] = current_line; // update the current line
-                self.text.insert(self.cursor_y + 1, new_line); // insert the new line after the current line
-                // move the cursor to the start of the new line
-                self.cursor_y += 1;
-                self.cursor_x = 0;
-            }
-            _ => {
-                // if the key is a character, append it to the text
-                if let Some(c) = key_event.code.as_char() {
-                    self.text[self.cursor_y].insert(self.cursor_x, c);
-                    self.cursor_x += 1;
-
-                    // Ensure the cursor does not go out of bounds
-                    if self.cursor_x > self.text[self.cursor_y].len() {
-                        self.cursor_x = self.text[self.cursor_y].len();
-                    }
-                    // Ensure the cursor_y does not go out of bounds
-                    if self.cursor_y >= self.text.len() {
-                        self.text.push("".to_string());
-                    }
-                }
-            }
+    // Alt+R once it's confirmed (or there was nothing to lose), back to the note as it is on disk
+    fn revert_current(&mut self) {
+        let file_name = self.current().display_name().to_string();
+        match self.reload_current() {
+            Ok(()) => self.set_status(format!("Reverted {} to what's on disk", file_name)),
+            Err(e) => self.set_status(format!("Failed to revert note: {}", e)),
         }
     }
 
     /*
-    This is where we get all the events. We make sure that we only handle the key presses, 
-    and then pass the key event to the `handle_key_event` method.
+    Where we are right now, saved when quitting.
+    That's also where the cursor is in every open note, plus the remembered ones that haven't been opened this time.
+    Notes that are gone are dropped, so the list doesn't keep growing.
      */
-    fn handle_events(&mut self) -> io::Result<()> {
-        match event::read()? {
-            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                self.handle_key_event(key_event);
+    fn session(&self) -> Session {
+        let current = self.current();
+        let mut positions: std::collections::BTreeMap<_, _> = self
+            .positions
+            .iter()
+            .filter(|(file_name, _)| self.note_exists(file_name))
+            .map(|(file_name, position)| (file_name.clone(), *position))
+            .collect();
+        for buffer in &self.buffers {
+            if let Some(file_name) = &buffer.file_name {
+                positions.insert(file_name.clone(), (buffer.cursor_x, buffer.cursor_y, buffer.scroll_y));
             }
-            _ => {}
         }
-        Ok(())
-    }
-
-    // Getting all the files in folder and dealing with that stuff
-    fn get_notes(&mut self) -> io::Result<()> {
-        self.files.clear();
-        for entry in fs::read_dir(&self.folder)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(file_name) = path.file_name() {
-                    if let Some(file_name_str) = file_name.to_str() {
-                        self.files.push(file_name_str.to_string());
-                    }
-                }
-            }
+        Session {
+            positions,
+            current_file: current.file_name.clone(),
+            cursor_x: current.cursor_x,
+            cursor_y: current.cursor_y,
+            scroll_y: current.scroll_y,
+            explorer_open: self.explorer_open,
+            wrap_enabled: self.wrap_enabled,
+            markdown_enabled: self.markdown_enabled,
+            relative_line_numbers: Some(self.relative_line_numbers),
+            show_whitespace: self.show_whitespace,
+            writing_mode: self.writing_mode,
         }
-        self.files.sort(); // Sort files alphabetically
-        Ok(())
     }
 
-    fn create_note(&mut self, file_name: &str) -> io::Result<()> {
-        let file_path = Path::new(&self.folder).join(file_name);
-        if !file_path.exists() {
-            let mut file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .open(file_path)?;
-            file.write_all(b"")?; // Create an empty file
-            self.get_notes()?; // Refresh the list of files
-        }
-        Ok(())
+    // Puts the panels and view toggles back the way they were last time, even when a file is opened from the command line
+    pub fn restore_layout(&mut self, session: &Session) {
+        self.explorer_open = session.explorer_open;
+        self.wrap_enabled = session.wrap_enabled;
+        self.markdown_enabled = session.markdown_enabled;
+        self.relative_line_numbers = session.relative_line_numbers.unwrap_or(self.relative_line_numbers);
+        self.show_whitespace = session.show_whitespace;
+        self.writing_mode = session.writing_mode;
+        self.positions = session.positions.clone().into_iter().collect();
     }
 
-    fn open_note(&mut self, file_name: &str) -> io::Result<()> {
-        let file_path = Path::new(&self.folder).join(file_name);
-        if file_path.exists() {
-            let mut file = File::open(file_path)?;
-            let mut content = String::new();
-            file.read_to_string(&mut content)?;
-            self.text = content.lines().map(|line| line.to_string()).collect();
-            if self.text.is_empty() {
-                self.text.push("".to_string()); // Ensure there's at least one line
-            }
-            self.cursor_x = 0;
-            self.cursor_y = 0;
-        } else {
-            eprintln!("File not found: {}", file_name);
-        }
-        Ok(())
+    // Puts the cursor (and scroll) of the current note back to `position`, as far as the text allows since it can have changed
+    fn restore_position(&mut self, (cursor_x, cursor_y, scroll_y): (usize, usize, usize)) {
+        let current = self.current_mut();
+        current.set_cursor(cursor_y, cursor_x);
+        current.scroll_y = scroll_y.min(current.cursor_y);
     }
 
-    fn save_note(&mut self, file_name: &str) -> io::Result<()> {
-        let file_path = Path::new(&self.folder).join(file_name);
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(file_path)?;
-        for line in &self.text {
-            writeln!(file, "{}", line)?;
+    /*
+    Opens the note from the last session and puts the cursor back where it was.
+    If that note is gone we fall back to default.txt, and the cursor is clamped
+    in case the file got shorter since then.
+     */
+    pub fn restore_session(&mut self, session: &Session) -> io::Result<()> {
+        let file_name = match &session.current_file {
+            Some(file_name) if self.note_exists(file_name) => file_name.clone(),
+            _ => return self.open_default(),
+        };
+        if let Err(e) = self.open_note(&file_name) {
+            // it's still there but can't be opened (like being too big), so start on default.txt instead
+            self.set_status(format!("Failed to open note: {}", e));
+            return self.open_default();
         }
+        self.restore_position((session.cursor_x, session.cursor_y, session.scroll_y));
         Ok(())
     }
-}
+    /*
+    Draws the Widget we rendered into the terminal. 
+    Before rendering we update the scroll offsets so the cursor is always inside the editor.
+    Also draws the cursor at the current position.
+     */
+    fn draw(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        self.last_area = area;
+        let layout = self.layout(area);
+        let text_area = layout.text;
+        let (view_width, view_height) = (text_area.width as usize, text_area.height as usize);
+        self.view_width = view_width;
+        self.view_height = view_height;
+        self.current_mut().keep_one_line(); // rendering indexes the cursor's line, so make sure there is one
+        if self.follow_cursor {
+            let (wrap, tab_width) = (self.wrap_enabled, self.tab_width);
+            self.current_mut().scroll_to_cursor(view_width, view_height, wrap, tab_width);
+        }
+        // the other pane of a split keeps its cursor in view too, the mouse wheel only scrolls the focused one
+        if let (Some(pane), Some(other)) = (layout.other, self.split) {
+            let text = self.text_area(pane);
+            let (wrap, tab_width) = (self.wrap_enabled, self.tab_width);
+            let buffer = &mut self.buffers[other];
+            buffer.keep_one_line();
+            buffer.scroll_to_cursor(text.width as usize, text.height as usize, wrap, tab_width);
+        }
+        // the explorer follows the selection while picking a file, and never scrolls past the end of the list
+        let explorer_rows = layout.explorer.height.saturating_sub(2) as usize;
+        if matches!(self.mode, Mode::SelectFile | Mode::DeleteConfirm) {
+            self.explorer_scroll = scroll_offset(self.explorer_scroll, self.file_select_index, explorer_rows);
+        }
+        self.explorer_scroll = self.explorer_scroll.min(self.files.len().saturating_sub(1));
 
-/*
+        frame.render_widget(&*self, area);
 
-Explanation of the code:
-Bascially this is the rendering of the widget.
-Nothing here is what "draws" it on the screen, but rather how it is structured.
-This is the implementation of the `Widget` trait for the `App` struct.
-Since we're implementing the `Widget` trait, we need to define the `render` method.
-Now that we have the widget implemented we ccan turn our app struct into a widget.
 
-*/
-impl Widget for &App {
-    fn render(self, area: Rect, buf: &mut Buffer) {
+        // render the cursor at the current position, minus how far we scrolled
+        let current = self.current();
+        let (row, col) = current.cursor_visual(view_width, self.wrap_enabled, self.tab_width);
+        // if the mouse wheel scrolled the cursor out of view, just don't show it
+        if row < current.scroll_y || row >= current.scroll_y + view_height || col < current.scroll_x {
+            return;
+        }
+        let cursor_position = Rect {
+            x: text_area.x + (col - current.scroll_x) as u16,
+            y: text_area.y + (row - current.scroll_y) as u16,
+            width: 1,
+            height: 1,
+        };
+        frame.set_cursor_position((cursor_position.x, cursor_position.y));
+    }
 
-        // Split the area into left and right panels
+    /*
+    Splits the screen into the explorer, editor and line number panels.
+    Both `draw` (for the cursor) and `render` use this, so they always agree on where the editor is,
+    no matter how wide the terminal is.
+     */
+    fn layout(&self, area: Rect) -> AppLayout {
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(13), Constraint::Percentage(85), Constraint::Percentage(2),])
+            .constraints([
+                Constraint::Percentage(self.explorer_width),
+                Constraint::Percentage(98 - self.explorer_width),
+                Constraint::Percentage(2),
+            ])
             .split(area);
-        
-        // Block on the right, this displays the content of the file and the editor
-        let instructions = Line::from(vec![
-            " Help ".bold().into(),
-            "<Ctrl+H> ".yellow().bold().into(),
-            " Quit ".bold().into(),
-            "<Ctrl+Q> ".red().bold().into(),
-            // " Save ".bold().into(),
-            // "<Ctrl+S> ".green().bold().into(),
-            " Toggle Explorer ".bold().into(),
-            "<Ctrl+E> ".yellow().bold().into(),
-            " Cursor Pos <".bold().into(),
-
-            if self.cursor_x == self.text[self.cursor_y].len() {
-                self.cursor_x.to_string().red().bold().into()
-            } else {
-                self.cursor_x.to_string().blue().bold().into()
-            },
-
-            " : ".bold().into(),
+        // If explorer is closed, use the full area for the editor
+        let editor = if self.explorer_open { chunks[1] } else { area };
+        // a split view halves the editor, the panes stay where they are and only the focus moves between them
+        let (editor, other) = if self.split.is_some() {
+            let panes = Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).split(editor);
+            if self.focus_right { (panes[1], Some(panes[0])) } else { (panes[0], Some(panes[1])) }
+        } else {
+            (editor, None)
+        };
+        AppLayout {
+            explorer: chunks[0],
+            editor,
+            text: self.text_area(editor),
+            line_numbers: chunks[2],
+            other,
+        }
+    }
 
-            if self.cursor_y == self.text.len() - 1 {
-                self.cursor_y.to_string().red().bold().into()
-            } else {
-                self.cursor_y.to_string().blue().bold().into()
-            },
+    // The text goes inside the border of a pane, in writing mode only in a column in the middle with margins on both sides
+    fn text_area(&self, pane: Rect) -> Rect {
+        let mut text = pane.inner(Margin::new(1, 1));
+        if self.writing_mode && text.width as usize > self.writing_width {
+            text.x += (text.width - self.writing_width as u16) / 2;
+            text.width = self.writing_width as u16;
+        }
+        text
+    }
 
-            ">".bold().into(),
-        ]);
+    // How many rows the whole of `buffer` takes up in a pane `width` wide, with wrapping a long line takes several
+    fn visual_rows(&self, buffer: &Buffer, width: usize) -> usize {
+        if self.wrap_enabled {
+            buffer.text.iter().map(|line| wrapped_rows(line, width, self.tab_width)).sum()
+        } else {
+            buffer.text.len()
+        }
+    }
 
+    /*
+    Draws the text of `buffer` into `text_area` (inside a pane's border), only what's in view.
+    `searching` is for the focused pane while the search is open, its matches are highlighted instead of the Markdown.
+     */
+    fn render_text(&self, buffer: &Buffer, text_area: Rect, searching: bool, buf: &mut ratatui::buffer::Buffer) {
         // this is the text that will be displayed in the editor
-        let editor_text = Text::from(self.text.iter().map(|line| Line::from(line.as_str())).collect::<Vec<Line>>());
-        let editor_paragraph = Paragraph::new(editor_text)
-            .block(Block::default().borders(ratatui::widgets::Borders::ALL))
-            .wrap(ratatui::widgets::Wrap { trim: true });
-
-        let editor_area = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Min(1)])
-            .split(if self.explorer_open { chunks[1] } else { area });
-        // Render the editor paragraph in the bottom part of the right panel
-        editor_paragraph.render(editor_area[0], buf);
+        // with wrapping on we split the lines ourselves (see `wrap_line`), otherwise long lines are scrolled horizontally
+        let text_width = text_area.width as usize;
+        let text_rows = text_area.height as usize;
+        // only what's in view gets styled, so a note with thousands of lines (or one huge line) draws as fast as a short one
+        // with wrapping the top line can be partly scrolled past, `skipped_rows` of it are above the editor
+        let (first_line, skipped_rows) = buffer.first_visible_line(text_width, self.wrap_enabled, self.tab_width);
+        let theme = &self.theme;
+        let mut in_fence = buffer.text[..first_line].iter().fold(false, |in_fence, line| fence_after(line, in_fence));
+        let selection = buffer.selection();
+        // which columns of a line are on screen, with wrapping it's all the rows that fit (for the top line the ones not scrolled past)
+        let mut skip = skipped_rows;
+        let mut columns = || if self.wrap_enabled {
+            (std::mem::take(&mut skip) * text_width, text_rows * text_width)
+        } else {
+            (buffer.scroll_x, text_width)
+        };
+        let editor_lines = buffer.text.iter().enumerate().skip(first_line).take(text_rows).map(|(y, line)| {
+            let (from, width) = columns();
+            // a long line only gets the chars on screen styled, `start` is the first of them and `start_col` its column
+            // (a tab can start before `from`), everything below works on that part and counts from there
+            let (start, start_col, end) = if line.len() > LONG_LINE {
+                let start = char_at_column(line, from, self.tab_width);
+                let end = char_at_column(line, from + width, self.tab_width) + 1;
+                (start, display_width(line, start, self.tab_width), end)
+            } else {
+                (0, 0, usize::MAX)
+            };
+            let part = byte_offset(line, start)..byte_offset(line, end);
+            let text = &line[part.clone()];
+            let (markdown, next_in_fence) = highlight_part(line, part, in_fence);
+            in_fence = next_in_fence;
+            let styled = if searching {
+                self.styled_line(y, text, start)
+            } else if self.markdown_enabled {
+                markdown
+            } else {
+                Line::from(text)
+            };
+            let styled = if self.show_whitespace {
+                let trailing_from = line.trim_end_matches([' ', '\t']).chars().count();
+                visible_whitespace(styled, trailing_from.saturating_sub(start))
+            } else {
+                styled
+            };
+            // the selection is drawn inverted on top of whatever styling the line already has
+            let styled = match selection {
+                Some(((start_y, start_x), (end_y, end_x))) if (start_y..=end_y).contains(&y) => {
+                    let from = if y == start_y { start_x } else { 0 };
+                    let to = if y == end_y { end_x } else { usize::MAX };
+                    style_range(styled, from.saturating_sub(start), to.saturating_sub(start), theme.selection)
+                }
+                _ => styled,
+            };
+            let styled = if text.contains('\t') { expand_tabs(styled, start_col, self.tab_width, self.show_whitespace) } else { styled };
+            // the lines are cut down to what fits before wrapping (or instead of scrolling sideways), so the rest is never touched
+            cut_columns(styled, from - start_col, width)
+        });
+        let editor_text = if self.wrap_enabled {
+            let rows = editor_lines.flat_map(|line| wrap_line(line, text_width));
+            Text::from(rows.take(text_rows).collect::<Vec<Line>>())
+        } else {
+            Text::from(editor_lines.collect::<Vec<Line>>())
+        };
+        // the border (with the tabs and status line) is drawn around it in `render`
+        Paragraph::new(editor_text).render(text_area, buf);
 
-        let editor_block = Block::bordered()
-            .title(" Editor ".bold().blue())
-            .title_bottom(instructions.centered())
-            .border_set(border::PLAIN);
+        // the ruler shades one column (counted from the start of the line, so it moves with horizontal scrolling)
+        if let Some(ruler) = self.ruler_column
+            && ruler >= buffer.scroll_x
+            && ruler - buffer.scroll_x < text_width
+        {
+            let x = text_area.x + (ruler - buffer.scroll_x) as u16;
+            for y in text_area.y..text_area.bottom() {
+                buf[(x, y)].set_bg(theme.ruler);
+            }
+        }
+    }
 
-        // Rendering the line numbers on the left side
-        // We create a vector of lines, each line is a number from 1 to the number of lines in the text
-        let line_numbers: Vec<Line> = (0..self.text.len())
-            .map(| i| {
-                if i == self.cursor_y {
-                    Line::from(i.to_string().red().bold())
-                } else {
-                    Line::from(i.to_string().blue().bold())
-                }
-            })
-          //.map(|mut i| {
-            //     if i == self.cursor_y {
-            //         i = 0;
-            //         Line::from(i.to_string().red().bold())    // This is for if I want the line number to be how far away fron the cursor it is
-            //     } else {
-            //         if i > self.cursor_y { i -= self.cursor_y; } else { i = self.cursor_y - i;}
-            //         Line::from(i.to_string().blue().bold())
-            //     }
-            // })
-            .collect();
-        let line_numbers_text = Text::from(line_numbers);
-        let line_numbers_paragraph = Paragraph::new(line_numbers_text)
-            .block(Block::default().borders(ratatui::widgets::Borders::ALL))
-            .wrap(ratatui::widgets::Wrap { trim: true });
-        line_numbers_paragraph.render(chunks[2], buf);
+    // The buffer (tab) being edited
+    fn current(&self) -> &Buffer {
+        &self.buffers[self.active]
+    }
 
-        if self.explorer_open {
-            // Block on the left, this displays the files
-            let files_paragraph = Paragraph::new(
-                Text::from(self.files.iter().map(|file| Line::from(file.as_str())).collect::<Vec<Line>>())
-            )
-                .block(Block::default().borders(ratatui::widgets::Borders::ALL))
-                .wrap(ratatui::widgets::Wrap { trim: true });
-            let files_block = Block::bordered()
-                .title(" Files ".bold().blue())
-                .border_set(border::PLAIN);
-            let files_area = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Min(1)])
-                .split(chunks[0]);
+    fn current_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.active]
+    }
 
-            files_paragraph.render(files_area[0], buf);
+    /*
+    Cycles to the next (or previous) tab, wrapping around at the ends.
+    Search matches belong to the buffer they were found in, so we look again in the new one.
+     */
+    fn switch_tab(&mut self, forward: bool) {
+        let count = self.buffers.len();
+        let before = self.current().display_name().to_string();
+        self.set_active(if forward { (self.active + 1) % count } else { (self.active + count - 1) % count });
+        self.remember_previous(before);
+        self.update_search_matches();
+    }
 
-            files_block.render(chunks[0], buf);
-            editor_block.render(chunks[1], buf);
+    // Makes buffer `index` the focused one. If it's in the other pane of a split they trade places, so both panes keep a note of their own
+    fn set_active(&mut self, index: usize) {
+        if self.split == Some(index) {
+            self.split = Some(self.active);
+        }
+        self.active = index;
+    }
 
-        } else {
-            // If explorer is closed, use the full area for the editor
-            editor_block.render(area, buf);
+    /*
+    Alt+V splits the editor in two, to see two notes side by side, or goes back to one pane.
+    The new pane on the right shows the note we were on before (or the next tab), each pane keeps its own cursor and scroll
+    since they're separate buffers. Only the focused pane gets keys, Alt+P moves the focus over.
+     */
+    fn toggle_split(&mut self) {
+        if self.split.take().is_some() {
+            self.focus_right = false;
+            return;
         }
+        if self.buffers.len() < 2 {
+            self.set_status("Open another note to split the view with".to_string());
+            return;
+        }
+        let previous = self.previous_file.as_deref();
+        let other = self
+            .buffers
+            .iter()
+            .position(|buffer| Some(buffer.display_name()) == previous)
+            .filter(|index| *index != self.active)
+            .unwrap_or((self.active + 1) % self.buffers.len());
+        self.split = Some(other);
+        self.focus_right = false;
+    }
 
-        // Rendering the help menu if it's open
-        if self.help_menu_open {
-            // preparing help area
-            // 1) determine size of the help box
-            let help_width = 30;
-            let help_height = 10;
-            let x = (area.width.saturating_sub(help_width)) / 2 + area.x;
-            let y = (area.height.saturating_sub(help_height)) / 2 + area.y;
-            let help_area = Rect::new(x, y, help_width, help_height);
-
-            // Manually clear the help area by filling it with spaces
-            for y in help_area.top()..help_area.bottom() {
-                for x in help_area.left()..help_area.right() {
-                    if let Some(cell) = buf.cell_mut((x, y)) {
-                        cell.set_symbol(" ");
-                    }
-                }
-            }
-
-            let help_text = Text::from(vec![
-                Line::from("Ctrl+Q: Quit"),
-                Line::from("Ctrl+S: Save"),
-                Line::from("Ctrl+E: Toggle Explorer"),
-                Line::from("Ctrl+N: Create New Note"),
-                Line::from("Ctrl+O: Open Note"),
-                Line::from("Ctrl+H: Toggle Help Menu"),
-            ]);
-            let help_paragraph = Paragraph::new(help_text)
-                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Help ".bold().blue()))
-                .wrap(ratatui::widgets::Wrap { trim: true });
-            help_paragraph.render(help_area, buf);
+    // Moves the focus to the other pane of the split
+    fn switch_pane(&mut self) {
+        if let Some(other) = self.split {
+            self.split = Some(self.active);
+            self.active = other;
+            self.focus_right = !self.focus_right;
+            self.update_search_matches();
         }
+    }
 
-        // rednering the create note block if in note creation mode
-        if self.note_create_mode {
-            // preparing create note area
-            let create_note_width = 35;
-            let create_note_height = 8;
-            let x = (area.width.saturating_sub(create_note_width)) / 2 + area.x;
-            let y = (area.height.saturating_sub(create_note_height)) / 2 + area.y;
-            let create_note_area = Rect::new(x, y, create_note_width, create_note_height);
+    // After moving to another note, `before` is the one to go back to with Alt+O
+    fn remember_previous(&mut self, before: String) {
+        if before != self.current().display_name() {
+            self.previous_file = Some(before);
+        }
+    }
 
-            // Manually clear the create note area by filling it with spaces
-            for y in create_note_area.top()..create_note_area.bottom() {
-                for x in create_note_area.left()..create_note_area.right() {
-                    if let Some(cell) = buf.cell_mut((x, y)) {
-                        cell.set_symbol(" ");
-                    }
-                }
+    /*
+    Alt+O, bounces between the last two notes.
+    Each tab keeps its own cursor, so we land right where we left off.
+    If the tab was closed in the meantime the note is opened again.
+     */
+    fn switch_to_previous(&mut self) {
+        match self.previous_file.clone() {
+            Some(previous) => {
+                self.switch_note(&previous);
             }
+            None => self.set_status("No previous note yet".to_string()),
+        }
+    }
 
-            let create_note_text = Text::from(vec![
-                Line::from("Create Note:"),
-                Line::from(format!("Name: {}", self.new_file_name)),
-                Line::from(""),
-                Line::from(vec![
-                    "Create: ".into(),
-                    "Enter".bold().green().into(),
-                    " | Cancel: ".into(),
-                    "Esc".bold().red().into(),
-                ]),
-            ]);
-            let create_note_paragraph = Paragraph::new(create_note_text)
-                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Create Note ".bold().blue()))
-                .wrap(ratatui::widgets::Wrap { trim: true });
-            create_note_paragraph.render(create_note_area, buf);
+    /*
+    Mouse support:
+      - Clicking in the editor moves the cursor there.
+      - Clicking a file in the explorer opens it.
+      - The scroll wheel moves the view without moving the cursor.
+    We ignore the mouse while a popup is open, those are keyboard only.
+     */
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent) {
+        if self.modal_open() {
+            return;
         }
+        let layout = self.layout(self.last_area);
+        let editor = layout.editor;
+        let (column, row) = (mouse_event.column, mouse_event.row);
+        // a click in the other pane of a split moves the focus there, then it's a click like any other
+        if mouse_event.kind == MouseEventKind::Down(MouseButton::Left)
+            && layout.other.is_some_and(|other| other.contains((column, row).into()))
+        {
+            self.switch_pane();
+            return self.handle_mouse_event(mouse_event);
+        }
+        let in_editor = column > editor.x && column < editor.right().saturating_sub(1)
+            && row > editor.y && row < editor.bottom().saturating_sub(1);
 
-        // rendering the file selection mode if it's open
-        if self.file_select_mode {
-            // preparing file selection area
-            let file_select_width = 40;
-            let file_select_height = 4 + self.files.len() as u16; // 4 for the instructions + number of files
-            let x = (area.width.saturating_sub(file_select_width)) / 2 + area.x;
-            let y = (area.height.saturating_sub(file_select_height)) / 2 + area.y;
-            let file_select_area = Rect::new(x, y, file_select_width, file_select_height);
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) if in_editor => {
+                let text = layout.text;
+                let width = text.width as usize;
+                let (wrap, tab_width) = (self.wrap_enabled, self.tab_width);
+                let current = self.current_mut();
+                let visual_row = (row - text.y) as usize + current.scroll_y;
+                // a click in the margins of writing mode goes to the nearest end of the row
+                let visual_col = (column.clamp(text.x, text.right().saturating_sub(1)) - text.x) as usize + current.scroll_x;
+                (current.cursor_y, current.cursor_x) = current.logical_position(visual_row, visual_col, width, wrap, tab_width);
+                current.selection_anchor = None;
+                self.follow_cursor = true;
+            }
+            MouseEventKind::Down(MouseButton::Left) if self.explorer_open && layout.explorer.contains((column, row).into()) => {
+                // the file names start one row below the top border
+                let index = (row - layout.explorer.y).saturating_sub(1) as usize + self.explorer_scroll;
+                if let Some(entry) = self.files.get(index).cloned() {
+                    self.open_entry(&entry);
+                }
+            }
+            MouseEventKind::ScrollUp if self.explorer_open && layout.explorer.contains((column, row).into()) => {
+                self.explorer_scroll = self.explorer_scroll.saturating_sub(MOUSE_SCROLL_LINES);
+            }
+            MouseEventKind::ScrollDown if self.explorer_open && layout.explorer.contains((column, row).into()) => {
+                self.explorer_scroll = (self.explorer_scroll + MOUSE_SCROLL_LINES).min(self.files.len().saturating_sub(1));
+            }
+            MouseEventKind::ScrollUp if in_editor => {
+                let current = self.current_mut();
+                current.scroll_y = current.scroll_y.saturating_sub(MOUSE_SCROLL_LINES);
+                self.follow_cursor = false;
+            }
+            MouseEventKind::ScrollDown if in_editor => {
+                let current = self.current_mut();
+                current.scroll_y = (current.scroll_y + MOUSE_SCROLL_LINES).min(current.text.len().saturating_sub(1));
+                self.follow_cursor = false;
+            }
+            _ => {}
+        }
+    }
+
+    // true if any popup is open (these take over the keyboard)
+    fn modal_open(&self) -> bool {
+        self.mode != Mode::Editing
+    }
+
+    /*
+    The commands in the palette matching command_query, best first, with which chars of the description matched.
+    A tie keeps the order of the help (so with nothing typed it's the same list), and the palette doesn't list itself.
+     */
+    fn command_results(&self) -> Vec<(Action, Vec<usize>)> {
+        let mut results: Vec<(i32, Action, Vec<usize>)> = DEFAULT_KEYS
+            .iter()
+            .filter(|(action, _, _, _)| *action != Action::CommandPalette)
+            .filter_map(|(action, _, description, _)| {
+                fuzzy_match(&self.command_query, description).map(|(score, positions)| (score, *action, positions))
+            })
+            .collect();
+        results.sort_by_key(|(score, _, _)| std::cmp::Reverse(*score));
+        results.into_iter().map(|(_, action, positions)| (action, positions)).collect()
+    }
 
-            // Manually clear the file selection area by filling it with spaces
-            for y in file_select_area.top()..file_select_area.bottom() {
-                for x in file_select_area.left()..file_select_area.right() {
-                    if let Some(cell) = buf.cell_mut((x, y)) {
-                        cell.set_symbol(" ");
+    /*
+    Keys while a popup is open. The popup gets every key, nothing reaches the note.
+    Esc always closes it and goes back to editing, anything else is up to the popup.
+     */
+    fn handle_modal_key(&mut self, key_event: KeyEvent) {
+        if key_event.code == KeyCode::Esc {
+            self.close_modal();
+            return;
+        }
+        match self.mode {
+            Mode::Editing => {}
+            Mode::Help => {
+                // just for reading, Esc or the help key again closes it
+                if self.keymap.action(&key_event) == Some(Action::Help) {
+                    self.mode = Mode::Editing;
+                }
+            }
+            Mode::QuitConfirm => {
+                // If we are asking whether to save before quitting, only these keys do anything
+                match key_event.code {
+                    KeyCode::Char('s') | KeyCode::Char('S') => {
+                        // save every open note and quit, but stay open if a save failed so nothing is lost
+                        if let Err(e) = self.save_all() {
+                            self.set_status(format!("Failed to save note: {}", e));
+                            self.mode = Mode::Editing;
+                        } else {
+                            self.exit = true;
+                        }
+                    }
+                    KeyCode::Char('q') | KeyCode::Char('Q') => {
+                        // quit without saving
+                        self.exit = true;
+                    }
+                    KeyCode::Char('c') | KeyCode::Char('C') => self.mode = Mode::Editing,
+                    _ => {}
+                }
+            }
+            Mode::DiskConflict => {
+                // the note was changed by something else, so saving needs a decision first
+                let file_name = self.current().display_name().to_string();
+                match key_event.code {
+                    KeyCode::Char('r') | KeyCode::Char('R') => {
+                        // take what's on disk, dropping our edits
+                        match self.reload_current() {
+                            Ok(()) => self.set_status(format!("Reloaded {}", file_name)),
+                            Err(e) => self.set_status(format!("Failed to reload note: {}", e)),
+                        }
+                        self.mode = Mode::Editing;
+                    }
+                    KeyCode::Char('o') | KeyCode::Char('O') => {
+                        if let Err(e) = self.save_note(&file_name) {
+                            self.set_status(format!("Failed to save note: {}", e));
+                        }
+                        self.mode = Mode::Editing;
+                    }
+                    KeyCode::Char('c') | KeyCode::Char('C') => self.mode = Mode::Editing,
+                    _ => {}
+                }
+            }
+            Mode::Info => {
+                // the info popup is just for reading, Esc or its key again closes it
+                if self.keymap.action(&key_event) == Some(Action::Info) {
+                    self.mode = Mode::Editing;
+                }
+            }
+            Mode::CreateNote => {
+                // If we are in note creation mode, we handle the key events differently
+                if key_event.code == KeyCode::Enter && self.saving_as {
+                    // saving as asks before replacing another note, saving as the note's own name is just a save
+                    // cleaned up like a new note's name, "./b.txt" is b.txt (and is open in a tab if b.txt is)
+                    let file_name = clean_note_name(&self.new_file_name);
+                    if file_name.is_empty() {
+                        return;
+                    }
+                    if self.note_exists(&file_name) && file_name != self.current().display_name() {
+                        self.mode = Mode::OverwriteConfirm;
+                    } else {
+                        self.save_as_and_close(&file_name);
+                    }
+                } else if key_event.code == KeyCode::Enter {
+                    // If Enter is pressed, we open the note with that name, creating it first if it isn't there
+                    // "./todo.txt" and "work//todo.txt" are the same note as "todo.txt" and "work/todo.txt"
+                    let file_name = clean_note_name(&self.new_file_name);
+                    if self.is_folder(&file_name) {
+                        self.set_status(format!("{} is a folder", file_name));
+                    } else if !file_name.is_empty() {
+                        // either way we open it, the modal already told the user which one it would be
+                        if let Err(e) = self.create_note(&file_name) {
+                            self.set_status(format!("Failed to create note: {}", e));
+                        } else {
+                            self.switch_note(&file_name); // opens it in its own tab
+                            self.mode = Mode::Editing;     // Exit note creation mode
+                            self.new_file_name.clear();    // Clear the new file name
+                        }
+                    }
+                } else if key_event.code == KeyCode::Backspace {
+                    // If Backspace is pressed, remove the last character from the new file name
+                    if !self.new_file_name.is_empty() {
+                        self.new_file_name.pop();
+                    }
+                } else if let Some(c) = key_event.code.as_char() {
+                    // If any other character is pressed, append it to the new file name
+                    self.new_file_name.push(c);
+                }
+            }
+            Mode::Search => {
+                // Plain letters (including N) are part of the query, so next/previous use Enter and Shift+Enter or Down/Up
+                match key_event.code {
+                    KeyCode::Enter if key_event.modifiers.contains(KeyModifiers::SHIFT) => self.jump_to_match(false),
+                    KeyCode::Enter | KeyCode::Down => self.jump_to_match(true),
+                    KeyCode::Up => self.jump_to_match(false),
+                    KeyCode::Backspace => {
+                        self.search_query.pop();
+                        self.update_search_matches();
+                    }
+                    _ => {
+                        if let Some(c) = key_event.code.as_char() {
+                            self.search_query.push(c);
+                            self.update_search_matches();
+                        }
+                    }
+                }
+            }
+            Mode::Replace => {
+                // Tab switches between the find and replace fields, Enter replaces one match at a time
+                match key_event.code {
+                    KeyCode::Char('a') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.replace_all();
+                    }
+                    KeyCode::Enter => self.replace_current(),
+                    KeyCode::Down => self.jump_to_match(true),
+                    KeyCode::Up => self.jump_to_match(false),
+                    KeyCode::Tab | KeyCode::BackTab => {
+                        self.replace_editing_find = !self.replace_editing_find;
+                    }
+                    KeyCode::Backspace => {
+                        if self.replace_editing_find {
+                            self.search_query.pop();
+                            self.update_search_matches();
+                        } else {
+                            self.replace_query.pop();
+                        }
+                    }
+                    _ => {
+                        if let Some(c) = key_event.code.as_char() {
+                            if self.replace_editing_find {
+                                self.search_query.push(c);
+                                self.update_search_matches();
+                            } else {
+                                self.replace_query.push(c);
+                            }
+                        }
                     }
                 }
             }
+            Mode::Rename => {
+                // Renaming works just like typing the name of a new note
+                if key_event.code == KeyCode::Enter {
+                    let old_name = self.current().display_name().to_string();
+                    // cleaned up like a new note's name, so the tab and the explorer agree on what it's called
+                    let new_name = clean_note_name(&self.rename_buffer);
+                    if new_name.is_empty() {
+                        self.rename_error = Some("Name can't be empty".to_string());
+                    } else if let Err(e) = self.rename_note(&old_name, &new_name) {
+                        self.rename_error = Some(e.to_string()); // keep the modal open so they can fix it
+                    } else {
+                        self.mode = Mode::Editing;
+                        self.rename_buffer.clear();
+                        self.rename_error = None;
+                    }
+                } else if key_event.code == KeyCode::Backspace {
+                    self.rename_buffer.pop();
+                    self.rename_error = None;
+                } else if let Some(c) = key_event.code.as_char() {
+                    self.rename_buffer.push(c);
+                    self.rename_error = None;
+                }
+            }
+            Mode::GoTo => {
+                // Line numbers count from 1, same as the line number panel
+                match key_event.code {
+                    KeyCode::Enter => match self.goto_buffer.trim().parse::<usize>() {
+                        Ok(line) => {
+                            self.current_mut().goto_line(line);
+                            self.mode = Mode::Editing;
+                            self.goto_buffer.clear();
+                        }
+                        Err(_) => self.goto_error = Some("Not a line number".to_string()),
+                    },
+                    KeyCode::Backspace => {
+                        self.goto_buffer.pop();
+                        self.goto_error = None;
+                    }
+                    _ => {
+                        if let Some(c) = key_event.code.as_char() {
+                            self.goto_buffer.push(c);
+                            self.goto_error = None;
+                        }
+                    }
+                }
+            }
+            Mode::DeleteConfirm => {
+                // If we are confirming a delete, only yes/no do anything
+                match key_event.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                        if let Some(entry) = self.files.get(self.file_select_index).cloned() {
+                            let file_name = self.note_name(&entry);
+                            match self.delete_note(&file_name) {
+                                Ok(()) => self.set_status(format!("Deleted {}", file_name)),
+                                Err(e) => self.set_status(format!("Failed to delete note: {}", e)),
+                            }
+                        }
+                        self.mode = Mode::SelectFile; // back to the (updated) list
+                    }
+                    KeyCode::Char('n') | KeyCode::Char('N') => self.mode = Mode::SelectFile,
+                    _ => {}
+                }
+            }
+            Mode::Commands => match key_event.code {
+                // typing narrows the commands down, Enter runs the selected one like its key would
+                KeyCode::Enter => {
+                    if let Some((action, _)) = self.command_results().into_iter().nth(self.command_index) {
+                        self.close_modal();
+                        if self.current().read_only && action.edits() {
+                            self.set_status(self.read_only_message());
+                        } else {
+                            self.run_action(action);
+                        }
+                    }
+                }
+                KeyCode::Up => self.command_index = self.command_index.saturating_sub(1),
+                KeyCode::Down => {
+                    if self.command_index + 1 < self.command_results().len() {
+                        self.command_index += 1;
+                    }
+                }
+                KeyCode::Backspace => {
+                    self.command_query.pop();
+                    self.command_index = 0;
+                }
+                _ => {
+                    if let Some(c) = key_event.code.as_char() {
+                        self.command_query.push(c);
+                        self.command_index = 0;
+                    }
+                }
+            },
+            Mode::RevertConfirm => match key_event.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                    self.revert_current();
+                    self.mode = Mode::Editing;
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') => self.mode = Mode::Editing,
+                _ => {}
+            },
+            Mode::DeleteNoteConfirm => match key_event.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                    let file_name = self.current().display_name().to_string();
+                    match self.delete_note(&file_name) {
+                        Ok(()) => self.set_status(format!("Deleted {}", file_name)),
+                        Err(e) => self.set_status(format!("Failed to delete note: {}", e)),
+                    }
+                    self.mode = Mode::Editing;
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') => self.mode = Mode::Editing,
+                _ => {}
+            },
+            Mode::OverwriteConfirm => match key_event.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                    let file_name = clean_note_name(&self.new_file_name);
+                    self.save_as_and_close(&file_name);
+                }
+                // back to typing, so another name can be picked
+                KeyCode::Char('n') | KeyCode::Char('N') => self.mode = Mode::CreateNote,
+                _ => {}
+            },
+            Mode::Outline => match key_event.code {
+                // same keys as the file selection
+                KeyCode::Enter => {
+                    if let Some(&(line, _, _)) = self.outline.get(self.outline_index) {
+                        self.current_mut().goto_line(line + 1);
+                    }
+                    self.mode = Mode::Editing;
+                }
+                KeyCode::Up | KeyCode::Char('w') => self.outline_index = self.outline_index.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('s') if self.outline_index + 1 < self.outline.len() => self.outline_index += 1,
+                _ => {}
+            },
+            Mode::FindFile => {
+                // Letters are part of the query, so only the arrows move through the results
+                match key_event.code {
+                    KeyCode::Enter => {
+                        let picked = self.find_results().get(self.find_index).map(|(note, _)| note.to_string());
+                        if let Some(note) = picked
+                            && self.switch_note(&note)
+                        {
+                            self.close_modal();
+                        }
+                    }
+                    KeyCode::Up => self.find_index = self.find_index.saturating_sub(1),
+                    KeyCode::Down => {
+                        if self.find_index + 1 < self.find_results().len() {
+                            self.find_index += 1;
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        self.find_query.pop();
+                        self.find_index = 0;
+                    }
+                    _ => {
+                        if let Some(c) = key_event.code.as_char() {
+                            self.find_query.push(c);
+                            self.find_index = 0; // the best match is always the one picked first
+                        }
+                    }
+                }
+            }
+            Mode::SelectFile => {
+                // If we are in file selection mode, we handle the key events differently
+                if key_event.code == KeyCode::Enter {
+                    // If Enter is pressed, open the selected file
+                    if self.file_select_index < self.files.len() {
+                        let entry = self.files[self.file_select_index].clone();
+                        if self.open_entry(&entry) {
+                            self.mode = Mode::Editing;  // Exit file selection mode
+                            self.file_select_index = 0; // Reset the file selection index
+                        }
+                    } else {
+                        self.flash(); // nothing to open in an empty folder
+                    }
+                } else if self.keymap.action(&key_event) == Some(Action::CycleSort) {
+                    self.cycle_sort();
+                } else if key_event.code == KeyCode::Tab {
+                    // typing the name is quicker than scrolling when it's known, it's opened (or made) like a new note,
+                    // starting from the folder being looked at so a bare name lands next to the files in the list
+                    self.mode = Mode::CreateNote;
+                    self.new_file_name = self.note_name("");
+                } else if key_event.code == KeyCode::Char('d') {
+                    // ask before deleting the selected file, folders (and ..) can't be deleted from here
+                    if self.files.get(self.file_select_index).is_some_and(|entry| !entry.ends_with('/') && entry != "..") {
+                        self.mode = Mode::DeleteConfirm;
+                    } else {
+                        self.flash();
+                    }
+                } else if key_event.code == KeyCode::Up || key_event.code == KeyCode::Char('w') {
+                    // Move up in the file list
+                    if self.file_select_index > 0 {
+                        self.file_select_index -= 1;
+                    }
+                } else if key_event.code == KeyCode::Down || key_event.code == KeyCode::Char('s') {
+                    // Move down in the file list (which can be empty, so no `len() - 1` here)
+                    if self.file_select_index + 1 < self.files.len() {
+                        self.file_select_index += 1;
+                    }
+                }
+            }
+        }
+    }
 
-            // Prepare the text for the file selection menu
-            let mut file_lines: Vec<Line> = self.files.iter().enumerate().map(|(i, file)| {
-                if i == self.file_select_index {
-                    Line::from(file.as_str().bold().yellow()) // Highlight the selected file
-                } else if file.as_str() == self.current_file.as_deref().unwrap_or("default.txt") {
-                    Line::from(file.as_str().bold().green()) // Highlight the current file
+    // Closes the open popup, throwing away whatever was half typed into it
+    fn close_modal(&mut self) {
+        self.mode = Mode::Editing;
+        self.new_file_name.clear();
+        self.saving_as = false;
+        self.rename_buffer.clear();
+        self.rename_error = None;
+        self.goto_buffer.clear();
+        self.goto_error = None;
+        self.find_query.clear();
+        self.find_index = 0;
+    }
+
+    /*
+    Keys in vim's normal mode (only with `vim_mode` on). This is a small part of vim, not the real thing:
+      - h/j/k/l move, Enter and Backspace move down and left
+      - i types before the cursor, a after it
+      - x deletes the char under the cursor, dd the whole line
+      - o/O start a new line below/above and start typing on it
+    Other plain keys do nothing (so a stray key never ends up in the note), and anything with Ctrl or Alt,
+    the arrows and so on aren't handled here (returns false) so they work just like outside vim mode.
+     */
+    fn handle_normal_key(&mut self, key_event: KeyEvent) -> bool {
+        if key_event.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return false;
+        }
+        let pending_d = std::mem::take(&mut self.vim_pending_d);
+        let c = match key_event.code {
+            KeyCode::Char(c) => c,
+            KeyCode::Enter => 'j',
+            KeyCode::Backspace => 'h',
+            KeyCode::Delete => 'x',
+            KeyCode::Tab | KeyCode::BackTab => return true,
+            _ => return false,
+        };
+        if matches!(c, 'x' | 'd' | 'o' | 'O') && self.current().read_only {
+            self.set_status(self.read_only_message());
+            return true;
+        }
+        let auto_indent = self.auto_indent;
+        let current = self.current_mut();
+        current.selection_anchor = None;
+        match c {
+            'h' => current.move_left(),
+            'j' => current.move_down(),
+            'k' => current.move_up(),
+            'l' => current.move_right(),
+            'i' => self.vim_normal = false,
+            'a' => {
+                current.move_right();
+                self.vim_normal = false;
+            }
+            'x' if current.cursor_x < current.text[current.cursor_y].chars().count() => current.delete(),
+            'd' if pending_d => current.delete_line(),
+            'd' => self.vim_pending_d = true,
+            'o' => {
+                current.end();
+                current.split_line(auto_indent);
+                self.vim_normal = false;
+            }
+            'O' => {
+                current.home();
+                current.split_line(false);
+                current.move_up();
+                self.vim_normal = false;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /*
+    This is where we can handle the key that is pressed.
+    Each are handled through a match statement.
+    We can handle combinations of keys.
+    We have to handle certain keys seperately like arrow ketts, backspace, enter, etc.
+      - Arrow keys allow us to move the cursor around the text.
+      - Backspace allows us to delete the chararcter at the cursor pos
+      - Enter allows us to split the current line at the cursor position.
+    Commands like Ctrl+S to save, Ctrl+E to toggle the explorer, and Ctrl+Q to quit go through the keymap (see keymap.rs),
+    so they can be changed in the config, and `run_action` does them.
+    Every other key gets checked if it can be trasnlated to a char, if so we then just insert it to the text at the cursor position.
+     */
+    fn handle_key_event(&mut self, key_event: KeyEvent) {
+        // any key brings the view back to the cursor after scrolling with the mouse
+        self.follow_cursor = true;
+
+        // while a popup is open it gets every key
+        if self.mode != Mode::Editing {
+            self.handle_modal_key(key_event);
+            return;
+        }
+
+        // with vim_mode on Esc goes back to normal mode, where plain keys are commands instead of text
+        // like in vim the cursor steps back onto the last char typed
+        if self.vim_mode && key_event.code == KeyCode::Esc {
+            if !self.vim_normal {
+                self.current_mut().move_left();
+            }
+            self.vim_normal = true;
+            self.vim_pending_d = false;
+            self.current_mut().insert_group_open = false;
+            return;
+        }
+        if self.vim_normal && self.handle_normal_key(key_event) {
+            return;
+        }
+
+        // anything other than typing a char closes the current undo group
+        let continuing_insert = std::mem::take(&mut self.current_mut().insert_group_open);
+
+        // in read-only mode anything that would change the text is ignored
+        let action = self.keymap.action(&key_event);
+        if self.current().read_only && action.map_or_else(|| is_edit_key(&key_event), Action::edits) {
+            self.set_status(self.read_only_message());
+            return;
+        }
+
+        // a key from the keymap runs its command, everything below is moving around and typing
+        if let Some(action) = action {
+            self.run_action(action);
+            return;
+        }
+
+        // keys that can run into the edge of the text, if they change nothing at all the border flashes so they don't look ignored
+        let can_hit_edge = matches!(
+            key_event.code,
+            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown | KeyCode::Backspace | KeyCode::Delete
+        );
+        let edit_state = |app: &App| {
+            let current = app.current();
+            (current.cursor_y, current.cursor_x, current.selection(), current.text.len(), current.text[current.cursor_y].len())
+        };
+        let before = edit_state(self);
+
+        // Shift+movement starts (or extends) a selection, moving without Shift drops it
+        if matches!(
+            key_event.code,
+            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down | KeyCode::Home | KeyCode::End | KeyCode::PageUp | KeyCode::PageDown
+        ) {
+            if key_event.modifiers.contains(KeyModifiers::SHIFT) {
+                self.current_mut().start_selection();
+            } else {
+                self.current_mut().selection_anchor = None;
+            }
+        }
+
+        match key_event.code {
+            // handling cursor movement
+            KeyCode::Left if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.current_mut().word_left(),
+            KeyCode::Right if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.current_mut().word_right(),
+            KeyCode::Home if self.smart_home_end && self.wrap_enabled => {
+                let (width, tab_width) = (self.view_width, self.tab_width);
+                self.current_mut().row_home(width, tab_width);
+            }
+            KeyCode::End if self.smart_home_end && self.wrap_enabled => {
+                let (width, tab_width) = (self.view_width, self.tab_width);
+                self.current_mut().row_end(width, tab_width);
+            }
+            KeyCode::Home => self.current_mut().home(),
+            KeyCode::End => self.current_mut().end(),
+            KeyCode::PageUp => {
+                let lines = self.view_height;
+                self.current_mut().page_up(lines);
+            }
+            KeyCode::PageDown => {
+                let lines = self.view_height;
+                self.current_mut().page_down(lines);
+            }
+            KeyCode::Left => self.current_mut().move_left(),
+            KeyCode::Right => self.current_mut().move_right(),
+            KeyCode::Up => self.current_mut().move_up(),
+            KeyCode::Down => self.current_mut().move_down(),
+
+            // handling text editing
+            KeyCode::Backspace => {
+                let auto_pairs = self.auto_pairs;
+                self.current_mut().delete_back_paired(auto_pairs);
+            }
+            KeyCode::Delete => self.current_mut().delete(),
+            KeyCode::Tab => {
+                let tab_width = self.tab_width;
+                self.current_mut().indent(tab_width);
+            }
+            KeyCode::BackTab => {
+                let tab_width = self.tab_width;
+                self.current_mut().dedent(tab_width);
+            }
+            KeyCode::Enter => {
+                let auto_indent = self.auto_indent;
+                self.current_mut().split_line(auto_indent);
+            }
+            _ => {
+                // if the key is a character, insert it at the cursor
+                if let Some(c) = typed_char(&key_event) {
+                    let auto_pairs = self.auto_pairs;
+                    self.current_mut().type_char(c, continuing_insert, auto_pairs);
+                }
+            }
+        }
+        if can_hit_edge && edit_state(self) == before {
+            self.flash();
+        }
+    }
+
+    // The visual bell, flashes the editor border for a moment when a key can't do anything (unless `visual_bell` is off)
+    fn flash(&mut self) {
+        if self.visual_bell {
+            self.flash_until = Some(Instant::now() + BELL_FLASH);
+        }
+    }
+
+    // Runs what a key in the keymap is bound to (see keymap.rs), outside of any popup
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Save => {
+                // a note that was never named and has nothing in it (like on a first run) would just be an empty default.txt
+                let current = self.current();
+                if current.file_name.is_none() && !current.dirty && current.text.iter().all(String::is_empty) {
+                    self.set_status("Nothing to save yet, type something first".to_string());
+                    self.flash();
+                    return;
+                }
+                let file_name = self.current().display_name().to_string();
+                if self.default_taken(self.current()) {
+                    self.set_status(format!("{} already exists, save this note under another name with {}", file_name, self.keymap.label(Action::SaveAs)));
+                    self.flash();
+                    return;
+                }
+                if self.current().changed_since(self.store.modified(&file_name)) {
+                    self.mode = Mode::DiskConflict; // ask first instead of overwriting someone else's changes
+                    return;
+                }
+                if let Err(e) = self.save_note(&file_name) {
+                    self.set_status(format!("Failed to save note: {}", e));
+                }
+            }
+            Action::ToggleExplorer => {
+                self.explorer_open = !self.explorer_open;
+                if self.explorer_open {
+                    self.refresh_notes();
+                }
+            }
+            Action::Quit => {
+                // if any open note has unsaved changes, ask first instead of throwing them away
+                if self.buffers.iter().any(|buffer| buffer.dirty) {
+                    self.mode = Mode::QuitConfirm;
                 } else {
-                    Line::from(file.as_str())
+                    self.exit = true;
                 }
-            }).collect();
+            }
+            Action::SaveAs => {
+                // the same popup as creating a note, but Enter saves the current note under the new name
+                self.mode = Mode::CreateNote;
+                self.saving_as = true;
+            }
+            Action::NewNote => {
+                // create a new note
+                // Inside this loop we are going to display a prompt for the user to enter the name of the new note.
+                self.mode = Mode::CreateNote;
+            }
+            Action::OpenNote => {
+                self.mode = Mode::SelectFile;
+                self.refresh_notes();
+            }
+            Action::FindNote => {
+                // the fuzzy finder, listing the notes now so it's up to date
+                self.find_notes = self.all_notes();
+                self.find_index = 0;
+                self.mode = Mode::FindFile;
+            }
+            Action::CommandPalette => {
+                // every command found by typing part of its name
+                self.mode = Mode::Commands;
+                self.command_query.clear();
+                self.command_index = 0;
+            }
+            Action::Refresh => {
+                // manual refresh, for when files were added or removed outside the app
+                self.refresh_notes();
+            }
+            Action::ToggleWrap => {
+                // toggle line wrapping
+                self.wrap_enabled = !self.wrap_enabled;
+            }
+            Action::WritingMode => self.writing_mode = !self.writing_mode,
+            Action::EditExternally => {
+                // open the note in $EDITOR, `run` takes care of it since it needs the terminal
+                self.external_edit = true;
+            }
+            Action::ExportHtml => {
+                // export the note as a web page next to it
+                match self.export_html() {
+                    Ok(file_name) => self.set_status(format!("Exported to {}", file_name)),
+                    Err(e) => self.set_status(format!("Failed to export note: {}", e)),
+                }
+            }
+            Action::CycleSort => self.cycle_sort(),
+            Action::Outline => {
+                // the outline, starting on the heading of the section the cursor is in
+                self.outline = headings(&self.current().text);
+                if self.outline.is_empty() {
+                    self.set_status("No headings in this note".to_string());
+                } else {
+                    let cursor_y = self.current().cursor_y;
+                    self.outline_index = self.outline.iter().rposition(|&(line, _, _)| line <= cursor_y).unwrap_or(0);
+                    self.mode = Mode::Outline;
+                }
+            }
+            Action::ShowWhitespace => {
+                // toggle showing whitespace
+                self.show_whitespace = !self.show_whitespace;
+            }
+            Action::RelativeNumbers => {
+                // toggle relative line numbers
+                self.relative_line_numbers = !self.relative_line_numbers;
+            }
+            Action::ToggleMarkdown => {
+                // toggle Markdown highlighting, for plain text notes where it just gets in the way
+                self.markdown_enabled = !self.markdown_enabled;
+            }
+            Action::Help => {
+                // open the help menu, the same key again (or Esc) closes it
+                self.mode = Mode::Help;
+            }
+            Action::Info => {
+                // stats about the note
+                self.mode = Mode::Info;
+            }
+            Action::Find => {
+                // start searching, the last query is kept so opening it again and Enter repeats it
+                self.mode = Mode::Search;
+                self.update_search_matches();
+            }
+            Action::Replace => {
+                // find and replace
+                self.mode = Mode::Replace;
+                self.replace_editing_find = true;
+                self.update_search_matches();
+            }
+            Action::Revert => {
+                // revert to the saved note, asking first if that throws away edits
+                if self.current().dirty {
+                    self.mode = Mode::RevertConfirm;
+                } else {
+                    self.revert_current();
+                }
+            }
+            Action::DeleteNote => {
+                // the same as d in the file selection but for the open note, a note that was never saved has nothing to delete
+                if self.store.info(self.current().display_name()).is_ok() {
+                    self.mode = Mode::DeleteNoteConfirm;
+                } else {
+                    self.set_status(format!("{} isn't saved yet, there's nothing to delete", self.current().display_name()));
+                    self.flash();
+                }
+            }
+            Action::Rename => {
+                // rename the current note, starting from its current name
+                self.mode = Mode::Rename;
+                self.rename_buffer = self.current().display_name().to_string();
+                self.rename_error = None;
+            }
+            Action::ReadOnly => {
+                // toggle read-only for the current note, except for ones that could never be saved anyway
+                let current = self.current_mut();
+                if current.not_utf8 {
+                    self.set_status("Not valid UTF-8, this note can only be read".to_string());
+                } else {
+                    current.read_only = !current.read_only;
+                }
+            }
+            Action::GoToLine => {
+                // go to line, the cursor gets scrolled into view on the next draw
+                self.mode = Mode::GoTo;
+                self.goto_error = None;
+            }
+            Action::Undo => {
+                if self.current().undo_stack.is_empty() {
+                    self.flash(); // nothing left to undo
+                }
+                self.current_mut().undo();
+            }
+            Action::Redo => {
+                if self.current().redo_stack.is_empty() {
+                    self.flash();
+                }
+                self.current_mut().redo();
+            }
+            Action::Copy => {
+                if let Some(text) = self.current().selected_text() {
+                    self.clipboard = text;
+                }
+            }
+            Action::Cut => {
+                if let Some(text) = self.current().selected_text() {
+                    self.clipboard = text;
+                    self.current_mut().delete_selection();
+                }
+            }
+            Action::InsertDate => {
+                // the local time zone can't always be found out, UTC is better than nothing
+                let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+                let date = format_date(&self.date_format, &now);
+                self.current_mut().insert_text(&date);
+            }
+            // task lists, a line that can't be made a task (or has no checkbox to flip) flashes
+            Action::InsertCheckbox => {
+                if !self.current_mut().insert_checkbox() {
+                    self.flash();
+                }
+            }
+            Action::ToggleCheckbox => {
+                if !self.current_mut().toggle_checkbox() {
+                    self.flash();
+                }
+            }
+            Action::Paste => {
+                if !self.clipboard.is_empty() {
+                    let text = self.clipboard.clone();
+                    self.current_mut().insert_text(&text);
+                }
+            }
 
-            // Add instructions at the bottom
-            file_lines.push(Line::from(""));
-            file_lines.push(Line::from(vec![
-                "Select: ".into(),
-                "Enter".bold().green().into(),
-                " | Cancel: ".into(),
-                "Esc".bold().red().into(),
-            ]));
+            // switching tabs
+            Action::NextTab => self.switch_tab(true),
+            Action::PreviousTab => self.switch_tab(false),
+            Action::PreviousNote => self.switch_to_previous(),
 
-            let file_select_text = Text::from(file_lines);
-            let file_select_paragraph = Paragraph::new(file_select_text)
-                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Select File ".bold().blue()))
-                .wrap(ratatui::widgets::Wrap { trim: true });
-            file_select_paragraph.render(file_select_area, buf);
+            // two notes side by side
+            Action::SplitView => self.toggle_split(),
+            Action::SwitchPane => {
+                if self.split.is_some() {
+                    self.switch_pane();
+                } else {
+                    self.flash();
+                }
+            }
+
+            // moving and copying whole lines
+            Action::DuplicateLine => self.current_mut().duplicate_line(),
+            Action::DeleteToEnd => self.current_mut().delete_to_end(),
+            Action::SelectAll => self.current_mut().select_all(),
+            Action::MoveLineUp => self.current_mut().move_line(true),
+            Action::MoveLineDown => self.current_mut().move_line(false),
+            Action::DeleteWordBack => self.current_mut().delete_word_left(),
+            Action::DeleteWordForward => self.current_mut().delete_word_right(),
         }
     }
+
+    /*
+    Finds every occurrence of the search query in the text (see `Buffer::find`).
+    Since the matches changed, we forget which one we were on.
+     */
+    fn update_search_matches(&mut self) {
+        self.search_matches = self.buffers[self.active].find(&self.search_query);
+        self.search_index = None;
+    }
+
+    /*
+    Moves the cursor to the next (or previous) match, wrapping around at the ends.
+    The first jump goes to the closest match after (or before) the cursor.
+     */
+    fn jump_to_match(&mut self, forward: bool) {
+        if self.search_matches.is_empty() {
+            self.flash();
+            return;
+        }
+        let count = self.search_matches.len();
+        let cursor = (self.current().cursor_y, self.current().cursor_x);
+        let next = match self.search_index {
+            Some(i) if forward => (i + 1) % count,
+            Some(i) => (i + count - 1) % count,
+            None if forward => self.search_matches.iter().position(|m| *m >= cursor).unwrap_or(0),
+            None => self.search_matches.iter().rposition(|m| *m < cursor).unwrap_or(count - 1),
+        };
+        self.search_index = Some(next);
+        let current = &mut self.buffers[self.active];
+        (current.cursor_y, current.cursor_x) = self.search_matches[next];
+        current.selection_anchor = None;
+    }
+
+    /*
+    Replaces the match the cursor is on and then moves to the next one.
+    If we haven't jumped to a match yet, the first Enter just jumps so you can see what will change.
+     */
+    fn replace_current(&mut self) {
+        let Some(i) = self.search_index else {
+            self.jump_to_match(true);
+            return;
+        };
+        let at = self.search_matches[i];
+        self.buffers[self.active].replace_at(at, &self.search_query, &self.replace_query);
+        self.update_search_matches();
+        self.jump_to_match(true);
+    }
+
+    // Replaces every match in the buffer in one undo step (see `Buffer::replace_all`)
+    fn replace_all(&mut self) {
+        self.buffers[self.active].replace_all(&self.search_matches, &self.search_query, &self.replace_query);
+        self.update_search_matches();
+    }
+
+    // e.g. "3/12 matches", shown in the search and replace prompts
+    fn match_counter(&self) -> String {
+        match self.search_index {
+            _ if self.search_matches.is_empty() => "no matches".to_string(),
+            Some(i) => format!("{}/{} matches", i + 1, self.search_matches.len()),
+            None => format!("{} matches", self.search_matches.len()),
+        }
+    }
+
+    /*
+    Turns one line of text into a styled `Line` for the editor.
+    While searching, matches are highlighted and the one we jumped to gets its own color.
+    `line` can be just part of line `y` starting at char `from` (see `render_text`), matches outside it are left out.
+     */
+    fn styled_line<'a>(&self, y: usize, line: &'a str, from: usize) -> Line<'a> {
+        if !matches!(self.mode, Mode::Search | Mode::Replace) || self.search_query.is_empty() {
+            return Line::from(line);
+        }
+        let match_len = self.search_query.chars().count();
+        let mut spans = vec![];
+        let mut last = 0; // byte offset of where the unstyled text starts
+        let matches = self.search_matches.iter().enumerate().filter(|(_, m)| m.0 == y && m.1 + match_len > from);
+        for (i, (_, col)) in matches {
+            let start = byte_offset(line, col.saturating_sub(from));
+            let end = byte_offset(line, col + match_len - from);
+            let style = if self.search_index == Some(i) {
+                Style::default().fg(Color::Black).bg(Color::Yellow)
+            } else {
+                Style::default().fg(Color::Black).bg(Color::DarkGray)
+            };
+            spans.push(line[last..start].into());
+            spans.push(Span::styled(&line[start..end], style));
+            last = end;
+        }
+        spans.push(line[last..].into());
+        Line::from(spans)
+    }
+
+    /*
+    This is where we get all the events from the terminal, they're passed on to `handle_event`.
+    We only wait a little while for an event, so even if nothing is pressed we still wake up
+    to check the autosave timer and clear old status messages.
+     */
+    fn handle_events(&mut self) -> io::Result<()> {
+        // while the border is flashing wake up when it ends, so it's redrawn back to normal right away
+        let timeout = self.flash_until.map_or(POLL_TIMEOUT, |until| until.saturating_duration_since(Instant::now()).min(POLL_TIMEOUT));
+        if event::poll(timeout)? {
+            self.handle_event(event::read()?);
+        }
+        if self.flash_until.is_some_and(|until| Instant::now() >= until) {
+            self.flash_until = None;
+        }
+
+        self.check_autosave_timer();
+        self.check_folder_watch();
+
+        if self.status_message.as_ref().is_some_and(|(_, at)| at.elapsed() >= STATUS_DURATION) {
+            self.status_message = None;
+        }
+        Ok(())
+    }
+
+    /*
+    Reacts to one event. We make sure that we only handle the key presses (not releases),
+    and then pass the key event to the `handle_key_event` method.
+    Losing focus (switching windows) triggers an autosave.
+    Nothing in here touches the terminal, so tests can feed it made up events.
+     */
+    pub fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                self.handle_key_event(key_event);
+            }
+            Event::Mouse(mouse_event) => {
+                self.handle_mouse_event(mouse_event);
+            }
+            Event::Paste(text) => {
+                self.handle_paste(&text);
+            }
+            Event::Resize(_, _) => {
+                // nothing to update, returning is enough to redraw right away with the new size
+            }
+            Event::FocusLost if self.autosave_secs.is_some() => {
+                self.autosave();
+            }
+            _ => {}
+        }
+    }
+
+    /*
+    Pasted text (with bracketed paste the terminal sends it all at once) goes in as one undo step,
+    with its newlines kept, instead of being typed a char at a time.
+    Terminals often send \r for line breaks, so those are turned into \n first.
+    Prompts only take one line, so only the first line is typed into them, and other popups ignore pastes.
+     */
+    fn handle_paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if self.modal_open() {
+            if matches!(self.mode, Mode::CreateNote | Mode::Search | Mode::Replace | Mode::Rename | Mode::GoTo | Mode::FindFile | Mode::Commands) {
+                for c in text.lines().next().unwrap_or_default().chars() {
+                    self.handle_key_event(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+                }
+            }
+            return;
+        }
+        if self.current().read_only {
+            self.set_status(self.read_only_message());
+            return;
+        }
+        self.current_mut().insert_text(&text);
+    }
+
+    // A key press, as if it came from the terminal (`apply_key(KeyCode::Char('s'), KeyModifiers::CONTROL)` saves)
+    pub fn apply_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        self.handle_event(Event::Key(KeyEvent::new(code, modifiers)));
+    }
+
+    /*
+    Shows a message in the status line for a few seconds.
+    The terminal is taken over while the app runs, so anything printed to stderr would never be seen,
+    every error (and anything else worth telling the user) goes through here instead.
+     */
+    fn set_status(&mut self, message: String) {
+        self.status_message = Some((message, Instant::now()));
+    }
+
+    // What's shown when an edit is blocked by read-only mode, with whatever key turns it off
+    fn read_only_message(&self) -> String {
+        format!("Read only, {} to allow edits", self.keymap.label(Action::ReadOnly))
+    }
+
+    // Autosaves once the timer goes off (every `autosave_secs`), and starts it over
+    fn check_autosave_timer(&mut self) {
+        if let Some(secs) = self.autosave_secs
+            && Instant::now() >= self.next_autosave
+        {
+            self.autosave();
+            self.next_autosave = Instant::now() + Duration::from_secs(secs);
+        }
+    }
+
+    // Saves every note with unsaved changes, used by the autosave timer and on focus loss
+    fn autosave(&mut self) {
+        if !self.buffers.iter().any(|buffer| buffer.dirty) {
+            return;
+        }
+        match self.save_all() {
+            Ok(()) => self.autosaved_at = Some(Instant::now()),
+            Err(e) => self.set_status(format!("Failed to autosave note: {}", e)),
+        }
+    }
+
+    /*
+    Getting all the files in folder and dealing with that stuff.
+    Subfolders are listed too, with a trailing `/` so they stand out, and come before the files.
+    If we're not at the root there's also a `..` at the top to go back up.
+    Only notes (files with one of `extensions`) are listed and hidden (dot) files are skipped,
+    anything else can still be opened by naming it on the command line.
+    Whatever matches the folder's `.trmnotesignore` is left out too (see ignore.rs).
+    Files are ordered by `sort_order`, and anything that ties (or whose metadata can't be read) falls back to the name.
+     */
+    fn get_notes(&mut self) -> io::Result<()> {
+        let folder = self.note_name("");
+        let (ignored, invalid) = self.ignore_list(&folder);
+        if !invalid.is_empty() {
+            self.set_status(format!("Skipping bad patterns in {}: {}", IGNORE_FILE, invalid.join(", ")));
+        }
+        let mut folders = vec![];
+        let mut files = vec![];
+        for (name, info) in self.store.list(&folder)? {
+            if name.starts_with('.') || ignored.is_ignored(&name, info.is_folder) {
+                continue;
+            }
+            if info.is_folder {
+                folders.push(format!("{}/", name));
+            } else if self.is_note(Path::new(&name)) {
+                files.push((name, info));
+            }
+        }
+        folders.sort(); // folders are always alphabetical, their size and time don't say much
+        match self.sort_order {
+            SortOrder::Name => files.sort_by(|a, b| a.0.cmp(&b.0)),
+            SortOrder::Modified => files.sort_by(|a, b| b.1.modified.cmp(&a.1.modified).then_with(|| a.0.cmp(&b.0))),
+            SortOrder::Size => files.sort_by(|a, b| b.1.size.cmp(&a.1.size).then_with(|| a.0.cmp(&b.0))),
+        }
+
+        self.files.clear();
+        if !self.at_root() {
+            self.files.push("..".to_string());
+        }
+        self.files.extend(folders);
+        self.files.extend(files.into_iter().map(|(file_name, _)| file_name));
+        Ok(())
+    }
+
+    /*
+    Every note under the notes root, subfolders included, named the way switch_note wants them (like "work/todo.txt").
+    The same things are left out as in the explorer: hidden files, anything that isn't a note and whatever the
+    `.trmnotesignore` of its folder says. Symlinked folders aren't gone into, so a link back up can't loop forever.
+     */
+    fn all_notes(&self) -> Vec<String> {
+        let mut notes = vec![];
+        let mut folders = vec![String::new()];
+        while let Some(folder) = folders.pop() {
+            let Ok(entries) = self.store.list(&folder) else {
+                continue; // can't read this one, the rest are still worth listing
+            };
+            let (ignored, _) = self.ignore_list(&folder);
+            for (name, info) in entries {
+                if name.starts_with('.') || ignored.is_ignored(&name, info.is_folder) {
+                    continue;
+                }
+                let path = Path::new(&folder).join(&name).display().to_string();
+                if info.is_folder {
+                    if !info.is_link {
+                        folders.push(path);
+                    }
+                } else if self.is_note(Path::new(&name)) {
+                    notes.push(path);
+                }
+            }
+        }
+        notes.sort();
+        notes
+    }
+
+    // The `.trmnotesignore` of a folder in the store (see ignore.rs), a missing one just means nothing is ignored
+    fn ignore_list(&self, folder: &str) -> (IgnoreList, Vec<String>) {
+        match self.store.read(&Path::new(folder).join(IGNORE_FILE).display().to_string()) {
+            Ok(content) => IgnoreList::parse(&String::from_utf8_lossy(&content)),
+            Err(_) => (IgnoreList::default(), vec![]),
+        }
+    }
+
+    // The notes matching find_query, best first (shorter names win a tie), with which chars of each one matched
+    fn find_results(&self) -> Vec<(&str, Vec<usize>)> {
+        let mut results: Vec<(i32, &str, Vec<usize>)> = self
+            .find_notes
+            .iter()
+            .filter_map(|note| fuzzy_match(&self.find_query, note).map(|(score, positions)| (score, note.as_str(), positions)))
+            .collect();
+        results.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.len().cmp(&b.1.len())).then_with(|| a.1.cmp(b.1)));
+        results.into_iter().map(|(_, note, positions)| (note, positions)).collect()
+    }
+
+    // true if the file has one of the note extensions (ignoring case, so NOTES.MD counts)
+    fn is_note(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| self.extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(extension)))
+    }
+
+    // true if the explorer is showing the notes root, not a subfolder
+    fn at_root(&self) -> bool {
+        Path::new(&self.folder).components().eq(Path::new(&self.root).components())
+    }
+
+    // Turns an entry in the explorer into a note name relative to the root, e.g. "todo.txt" in work/ is "work/todo.txt"
+    fn note_name(&self, entry: &str) -> String {
+        match Path::new(&self.folder).strip_prefix(&self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative.join(entry).display().to_string(),
+            _ => entry.to_string(),
+        }
+    }
+
+    /*
+    Opens whatever is picked in the explorer (by Enter in the file selection or a click).
+    `..` goes up a folder, a folder goes into it, and a file gets opened.
+    Returns true if a note was opened, so the file selection knows to close.
+     */
+    fn open_entry(&mut self, entry: &str) -> bool {
+        if entry == ".." {
+            if let Some(parent) = Path::new(&self.folder).parent() {
+                self.folder = parent.display().to_string();
+            }
+        } else if let Some(name) = entry.strip_suffix('/') {
+            let target = Path::new(&self.folder).join(name);
+            // a symlink back to this folder (or one above it) would let us go down forever, so don't follow it
+            let is_link = self.store.info(&self.note_name(name)).is_ok_and(|info| info.is_link);
+            let is_loop = is_link
+                && match (fs::canonicalize(&target), fs::canonicalize(&self.folder)) {
+                    (Ok(target), Ok(here)) => here.starts_with(target),
+                    _ => true, // can't tell where it goes, so don't risk it
+                };
+            if is_loop {
+                self.set_status(format!("Not opening {}, it loops back on itself", name));
+                return false;
+            }
+            self.folder = target.display().to_string();
+        } else {
+            let name = self.note_name(entry);
+            return self.switch_note(&name);
+        }
+        self.file_select_index = 0;
+        self.refresh_notes();
+        false
+    }
+
+    /*
+    Makes sure the notes folder is there, so a fresh setup (or a new folder in the config) just works.
+    If it can't be made (like no permission) the app still starts, with the error in the status line.
+    On the first run (no notes anywhere in the folder) default.txt is made with a short welcome,
+    so there's something to read instead of an empty screen. `welcome_note = false` skips that.
+     */
+    pub fn ensure_folder(&mut self) {
+        if let Err(e) = self.store.prepare() {
+            self.set_status(format!("Failed to create notes folder {}: {}", self.root, e));
+            return;
+        }
+        if self.welcome_note && self.all_notes().is_empty() {
+            // the keys are whatever the config set them to, like the hint for an empty note
+            let label = |action| self.keymap.label(action);
+            let welcome = format!(
+                "Welcome to trmnotes!\n\n\
+                 This is default.txt, a note like any other, so change it or delete it.\n\
+                 Every note is a plain text (or Markdown) file in your notes folder.\n\n\
+                 {}: create a note\n\
+                 {} / {}: open one, from a list or by typing part of its name\n\
+                 {}: save (autosave does it for you too)\n\
+                 {}: every other key\n\
+                 {}: quit\n",
+                label(Action::NewNote),
+                label(Action::OpenNote),
+                label(Action::FindNote),
+                label(Action::Save),
+                label(Action::Help),
+                label(Action::Quit),
+            );
+            // create fails if it's there after all, so it can never write over a default.txt
+            let result = self.store.create("default.txt").and_then(|()| self.store.write("default.txt", welcome.as_bytes()));
+            if let Err(e) = result {
+                self.set_status(format!("Failed to create default.txt: {}", e));
+            }
+        }
+    }
+
+    // Starts on default.txt if it's there, otherwise on an empty unnamed note (which is saved as default.txt)
+    fn open_default(&mut self) -> io::Result<()> {
+        if self.note_exists("default.txt") {
+            self.open_note("default.txt")
+        } else {
+            Ok(())
+        }
+    }
+
+    // Switches to the next sort order and lists the notes again, keeping the same entry selected
+    fn cycle_sort(&mut self) {
+        let selected = self.files.get(self.file_select_index).cloned();
+        self.sort_order = self.sort_order.next();
+        self.refresh_notes();
+        if let Some(index) = selected.and_then(|selected| self.files.iter().position(|file| *file == selected)) {
+            self.file_select_index = index;
+        }
+        self.set_status(format!("Sorting notes by {}", self.sort_order.label()));
+    }
+
+    /*
+    Lists the notes again once the watch says the folder changed, following the explorer into whatever folder it's in.
+    If the watch can't follow (the new folder can't be watched) it's dropped, and F5 is back to being the only way.
+     */
+    fn check_folder_watch(&mut self) {
+        let Some(watch) = &mut self.folder_watch else {
+            return;
+        };
+        if let Err(e) = watch.follow(Path::new(&self.folder)) {
+            self.folder_watch = None;
+            self.set_status(format!("Stopped watching the notes folder ({}), {} refreshes", e, self.keymap.label(Action::Refresh)));
+            return;
+        }
+        if watch.changed() {
+            // the list can change under the file selection, so keep the same entry selected if it's still there
+            let selected = self.files.get(self.file_select_index).cloned();
+            self.refresh_notes();
+            self.file_select_index = selected
+                .and_then(|selected| self.files.iter().position(|file| *file == selected))
+                .unwrap_or(self.file_select_index.min(self.files.len().saturating_sub(1)));
+        }
+    }
+
+    // Shown in the explorer and the file selection when there's nothing in the folder
+    fn no_notes_hint(&self) -> String {
+        format!("No notes yet — {} to create one", self.keymap.label(Action::NewNote))
+    }
+
+    // Same as get_notes, but for key handlers where we can't return the error
+    fn refresh_notes(&mut self) {
+        if let Err(e) = self.get_notes() {
+            self.set_status(format!("Failed to get notes: {}", e));
+        }
+    }
+
+    /*
+    Sets things up to open a file given on the command line.
+    If it's inside the notes folder we keep using that folder,
+    otherwise the file's own folder becomes the notes folder. The file is created if it's missing.
+     */
+    pub fn open_path(&mut self, path: &Path) -> io::Result<()> {
+        let file_name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a file", path.display()))
+        })?;
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let parent = fs::canonicalize(parent)?;
+
+        let inside_folder = fs::canonicalize(&self.root)
+            .ok()
+            .and_then(|folder| parent.strip_prefix(folder).ok().map(Path::to_path_buf));
+        let name = match inside_folder {
+            Some(relative) => relative.join(file_name).display().to_string(),
+            None => {
+                self.root = parent.display().to_string();
+                self.folder = self.root.clone();
+                self.store = Box::new(FsNoteStore::new(&self.root));
+                file_name.to_string_lossy().to_string()
+            }
+        };
+        self.create_note(&name)?;
+        self.open_note(&name)
+    }
+
+    /*
+    Creates an empty note in the folder.
+    If a note with that name is already there we leave it alone and say so,
+    instead of pretending we made a fresh one.
+    The name can be a path like `journal/2024.md`, any folders in it that aren't there yet are made,
+    but it has to stay inside the notes folder.
+     */
+    fn create_note(&mut self, file_name: &str) -> io::Result<CreateOutcome> {
+        check_inside_notes(file_name)?;
+        if self.note_exists(file_name) {
+            return Ok(CreateOutcome::AlreadyExists);
+        }
+        self.store.create(file_name)?;
+        self.get_notes()?; // Refresh the list of files
+        Ok(CreateOutcome::Created)
+    }
+
+    // true if there's already a note with this name in the folder
+    fn note_exists(&self, file_name: &str) -> bool {
+        self.store.info(file_name).is_ok()
+    }
+
+    // true if `name` is a folder in the store, not a note
+    fn is_folder(&self, name: &str) -> bool {
+        self.store.info(name).is_ok_and(|info| info.is_folder)
+    }
+
+    /*
+    Opens a note in its own tab and makes it the current one, returns false if it couldn't be opened.
+    If it's already open we just switch to that tab, so unsaved changes there aren't thrown away.
+     */
+    fn switch_note(&mut self, file_name: &str) -> bool {
+        let before = self.current().display_name().to_string();
+        if let Some(index) = self.buffers.iter().position(|buffer| buffer.display_name() == file_name) {
+            self.set_active(index);
+            self.remember_previous(before);
+            self.update_search_matches();
+            return true;
+        }
+
+        let previous = self.active;
+        self.buffers.push(Buffer { uses_hard_tabs: self.hard_tabs, ..Buffer::new(Some(file_name.to_string())) });
+        self.active = self.buffers.len() - 1;
+        if let Err(e) = self.open_note(file_name) {
+            self.set_status(format!("Failed to open note: {}", e));
+            self.buffers.pop();
+            self.active = previous;
+            false
+        } else {
+            // a note from the last run opens where we left it, each tab keeps its own cursor after that
+            if let Some(position) = self.positions.remove(file_name) {
+                self.restore_position(position);
+            }
+            self.remember_previous(before);
+            self.update_search_matches();
+            true
+        }
+    }
+
+    /*
+    Loads a note from the folder into the current buffer.
+    Everything is read into memory at once, which is fine for notes, but a huge file would freeze the app,
+    so anything over the size limit from the config is refused with an error instead.
+     */
+    fn open_note(&mut self, file_name: &str) -> io::Result<()> {
+        let info = match self.store.info(file_name) {
+            Ok(info) => info,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.set_status(format!("File not found: {}", file_name));
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        if let Some(max) = self.max_file_bytes
+            && info.size > max
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                format!("{} is too big to open ({} MB, the limit is {} MB)", file_name, info.size / (1024 * 1024), max / (1024 * 1024)),
+            ));
+        }
+        let content = self.store.read(file_name)?;
+        let current = self.current_mut();
+        current.load_bytes(content);
+        current.read_only |= info.read_only; // can't be saved either way
+        current.disk_modified = info.modified;
+        current.file_name = Some(file_name.to_string());
+        if current.not_utf8 {
+            self.set_status(format!("{} isn't valid UTF-8, opened read-only", file_name));
+        }
+        Ok(())
+    }
+
+    /*
+    Deletes a note from the folder and refreshes the list.
+    If it was open in a tab, that tab is closed too. If that was the last tab,
+    an empty unnamed note takes its place, nothing is made in the folder until it's saved.
+     */
+    fn delete_note(&mut self, file_name: &str) -> io::Result<()> {
+        self.store.delete(file_name)?;
+        self.get_notes()?;
+        if self.previous_file.as_deref() == Some(file_name) {
+            self.previous_file = None; // nothing to go back to anymore
+        }
+
+        // keep the selection inside the list now that it's one shorter
+        if self.file_select_index >= self.files.len() {
+            self.file_select_index = self.files.len().saturating_sub(1);
+        }
+
+        if let Some(index) = self.buffers.iter().position(|buffer| buffer.display_name() == file_name) {
+            self.buffers.remove(index);
+            if self.active > index || self.active == self.buffers.len() {
+                self.active = self.active.saturating_sub(1);
+            }
+            // the other pane of a split loses its note, or moves along with the tabs after it
+            self.split = match self.split {
+                Some(other) if other == index => None,
+                Some(other) if other > index => Some(other - 1),
+                other => other,
+            }
+            .filter(|other| *other != self.active);
+            if self.buffers.is_empty() {
+                // an unnamed note like on startup, default.txt only gets made if something is saved in it
+                self.buffers.push(Buffer { uses_hard_tabs: self.hard_tabs, ..Buffer::new(None) });
+            }
+        }
+        Ok(())
+    }
+
+    /*
+    Renames a note inside the notes folder.
+    A note that already exists is never overwritten, the store refuses that.
+     */
+    fn rename_note(&mut self, old: &str, new: &str) -> io::Result<()> {
+        if old == new {
+            return Ok(()); // nothing to do
+        }
+        check_inside_notes(new)?;
+        self.store.rename(old, new)?;
+
+        // any tab showing the old name follows the file to its new name
+        for buffer in self.buffers.iter_mut().filter(|buffer| buffer.display_name() == old) {
+            buffer.file_name = Some(new.to_string());
+        }
+        if let Some(position) = self.positions.remove(old) {
+            self.positions.insert(new.to_string(), position);
+        }
+        if self.previous_file.as_deref() == Some(old) {
+            self.previous_file = Some(new.to_string());
+        }
+        self.get_notes()?; // Refresh the list of files
+        Ok(())
+    }
+
+    /*
+    Saves the current buffer to a note in the folder, and says so in the status line (with how many lines it has).
+    An unnamed buffer gets the name it was saved as, so it's a normal note from then on.
+    If `keep_backups` is on the old version is backed up once the new one is written,
+    a backup that fails doesn't stop the save, it's just mentioned in the status line.
+     */
+    fn save_note(&mut self, file_name: &str) -> io::Result<()> {
+        let (trim, single_newline) = (self.trim_trailing_on_save, self.single_trailing_newline);
+        let current = self.current_mut();
+        if trim {
+            current.trim_trailing_whitespace();
+        }
+        if single_newline {
+            current.single_trailing_newline();
+        }
+        let content = current.contents()?;
+        // the old version has to be read before it's written over, but the backups only move along once the save worked
+        let old = if self.keep_backups > 0 { self.store.read(file_name).ok() } else { None };
+        self.store.write(file_name, content.as_bytes())?;
+        let backup = old.map_or(Ok(()), |old| back_up(self.store.as_mut(), file_name, &old, self.keep_backups));
+        let modified = self.store.modified(file_name);
+        let current = self.current_mut();
+        current.mark_saved(modified);
+        if current.file_name.is_none() {
+            current.file_name = Some(file_name.to_string());
+        }
+        let lines = current.text.len();
+        let saved = format!("Saved {} ({} line{})", file_name, lines, if lines == 1 { "" } else { "s" });
+        match backup {
+            Ok(()) => self.set_status(saved),
+            Err(e) => self.set_status(format!("{}, but failed to back up the old version: {}", saved, e)),
+        }
+        Ok(())
+    }
+
+    /*
+    What the status line says when the current note is longer than `warn_lines` or `warn_chars`, if it is.
+    This runs on every draw, so chars are only counted when the note has more bytes than the limit,
+    a note with fewer bytes can't have more chars than that.
+     */
+    fn size_warning(&self) -> Option<String> {
+        let text = &self.current().text;
+        let mut over = vec![];
+        if let Some(max) = self.warn_lines
+            && text.len() > max
+        {
+            over.push(format!("{}/{} lines", text.len(), max));
+        }
+        if let Some(max) = self.warn_chars
+            && text.iter().map(String::len).sum::<usize>() > max
+        {
+            let chars: usize = text.iter().map(|line| line.chars().count()).sum();
+            if chars > max {
+                over.push(format!("{}/{} chars", chars, max));
+            }
+        }
+        if over.is_empty() { None } else { Some(format!("long note: {}", over.join(", "))) }
+    }
+
+    /*
+    Save as, writes the current note to `file_name` and keeps editing it under that name.
+    The file it was opened from is left as it was, and a note already at `file_name` is replaced
+    (the popup asks about that first). A note open in another tab can't be saved over, that tab would be out of date.
+     */
+    fn save_as(&mut self, file_name: &str) -> io::Result<()> {
+        check_inside_notes(file_name)?;
+        if self.buffers.iter().enumerate().any(|(i, buffer)| i != self.active && buffer.display_name() == file_name) {
+            return Err(io::Error::other(format!("{} is open in another tab", file_name)));
+        }
+        self.save_note(file_name)?; // the store makes any folders in the name
+        self.current_mut().file_name = Some(file_name.to_string());
+        self.get_notes()?; // Refresh the list of files
+        Ok(())
+    }
+
+    // Saves as `file_name` from the popup, closing it if that worked and saying what went wrong if it didn't
+    fn save_as_and_close(&mut self, file_name: &str) {
+        match self.save_as(file_name) {
+            Ok(()) => self.close_modal(),
+            Err(e) => {
+                self.set_status(format!("Failed to save note: {}", e));
+                self.mode = Mode::CreateNote; // still there, so the name can be fixed
+            }
+        }
+    }
+
+    /*
+    Renders the current note from Markdown into `<name>.html` next to it (overwriting an older export).
+    It's made from what's in the editor, so unsaved changes are included. Returns the name of the new file.
+     */
+    fn export_html(&mut self) -> io::Result<String> {
+        let name = Path::new(self.current().display_name());
+        let title = name.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().to_string());
+        let file_name = name.with_extension("html").display().to_string();
+        let html = to_html(&title, &self.current().text);
+        self.store.write(&file_name, html.as_bytes())?;
+        Ok(file_name)
+    }
+
+    // An unnamed buffer is saved as default.txt, but never over one that's already there (something else made that one)
+    fn default_taken(&self, buffer: &Buffer) -> bool {
+        buffer.file_name.is_none() && self.store.info(buffer.display_name()).is_ok()
+    }
+
+    /*
+    Saves every open buffer with unsaved changes, stopping at the first one that fails.
+    Notes that were changed on disk by something else are skipped (and stay unsaved),
+    there's no one to ask when autosaving, so that's left for Ctrl+S. They're reported as an error at the end.
+    So is an unnamed buffer when there's already a default.txt, otherwise it's named default.txt like `save_note` does.
+    Backups that fail don't stop anything, they end up in the status line.
+     */
+    fn save_all(&mut self) -> io::Result<()> {
+        let mut changed = vec![];
+        let mut taken = vec![];
+        let mut not_backed_up = vec![];
+        for i in 0..self.buffers.len() {
+            if !self.buffers[i].dirty {
+                continue;
+            }
+            if self.default_taken(&self.buffers[i]) {
+                taken.push(self.buffers[i].display_name().to_string());
+                continue;
+            }
+            let buffer = &mut self.buffers[i];
+            let file_name = buffer.display_name().to_string();
+            if buffer.changed_since(self.store.modified(&file_name)) {
+                changed.push(buffer.display_name().to_string());
+                continue;
+            }
+            if self.trim_trailing_on_save {
+                buffer.trim_trailing_whitespace();
+            }
+            if self.single_trailing_newline {
+                buffer.single_trailing_newline();
+            }
+            let content = buffer.contents()?;
+            let old = if self.keep_backups > 0 { self.store.read(&file_name).ok() } else { None };
+            self.store.write(&file_name, content.as_bytes())?;
+            if old.is_some_and(|old| back_up(self.store.as_mut(), &file_name, &old, self.keep_backups).is_err()) {
+                not_backed_up.push(buffer.display_name().to_string());
+            }
+            buffer.mark_saved(self.store.modified(&file_name));
+            if buffer.file_name.is_none() {
+                buffer.file_name = Some(file_name);
+            }
+        }
+        if !not_backed_up.is_empty() {
+            self.set_status(format!("Failed to back up {}", not_backed_up.join(", ")));
+        }
+        if !changed.is_empty() {
+            return Err(io::Error::other(format!("{} changed on disk, save with {}", changed.join(", "), self.keymap.label(Action::Save))));
+        }
+        if let Some(name) = taken.first() {
+            return Err(io::Error::other(format!("{} already exists, save the new note with {}", name, self.keymap.label(Action::SaveAs))));
+        }
+        Ok(())
+    }
+}
+
+/*
+Draws a scrollbar over the right border of a bordered panel, showing where `position` (the first visible row) is out of `rows`.
+When everything fits there's nothing to scroll, so the plain border is left alone.
+ */
+fn render_scrollbar(panel: Rect, rows: usize, position: usize, buf: &mut ratatui::buffer::Buffer) {
+    let track = panel.inner(Margin { vertical: 1, horizontal: 0 }); // between the corners
+    if rows <= track.height as usize {
+        return;
+    }
+    let mut state = ScrollbarState::new(rows).position(position).viewport_content_length(track.height as usize);
+    Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None)
+        .render(track, buf, &mut state);
+}
+
+/*
+Splits a styled line into rows of at most `width` chars, keeping the styles of each piece.
+We wrap on characters (not words) so the cursor math in `cursor_visual` is simple and always matches.
+ */
+fn wrap_line(line: Line<'_>, width: usize) -> Vec<Line<'static>> {
+    let mut rows = vec![Line::default()];
+    if width == 0 {
+        return rows;
+    }
+    let mut used = 0; // chars already on the last row
+    for span in line.spans {
+        let mut piece = String::new();
+        for c in span.content.chars() {
+            if used == width {
+                rows.last_mut().unwrap().push_span(Span::styled(std::mem::take(&mut piece), span.style));
+                rows.push(Line::default());
+                used = 0;
+            }
+            piece.push(c);
+            used += 1;
+        }
+        rows.last_mut().unwrap().push_span(Span::styled(piece, span.style));
+    }
+    if used == width {
+        rows.push(Line::default()); // matches the extra row in `wrapped_rows`
+    }
+    rows
+}
+
+/*
+Fills in a date format like "%Y-%m-%d %H:%M" (the usual strftime letters, but only the common ones).
+%Y is the year, %m %d the month and day, %H %M %S the time, and %% is a plain %.
+Anything else is left as it is, so a typo shows up in the note instead of vanishing.
+ */
+fn format_date(format: &str, date: &OffsetDateTime) -> String {
+    let mut formatted = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => formatted.push_str(&format!("{:04}", date.year())),
+            Some('m') => formatted.push_str(&format!("{:02}", date.month() as u8)),
+            Some('d') => formatted.push_str(&format!("{:02}", date.day())),
+            Some('H') => formatted.push_str(&format!("{:02}", date.hour())),
+            Some('M') => formatted.push_str(&format!("{:02}", date.minute())),
+            Some('S') => formatted.push_str(&format!("{:02}", date.second())),
+            Some('%') => formatted.push('%'),
+            Some(other) => {
+                formatted.push('%');
+                formatted.push(other);
+            }
+            None => formatted.push('%'),
+        }
+    }
+    formatted
+}
+
+/*
+The number shown next to line `i` (counting from 0) in the gutter.
+Normally that's just the line number, with relative numbers on it's how far the line is from the cursor,
+except the cursor's line which always shows its real number (like vim's `relativenumber` with `number`).
+ */
+fn line_number(i: usize, cursor_y: usize, relative: bool) -> usize {
+    if relative && i != cursor_y { i.abs_diff(cursor_y) } else { i + 1 }
+}
+
+/*
+Note names are paths relative to the notes folder, this makes sure one can't point outside of it,
+like `../secrets.txt` or `/etc/passwd`. Only plain folder and file names are allowed in the path.
+ */
+fn check_inside_notes(file_name: &str) -> io::Result<()> {
+    let inside = Path::new(file_name)
+        .components()
+        .all(|component| matches!(component, std::path::Component::Normal(_) | std::path::Component::CurDir));
+    if inside {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is outside the notes folder", file_name)))
+    }
+}
+
+// A typed note name with the `.` and empty parts taken out, so the same note always gets the same name
+fn clean_note_name(name: &str) -> String {
+    Path::new(name)
+        .components()
+        .filter(|component| !matches!(component, std::path::Component::CurDir))
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/*
+Whether a key that isn't in the keymap (outside of any popup) would change the text, these are ignored in read-only mode.
+Keys that are in the keymap go by `Action::edits` instead.
+ */
+fn is_edit_key(key_event: &KeyEvent) -> bool {
+    let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+    match key_event.code {
+        KeyCode::Backspace | KeyCode::Delete | KeyCode::Enter => true,
+        KeyCode::Tab | KeyCode::BackTab => !ctrl,
+        KeyCode::Char(_) => typed_char(key_event).is_some(),
+        _ => false,
+    }
+}
+
+/*
+The char a key types into the note, if any. Shift is fine (that's just capitals), but a Ctrl or Alt chord
+that isn't in the keymap types nothing, so pressing an unbound one like Ctrl+B doesn't leave a "b" behind.
+Ctrl and Alt together is let through though, that's how AltGr chars (like @ on a German keyboard) arrive on Windows.
+ */
+fn typed_char(key_event: &KeyEvent) -> Option<char> {
+    let chord = key_event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+    if chord == KeyModifiers::CONTROL || chord == KeyModifiers::ALT {
+        return None;
+    }
+    key_event.code.as_char()
+}
+
+/*
+Swaps spaces for a dim · so they can be seen, only on screen, the text itself isn't touched.
+Tabs are dimmed but stay tabs, `expand_tabs` draws them (as a → when showing whitespace) later on.
+Every char is swapped for exactly one other char, so the selection still lines up with the real text.
+Whitespace from `trailing_from` (a char column) to the end of the line is trailing, and gets a red background.
+ */
+fn visible_whitespace(line: Line<'_>, trailing_from: usize) -> Line<'static> {
+    let mut visible = Line::default();
+    let mut col = 0; // char column of the next char
+    for span in line.spans {
+        for c in span.content.chars() {
+            let shown = match c {
+                ' ' => Some('·'),
+                '\t' => Some('\t'),
+                _ => None,
+            };
+            match shown {
+                Some(symbol) if col >= trailing_from => {
+                    visible.push_span(Span::styled(symbol.to_string(), span.style.fg(Color::DarkGray).bg(Color::Red)));
+                }
+                Some(symbol) => visible.push_span(Span::styled(symbol.to_string(), span.style.fg(Color::DarkGray))),
+                None => visible.push_span(Span::styled(c.to_string(), span.style)),
+            }
+            col += 1;
+        }
+    }
+    visible
+}
+
+/*
+Draws every tab as spaces up to the next tab stop (every `tab_width` columns), the text itself keeps the \t.
+With `arrow` the first of those spaces is a → instead, so tabs can be told apart from spaces.
+This is the last step before wrapping, since everything before it goes by chars, not columns.
+`start_col` is the column the line starts at, it's more than 0 when only the end part of a long line is drawn.
+ */
+fn expand_tabs(line: Line<'_>, start_col: usize, tab_width: usize, arrow: bool) -> Line<'static> {
+    let mut expanded = Line::default();
+    let mut col = start_col; // screen column of the next char
+    for span in line.spans {
+        let mut content = String::new();
+        for c in span.content.chars() {
+            if c == '\t' {
+                let stop = next_tab_stop(col, tab_width);
+                content.push(if arrow { '→' } else { ' ' });
+                content.push_str(&" ".repeat(stop - col - 1));
+                col = stop;
+            } else {
+                content.push(c);
+                col += 1;
+            }
+        }
+        expanded.push_span(Span::styled(content, span.style));
+    }
+    expanded
+}
+
+/*
+Keeps only columns `from..from + width` of a styled line (after tabs are expanded, so a column is a char).
+This is how long lines scroll sideways, and how only the rows in view of a wrapped line get wrapped.
+ */
+fn cut_columns(line: Line<'_>, from: usize, width: usize) -> Line<'static> {
+    let mut cut = Line::default();
+    let mut col = 0; // column of the first char of the span
+    for span in line.spans {
+        if col >= from + width {
+            break;
+        }
+        let len = span.content.chars().count();
+        if col + len > from {
+            let piece: String = span.content.chars().skip(from.saturating_sub(col)).take(from + width - col.max(from)).collect();
+            cut.push_span(Span::styled(piece, span.style));
+        }
+        col += len;
+    }
+    cut
+}
+
+/*
+Adds `style` on top of the existing styles for the chars in `start..end` of a line (used to show the selection).
+Spans that cross either edge get split in two.
+ */
+fn style_range(line: Line<'_>, start: usize, end: usize, style: Style) -> Line<'static> {
+    let mut styled = Line::default();
+    let mut col = 0; // char column of the next char
+    for span in line.spans {
+        let mut piece = String::new();
+        let mut piece_in_range = false;
+        for c in span.content.chars() {
+            let in_range = col >= start && col < end;
+            if in_range != piece_in_range && !piece.is_empty() {
+                let piece_style = if piece_in_range { span.style.patch(style) } else { span.style };
+                styled.push_span(Span::styled(std::mem::take(&mut piece), piece_style));
+            }
+            piece_in_range = in_range;
+            piece.push(c);
+            col += 1;
+        }
+        if !piece.is_empty() {
+            styled.push_span(Span::styled(piece, if piece_in_range { span.style.patch(style) } else { span.style }));
+        }
+    }
+    styled
+}
+
+/*
+Works out a rectangle of `width` x `height` centered in `area` for a modal (popup).
+If the terminal is smaller than that, the modal is shrunk to fit instead of going off screen.
+Since the modal is drawn on top of everything else, we manually clear it first.
+ */
+fn modal_area(area: Rect, width: u16, height: u16, buf: &mut ratatui::buffer::Buffer) -> Rect {
+    let (width, height) = (width.min(area.width), height.min(area.height));
+    let x = (area.width.saturating_sub(width)) / 2 + area.x;
+    let y = (area.height.saturating_sub(height)) / 2 + area.y;
+    let modal = Rect::new(x, y, width, height);
+    clear_area(modal, buf);
+    modal
+}
+
+// Cuts `name` down to `width` chars, ending in … if anything was cut off, so it fits on one row
+fn truncate_name(name: &str, width: usize) -> String {
+    if name.chars().count() <= width {
+        return name.to_string();
+    }
+    let mut short: String = name.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 {
+        short.push('…');
+    }
+    short
+}
+
+// Roughly how many minutes it takes to read `words` words, rounded up so a short note is still 1 minute
+fn reading_minutes(words: usize) -> usize {
+    words.div_ceil(WORDS_PER_MINUTE)
+}
+
+// Fills an area with spaces so whatever was rendered underneath doesn't show through
+fn clear_area(area: Rect, buf: &mut ratatui::buffer::Buffer) {
+    for y in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+            if let Some(cell) = buf.cell_mut((x, y)) {
+                cell.set_symbol(" ");
+            }
+        }
+    }
+}
+
+/*
+
+Explanation of the code:
+Bascially this is the rendering of the widget.
+Nothing here is what "draws" it on the screen, but rather how it is structured.
+This is the implementation of the `Widget` trait for the `App` struct.
+Since we're implementing the `Widget` trait, we need to define the `render` method.
+Now that we have the widget implemented we ccan turn our app struct into a widget.
+
+*/
+impl Widget for &App {
+    fn render(self, area: Rect, buf: &mut ratatui::buffer::Buffer) {
+
+        // Split the area into left and right panels
+        let layout = self.layout(area);
+        let current = self.current();
+        let theme = &self.theme;
+        
+        // Block on the right, this displays the content of the file and the editor
+        let mut instructions = Line::from(vec![
+            " Help ".bold(),
+            format!("<{}> ", self.keymap.label(Action::Help)).fg(theme.status).bold(),
+            " Quit ".bold(),
+            format!("<{}> ", self.keymap.label(Action::Quit)).red().bold(),
+            " Toggle Explorer ".bold(),
+            format!("<{}> ", self.keymap.label(Action::ToggleExplorer)).fg(theme.status).bold(),
+            // shown counting from 1 like the line number panel (and go to line), internally everything counts from 0
+            // the line is highlighted on the last line, and the column at the end of the line
+            " Ln ".bold(),
+            if current.cursor_y + 1 >= current.text.len() {
+                (current.cursor_y + 1).to_string().fg(theme.cursor_line).bold()
+            } else {
+                (current.cursor_y + 1).to_string().fg(theme.line_numbers).bold()
+            },
+
+            ", Col ".bold(),
+
+            // in chars, not bytes, so "é" is one column like it looks
+            if current.cursor_x == current.text[current.cursor_y].chars().count() {
+                (current.cursor_x + 1).to_string().fg(theme.cursor_line).bold()
+            } else {
+                (current.cursor_x + 1).to_string().fg(theme.line_numbers).bold()
+            },
+            " ".into(),
+        ]);
+
+        // how much is selected, line breaks count as a char like they do when pasting it
+        if let Some(((start_y, _), (end_y, _))) = current.selection()
+            && let Some(text) = current.selected_text()
+        {
+            let lines = end_y - start_y + 1;
+            instructions.push_span(format!("({} selected, {} line{}) ", text.chars().count(), lines, if lines == 1 { "" } else { "s" }).bold());
+        }
+
+        // flash "saved" for a couple seconds after an autosave
+        if self.autosaved_at.is_some_and(|at| at.elapsed() < SAVED_FLASH) {
+            instructions.push_span(" saved ✔ ".green().bold());
+        }
+
+        // the latest message, until it times out (see `handle_events`)
+        if let Some((message, _)) = &self.status_message {
+            instructions.push_span(format!(" {} ", message).fg(theme.status).bold());
+        }
+
+        // a nudge to split the note up once it's past the limits from the config
+        if let Some(warning) = self.size_warning() {
+            instructions.push_span(format!(" {} ", warning).yellow());
+        }
+
+        // which vim mode we're in, so typing into normal mode by accident isn't a surprise
+        if self.vim_mode {
+            instructions.push_span(if self.vim_normal { " NORMAL ".bold().black().bg(theme.status) } else { " INSERT ".bold().green() });
+        }
+
+        // the counts take up a lot of room, so only show them when there's space
+        if area.width >= MIN_WIDTH_FOR_COUNTS {
+            let (lines, words, chars) = current.stats();
+            instructions.push_span(format!(" {} lines, {} words, {} chars ", lines, words, chars).bold());
+        }
+
+        let text_width = layout.text.width as usize;
+        let text_rows = layout.text.height as usize;
+        let (first_line, skipped_rows) = current.first_visible_line(text_width, self.wrap_enabled, self.tab_width);
+        // search highlights take over from the Markdown ones while searching
+        let searching = matches!(self.mode, Mode::Search | Mode::Replace) && !self.search_query.is_empty();
+        self.render_text(current, layout.text, searching, buf);
+
+        // an empty note that was never named (like on a first run without the welcome note) gets a hint on what to do
+        if current.file_name.is_none() && !current.dirty && current.text.iter().all(String::is_empty) {
+            let hint = Text::from(vec![
+                Line::from("No note open yet"),
+                Line::from(format!("Start typing, {} saves it as default.txt", self.keymap.label(Action::Save))),
+                Line::from(format!(
+                    "{}: new note | {}: open one | {}: help",
+                    self.keymap.label(Action::NewNote),
+                    self.keymap.label(Action::OpenNote),
+                    self.keymap.label(Action::Help),
+                )),
+            ])
+            .dark_gray()
+            .centered();
+            let inner = layout.text;
+            let hint_area = Rect { y: inner.y + inner.height.saturating_sub(3) / 2, height: inner.height.min(3), ..inner };
+            hint.render(hint_area, buf);
+        }
+
+        // the tab bar, every open file with a * if it has unsaved changes, and the one we're editing highlighted
+        let mut tab_bar = Line::default();
+        if !self.title.is_empty() {
+            tab_bar.push_span(format!(" {} ", self.title).bold().fg(theme.title));
+        }
+        for (i, buffer) in self.buffers.iter().enumerate() {
+            let tab = format!(
+                " {}{}{} ",
+                buffer.tab_name(),
+                if buffer.dirty { "*" } else { "" },
+                if buffer.not_utf8 { " [not UTF-8]" } else if buffer.read_only { " [RO]" } else { "" },
+            );
+            if i == self.active {
+                tab_bar.push_span(tab.bold().black().bg(theme.title));
+            } else {
+                tab_bar.push_span(tab.fg(theme.title));
+            }
+        }
+        let flashing = self.flash_until.is_some_and(|until| Instant::now() < until);
+        let editor_block = Block::bordered()
+            .title(tab_bar)
+            .title_bottom(instructions.centered())
+            .border_set(border::PLAIN)
+            .border_style(if flashing { Style::new().reversed() } else { Style::new() });
+
+        // Rendering the line numbers on the left side
+        // We create a vector of lines, each line is a number from 1 to the number of lines in the text
+        // they're all padded to the width of the biggest one, so going from line 9 to 10 doesn't shift anything
+        // like the text only the ones in view are made
+        let number_width = current.text.len().to_string().len();
+        let line_numbers: Vec<Line> = (first_line..current.text.len())
+            .take(text_rows)
+            .map(|i| {
+                let number = format!("{:>number_width$}", line_number(i, current.cursor_y, self.relative_line_numbers));
+                let color = if i == current.cursor_y { theme.cursor_line } else { theme.line_numbers };
+                Line::from(number.fg(color).bold())
+            })
+            .collect();
+        // with wrapping on a long line takes up several rows, so pad with blank rows to keep the numbers lined up
+        let line_numbers: Vec<Line> = if self.wrap_enabled {
+            line_numbers
+                .into_iter()
+                .zip(&current.text[first_line..])
+                .flat_map(|(number, line)| {
+                    std::iter::once(number).chain(std::iter::repeat_n(Line::from(""), wrapped_rows(line, text_width, self.tab_width) - 1))
+                })
+                .skip(skipped_rows)
+                .take(text_rows)
+                .collect()
+        } else {
+            line_numbers
+        };
+        let line_numbers_text = Text::from(line_numbers);
+        let line_numbers_paragraph = Paragraph::new(line_numbers_text)
+            .block(Block::default().borders(ratatui::widgets::Borders::ALL))
+            .alignment(Alignment::Right)
+            .wrap(ratatui::widgets::Wrap { trim: true });
+        line_numbers_paragraph.render(layout.line_numbers, buf);
+
+        // lines changed since the last save get a bar on the left border of the line numbers (so the numbers keep their room),
+        // green for new lines and yellow for edited ones
+        let changes = current.line_changes().into_iter().zip(&current.text).flat_map(|(change, line)| {
+            let rows = if self.wrap_enabled { wrapped_rows(line, text_width, self.tab_width) } else { 1 };
+            std::iter::repeat_n(change, rows)
+        });
+        let gutter_rows = layout.line_numbers.y + 1..layout.line_numbers.bottom().saturating_sub(1);
+        for (y, change) in gutter_rows.zip(changes.skip(current.scroll_y)) {
+            let color = match change {
+                Some(LineChange::Added) => Color::Green,
+                Some(LineChange::Modified) => Color::Yellow,
+                None => continue,
+            };
+            if let Some(cell) = buf.cell_mut((layout.line_numbers.x, y)) {
+                cell.set_symbol("┃").set_fg(color);
+            }
+        }
+
+        if self.explorer_open {
+            // Block on the left, this displays the files, the one that's open is marked like in the file selection
+            // every file gets exactly one row (long names are cut short) so rows line up with the list for scrolling and clicks
+            let files_width = layout.explorer.width.saturating_sub(2) as usize;
+            let files_paragraph = Paragraph::new(
+                Text::from(self.files.iter().map(|file| {
+                    if self.note_name(file) == current.display_name() {
+                        Line::from(truncate_name(&format!("▸{}", file), files_width).bold().green())
+                    } else {
+                        Line::from(truncate_name(file, files_width))
+                    }
+                }).collect::<Vec<Line>>())
+            )
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL))
+                .scroll((self.explorer_scroll as u16, 0));
+            // inside a subfolder the title says which one
+            let mut files_title = if self.at_root() { " Files ".to_string() } else { format!(" Files — {} ", self.note_name("")) };
+            if self.sort_order != SortOrder::Name {
+                files_title.push_str(&format!("by {} ", self.sort_order.label()));
+            }
+            let files_block = Block::bordered()
+                .title(files_title.bold().fg(theme.title))
+                .border_set(border::PLAIN);
+            let files_area = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(1)])
+                .split(layout.explorer);
+
+            files_paragraph.render(files_area[0], buf);
+            // an empty folder says what to do instead of showing nothing, wrapped since the explorer is narrow
+            if self.files.is_empty() {
+                Paragraph::new(self.no_notes_hint().dark_gray())
+                    .wrap(ratatui::widgets::Wrap { trim: true })
+                    .render(layout.explorer.inner(Margin::new(1, 1)), buf);
+            }
+
+            files_block.render(layout.explorer, buf);
+        }
+        editor_block.render(layout.editor, buf);
+
+        // scrollbars go on top of the right borders, so they don't take any room away from the text
+        render_scrollbar(layout.editor, self.visual_rows(current, text_width), current.scroll_y, buf);
+
+        // the other pane of a split, just the note with its name on top, dimmed since the keys don't go there
+        if let (Some(pane), Some(other)) = (layout.other, self.split) {
+            let buffer = &self.buffers[other];
+            let text_area = self.text_area(pane);
+            self.render_text(buffer, text_area, false, buf);
+            let title = format!(" {}{} ", buffer.tab_name(), if buffer.dirty { "*" } else { "" });
+            Block::bordered()
+                .title(title.fg(theme.title))
+                .border_set(border::PLAIN)
+                .border_style(Style::new().dark_gray())
+                .render(pane, buf);
+            render_scrollbar(pane, self.visual_rows(buffer, text_area.width as usize), buffer.scroll_y, buf);
+        }
+        if self.explorer_open {
+            render_scrollbar(layout.explorer, self.files.len(), self.explorer_scroll, buf);
+        }
+
+        // Rendering the help menu if it's open
+        if self.mode == Mode::Help {
+            // every command with whatever keys it has (they can be changed in the config), plus the fixed keys
+            // in two columns, otherwise it wouldn't fit on most screens
+            let mut help_lines: Vec<String> = DEFAULT_KEYS
+                .iter()
+                .map(|(action, _, description, _)| format!("{}: {}", self.keymap.label(*action), description))
+                .collect();
+            help_lines.push("Tab / Shift+Tab: Indent".to_string());
+            help_lines.push("Shift+Arrows: Select".to_string());
+            let column_height = help_lines.len().div_ceil(2);
+            let help_area = modal_area(area, 84, column_height as u16 + 2, buf);
+            Block::default().borders(ratatui::widgets::Borders::ALL).title(" Help ".bold().fg(theme.title)).render(help_area, buf);
+
+            let columns = Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).split(help_area.inner(Margin::new(1, 1)));
+            for (column, lines) in columns.iter().zip(help_lines.chunks(column_height)) {
+                let column_width = column.width.saturating_sub(1) as usize; // a space between the columns
+                let text = Text::from(lines.iter().map(|line| Line::from(truncate_name(line, column_width))).collect::<Vec<_>>());
+                Paragraph::new(text).render(*column, buf);
+            }
+        }
+
+        // the note info popup, everything comes from the buffer so unsaved changes count too
+        if self.mode == Mode::Info {
+            let info_area = modal_area(area, 50, 11, buf);
+            let (lines, words, chars) = current.stats();
+            let path = Path::new(&self.root).join(current.display_name());
+            // the size is what's saved, so it's the one thing that can lag behind the editor
+            let size = match self.store.info(current.display_name()) {
+                Ok(info) => format!("{} bytes", info.size),
+                Err(_) => "not saved yet".to_string(),
+            };
+            let info_text = Text::from(vec![
+                Line::from(vec!["Note: ".bold(), current.display_name().into()]),
+                Line::from(vec!["Path: ".bold(), path.display().to_string().into()]),
+                Line::from(vec!["Lines: ".bold(), lines.to_string().into()]),
+                Line::from(vec!["Words: ".bold(), words.to_string().into()]),
+                Line::from(vec!["Characters: ".bold(), chars.to_string().into()]),
+                Line::from(vec!["On disk: ".bold(), size.into()]),
+                Line::from(vec!["Reading time: ".bold(), format!("~{} min", reading_minutes(words)).into()]),
+                Line::from(vec!["Close: ".into(), "Esc".bold().red()]),
+            ]);
+            let info_paragraph = Paragraph::new(info_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Note Info ".bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: false });
+            info_paragraph.render(info_area, buf);
+        }
+
+        // rednering the create note block if in note creation mode
+        if self.mode == Mode::CreateNote {
+            // preparing create note area
+            let create_note_width = 35;
+            let create_note_height = 8;
+            let create_note_area = modal_area(area, create_note_width, create_note_height, buf);
+
+            let title = if self.saving_as { "Save As" } else { "Create Note" };
+            let name = clean_note_name(&self.new_file_name); // what Enter will go by
+            let create_note_text = Text::from(vec![
+                Line::from(format!("{}:", title)),
+                Line::from(format!("Name: {}", self.new_file_name)),
+                // checked as the user types, so opening an old note by accident is never a surprise
+                if name.is_empty() {
+                    Line::from("")
+                } else if self.is_folder(&name) {
+                    Line::from("that's a folder".red())
+                } else if self.saving_as && name == current.display_name() {
+                    Line::from("same note — will just save".green())
+                } else if self.saving_as && self.note_exists(&name) {
+                    Line::from("already exists — will ask first".yellow())
+                } else if self.note_exists(&name) {
+                    Line::from("already exists — will open".yellow())
+                } else if self.saving_as {
+                    Line::from("will save a copy".green())
+                } else {
+                    Line::from("will create new".green())
+                },
+                Line::from(vec![
+                    if self.saving_as { "Save: ".into() } else { "Create: ".into() },
+                    "Enter".bold().green(),
+                    " | Cancel: ".into(),
+                    "Esc".bold().red(),
+                ]),
+            ]);
+            let create_note_paragraph = Paragraph::new(create_note_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(format!(" {} ", title).bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            create_note_paragraph.render(create_note_area, buf);
+        }
+
+        // rendering the search prompt along the bottom of the editor
+        if self.mode == Mode::Search {
+            let editor = layout.editor;
+            let search_area = Rect::new(editor.x, editor.bottom().saturating_sub(3), editor.width, 3.min(editor.height));
+            clear_area(search_area, buf);
+
+            let counter = self.match_counter();
+            let search_line = Line::from(vec![
+                "Find: ".bold(),
+                self.search_query.as_str().into(),
+                "  ".into(),
+                if self.search_matches.is_empty() { counter.red() } else { counter.green() },
+            ]);
+            let search_paragraph = Paragraph::new(search_line)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Search ".bold().fg(theme.title)));
+            search_paragraph.render(search_area, buf);
+        }
+
+        // rendering the find and replace prompt, same place as the search prompt but one line taller
+        if self.mode == Mode::Replace {
+            let editor = layout.editor;
+            let replace_area = Rect::new(editor.x, editor.bottom().saturating_sub(4), editor.width, 4.min(editor.height));
+            clear_area(replace_area, buf);
+
+            // the field we're typing into gets a marker so it's obvious where keys go
+            let marker = |active: bool| if active { "> ".fg(theme.status).bold() } else { "  ".into() };
+            let counter = self.match_counter();
+            let replace_text = Text::from(vec![
+                Line::from(vec![
+                    marker(self.replace_editing_find),
+                    "Find:    ".bold(),
+                    self.search_query.as_str().into(),
+                    "  ".into(),
+                    if self.search_matches.is_empty() { counter.red() } else { counter.green() },
+                ]),
+                Line::from(vec![
+                    marker(!self.replace_editing_find),
+                    "Replace: ".bold(),
+                    self.replace_query.as_str().into(),
+                ]),
+            ]);
+            let replace_paragraph = Paragraph::new(replace_text)
+                .block(
+                    Block::default()
+                        .borders(ratatui::widgets::Borders::ALL)
+                        .title(" Replace ".bold().fg(theme.title))
+                        .title_bottom(" Tab: switch | Enter: replace | Ctrl+A: all | Esc ".bold()),
+                );
+            replace_paragraph.render(replace_area, buf);
+        }
+
+        // rendering the rename prompt, same layout as creating a note
+        if self.mode == Mode::Rename {
+            let rename_area = modal_area(area, 40, 8, buf);
+
+            let rename_text = Text::from(vec![
+                Line::from("Rename Note:"),
+                Line::from(format!("Name: {}", self.rename_buffer)),
+                match &self.rename_error {
+                    Some(error) => Line::from(error.as_str().red()),
+                    None => Line::from(""),
+                },
+                Line::from(vec![
+                    "Rename: ".into(),
+                    "Enter".bold().green(),
+                    " | Cancel: ".into(),
+                    "Esc".bold().red(),
+                ]),
+            ]);
+            let rename_paragraph = Paragraph::new(rename_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Rename Note ".bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            rename_paragraph.render(rename_area, buf);
+        }
+
+        // rendering the go to line prompt, a smaller version of the rename one
+        if self.mode == Mode::GoTo {
+            let goto_area = modal_area(area, 30, 7, buf);
+
+            let goto_text = Text::from(vec![
+                Line::from(format!("Line (1-{}): {}", current.text.len(), self.goto_buffer)),
+                match &self.goto_error {
+                    Some(error) => Line::from(error.as_str().red()),
+                    None => Line::from(""),
+                },
+                Line::from(vec![
+                    "Go: ".into(),
+                    "Enter".bold().green(),
+                    " | Cancel: ".into(),
+                    "Esc".bold().red(),
+                ]),
+            ]);
+            let goto_paragraph = Paragraph::new(goto_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Go To Line ".bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            goto_paragraph.render(goto_area, buf);
+        }
+
+        // rendering the file selection mode if it's open
+        if matches!(self.mode, Mode::SelectFile | Mode::DeleteConfirm) {
+            // preparing file selection area
+            let file_select_width = 44;
+            let file_select_height = 5 + self.files.len().max(1) as u16; // 5 for the instructions + number of files (or the hint)
+            let file_select_area = modal_area(area, file_select_width, file_select_height, buf);
+            // on a small screen not every file fits, so only show the ones around the selection
+            let visible_files = file_select_area.height.saturating_sub(5) as usize;
+            let first_file = scroll_offset(0, self.file_select_index, visible_files);
+
+            // Prepare the text for the file selection menu
+            let name_width = file_select_area.width.saturating_sub(2) as usize;
+            let mut file_lines: Vec<Line> = self.files.iter().enumerate().skip(first_file).take(visible_files).map(|(i, file)| {
+                let name = truncate_name(file, name_width); // one row each, or the selection wouldn't line up
+                if i == self.file_select_index {
+                    Line::from(name.bold().yellow()) // Highlight the selected file
+                } else if self.note_name(file) == current.display_name() {
+                    Line::from(name.bold().green()) // Highlight the current file
+                } else {
+                    Line::from(name)
+                }
+            }).collect();
+            if self.files.is_empty() {
+                file_lines.push(Line::from(self.no_notes_hint().dark_gray()));
+            }
+
+            // Add instructions at the bottom
+            file_lines.push(Line::from(""));
+            file_lines.push(Line::from(vec![
+                "Select: ".into(),
+                "Enter".bold().green(),
+                " | Delete: ".into(),
+                "d".bold().red(),
+                " | Cancel: ".into(),
+                "Esc".bold().red(),
+            ]));
+            file_lines.push(Line::from(vec!["Type a name to open or create: ".into(), "Tab".bold().green()]));
+
+            let file_select_text = Text::from(file_lines);
+            let file_select_paragraph = Paragraph::new(file_select_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Select File ".bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            file_select_paragraph.render(file_select_area, buf);
+        }
+
+        // rendering the fuzzy finder, the chars that matched the query are underlined
+        if self.mode == Mode::FindFile {
+            let results = self.find_results();
+            let find_height = 4 + results.len().clamp(1, 12) as u16; // the query, the results and the instructions
+            let find_area = modal_area(area, 50, find_height, buf);
+            let visible_results = find_area.height.saturating_sub(4) as usize;
+            let first_result = scroll_offset(0, self.find_index, visible_results);
+
+            let name_width = find_area.width.saturating_sub(2) as usize;
+            let mut find_lines = vec![Line::from(vec!["Find: ".bold(), self.find_query.as_str().into()])];
+            if results.is_empty() {
+                find_lines.push(Line::from("No matching notes".dark_gray()));
+            }
+            for (i, (note, positions)) in results.iter().enumerate().skip(first_result).take(visible_results) {
+                let style = if i == self.find_index { Style::new().bold().yellow() } else { Style::new() };
+                // cut long names short the same way truncate_name does, but char by char to keep the underlines
+                let cut = note.chars().count() > name_width;
+                let shown = if cut { name_width.saturating_sub(1) } else { name_width };
+                let mut spans: Vec<Span> = note
+                    .chars()
+                    .take(shown)
+                    .enumerate()
+                    .map(|(j, c)| {
+                        let char_style = if positions.contains(&j) { style.underlined() } else { style };
+                        Span::styled(c.to_string(), char_style)
+                    })
+                    .collect();
+                if cut && name_width > 0 {
+                    spans.push(Span::styled("…", style));
+                }
+                find_lines.push(Line::from(spans));
+            }
+            find_lines.push(Line::from(vec![
+                "Open: ".into(),
+                "Enter".bold().green(),
+                " | Cancel: ".into(),
+                "Esc".bold().red(),
+            ]));
+            let find_paragraph = Paragraph::new(Text::from(find_lines))
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Find Note ".bold().fg(theme.title)));
+            find_paragraph.render(find_area, buf);
+        }
+
+        // the command palette, like the finder with each command's key after its name
+        if self.mode == Mode::Commands {
+            let results = self.command_results();
+            let palette_area = modal_area(area, 50, 4 + results.len().clamp(1, 12) as u16, buf);
+            let visible_results = palette_area.height.saturating_sub(4) as usize;
+            let first_result = scroll_offset(0, self.command_index, visible_results);
+
+            let mut palette_lines = vec![Line::from(vec!["Command: ".bold(), self.command_query.as_str().into()])];
+            if results.is_empty() {
+                palette_lines.push(Line::from("No matching commands".dark_gray()));
+            }
+            for (i, (action, positions)) in results.iter().enumerate().skip(first_result).take(visible_results) {
+                let style = if i == self.command_index { Style::new().bold().yellow() } else { Style::new() };
+                let description = DEFAULT_KEYS.iter().find(|(a, _, _, _)| a == action).map_or("", |(_, _, description, _)| description);
+                let mut spans: Vec<Span> = description
+                    .chars()
+                    .enumerate()
+                    .map(|(j, c)| Span::styled(c.to_string(), if positions.contains(&j) { style.underlined() } else { style }))
+                    .collect();
+                spans.push(format!("  {}", self.keymap.label(*action)).dark_gray());
+                palette_lines.push(Line::from(spans));
+            }
+            palette_lines.push(Line::from(vec![
+                "Run: ".into(),
+                "Enter".bold().green(),
+                " | Cancel: ".into(),
+                "Esc".bold().red(),
+            ]));
+            let palette_paragraph = Paragraph::new(Text::from(palette_lines))
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Commands ".bold().fg(theme.title)));
+            palette_paragraph.render(palette_area, buf);
+        }
+
+        // rendering the outline like the file selection, each level indented a bit more than the one above
+        if self.mode == Mode::Outline {
+            let outline_area = modal_area(area, 50, 4 + self.outline.len() as u16, buf);
+            let visible_headings = outline_area.height.saturating_sub(4) as usize;
+            let first_heading = scroll_offset(0, self.outline_index, visible_headings);
+
+            let title_width = outline_area.width.saturating_sub(2) as usize;
+            let mut outline_lines: Vec<Line> = self.outline.iter().enumerate().skip(first_heading).take(visible_headings).map(|(i, (_, level, title))| {
+                let indent = "  ".repeat(*level as usize - 1);
+                let title = truncate_name(&format!("{}{}", indent, title), title_width);
+                if i == self.outline_index { Line::from(title.bold().yellow()) } else { Line::from(title) }
+            }).collect();
+            outline_lines.push(Line::from(""));
+            outline_lines.push(Line::from(vec![
+                "Go: ".into(),
+                "Enter".bold().green(),
+                " | Cancel: ".into(),
+                "Esc".bold().red(),
+            ]));
+            let outline_paragraph = Paragraph::new(Text::from(outline_lines))
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Outline ".bold().fg(theme.title)));
+            outline_paragraph.render(outline_area, buf);
+        }
+
+        // rendering the delete confirmation on top of the file selection
+        if self.mode == Mode::DeleteConfirm {
+            let delete_confirm_area = modal_area(area, 40, 6, buf);
+
+            let delete_confirm_text = Text::from(vec![
+                Line::from(format!("Delete {}?", self.files.get(self.file_select_index).map(String::as_str).unwrap_or(""))),
+                Line::from(""),
+                Line::from(vec![
+                    "Delete: ".into(),
+                    "Y".bold().red(),
+                    " | Cancel: ".into(),
+                    "N/Esc".bold().green(),
+                ]),
+            ]);
+            let delete_confirm_paragraph = Paragraph::new(delete_confirm_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Delete Note ".bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            delete_confirm_paragraph.render(delete_confirm_area, buf);
+        }
+
+        // rendering the unsaved changes prompt when trying to quit
+        if self.mode == Mode::QuitConfirm {
+            let quit_confirm_area = modal_area(area, 44, 7, buf);
+
+            let quit_confirm_text = Text::from(vec![
+                Line::from(match self.buffers.iter().filter(|buffer| buffer.dirty).count() {
+                    1 => format!("{} has unsaved changes!", self.buffers.iter().find(|buffer| buffer.dirty).unwrap().display_name()),
+                    dirty => format!("{} notes have unsaved changes!", dirty),
+                }),
+                Line::from(""),
+                Line::from(vec![
+                    "Save & Quit: ".into(),
+                    "S".bold().green(),
+                    " | Quit: ".into(),
+                    "Q".bold().red(),
+                    " | Cancel: ".into(),
+                    "Esc".bold().yellow(),
+                ]),
+            ]);
+            let quit_confirm_paragraph = Paragraph::new(quit_confirm_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Quit ".bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            quit_confirm_paragraph.render(quit_confirm_area, buf);
+        }
+
+        // rendering the revert prompt, only shown when there's something to lose
+        if self.mode == Mode::RevertConfirm {
+            let revert_confirm_area = modal_area(area, 44, 6, buf);
+
+            let revert_confirm_text = Text::from(vec![
+                Line::from(format!("Throw away your changes to {}?", current.display_name())),
+                Line::from(""),
+                Line::from(vec![
+                    "Revert: ".into(),
+                    "Y".bold().red(),
+                    " | Cancel: ".into(),
+                    "N/Esc".bold().green(),
+                ]),
+            ]);
+            let revert_confirm_paragraph = Paragraph::new(revert_confirm_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Revert Note ".bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            revert_confirm_paragraph.render(revert_confirm_area, buf);
+        }
+
+        // rendering the prompt for deleting the current note, like the one in the file selection
+        if self.mode == Mode::DeleteNoteConfirm {
+            let delete_note_area = modal_area(area, 44, 6, buf);
+
+            let delete_note_text = Text::from(vec![
+                Line::from(format!("Delete {}?", current.display_name())),
+                Line::from(""),
+                Line::from(vec![
+                    "Delete: ".into(),
+                    "Y".bold().red(),
+                    " | Cancel: ".into(),
+                    "N/Esc".bold().green(),
+                ]),
+            ]);
+            let delete_note_paragraph = Paragraph::new(delete_note_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Delete Note ".bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            delete_note_paragraph.render(delete_note_area, buf);
+        }
+
+        // rendering the overwrite prompt for save as, the name is still in new_file_name
+        if self.mode == Mode::OverwriteConfirm {
+            let overwrite_confirm_area = modal_area(area, 44, 6, buf);
+
+            let overwrite_confirm_text = Text::from(vec![
+                Line::from(format!("{} already exists, replace it?", clean_note_name(&self.new_file_name))),
+                Line::from(""),
+                Line::from(vec![
+                    "Replace: ".into(),
+                    "Y".bold().red(),
+                    " | Pick another name: ".into(),
+                    "N".bold().green(),
+                ]),
+            ]);
+            let overwrite_confirm_paragraph = Paragraph::new(overwrite_confirm_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Save As ".bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            overwrite_confirm_paragraph.render(overwrite_confirm_area, buf);
+        }
+
+        if self.mode == Mode::DiskConflict {
+            let disk_conflict_area = modal_area(area, 50, 7, buf);
+
+            let disk_conflict_text = Text::from(vec![
+                Line::from(format!("{} was changed outside of trmnotes!", current.display_name())),
+                Line::from(""),
+                Line::from(vec![
+                    "Reload: ".into(),
+                    "R".bold().green(),
+                    " | Overwrite: ".into(),
+                    "O".bold().red(),
+                    " | Cancel: ".into(),
+                    "Esc".bold().yellow(),
+                ]),
+            ]);
+            let disk_conflict_paragraph = Paragraph::new(disk_conflict_text)
+                .block(Block::default().borders(ratatui::widgets::Borders::ALL).title(" Changed On Disk ".bold().fg(theme.title)))
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            disk_conflict_paragraph.render(disk_conflict_area, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trmnotes::buffer::LineEnding;
+    use trmnotes::store::InMemoryStore;
+
+    fn app_with(lines: &[&str]) -> App {
+        let buffer = Buffer {
+            text: lines.iter().map(|l| l.to_string()).collect(),
+            ..Buffer::new(Some("default.txt".to_string()))
+        };
+        App { buffers: vec![buffer], ..Default::default() }
+    }
+
+    // every popup, for tests that should hold for all of them
+    const ALL_MODES: [Mode; 17] = [
+        Mode::Help,
+        Mode::Info,
+        Mode::QuitConfirm,
+        Mode::DiskConflict,
+        Mode::CreateNote,
+        Mode::Search,
+        Mode::Replace,
+        Mode::Rename,
+        Mode::GoTo,
+        Mode::SelectFile,
+        Mode::FindFile,
+        Mode::Commands,
+        Mode::Outline,
+        Mode::DeleteConfirm,
+        Mode::RevertConfirm,
+        Mode::DeleteNoteConfirm,
+        Mode::OverwriteConfirm,
+    ];
+
+    // An app whose notes folder is `folder`
+    fn app_in(folder: &str) -> App {
+        App { root: folder.to_string(), folder: folder.to_string(), store: Box::new(FsNoteStore::new(folder)), ..Default::default() }
+    }
+
+    // A fresh, empty folder in the temp dir for tests that touch the file system
+    fn temp_folder(name: &str) -> String {
+        let folder = std::env::temp_dir().join(format!("trmnotes-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        format!("{}/", folder.display())
+    }
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.apply_key(code, KeyModifiers::NONE);
+    }
+
+    fn type_str(app: &mut App, s: &str) {
+        for c in s.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn undo_groups_typed_chars_and_redo_restores() {
+        let mut app = App::default();
+        type_str(&mut app, "hello");
+        press(&mut app, KeyCode::Enter);
+        type_str(&mut app, "world");
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        assert_eq!(app.current().text, vec!["hello".to_string(), "".to_string()]);
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        assert_eq!(app.current().text, vec!["hello".to_string()]);
+        assert_eq!((app.current().cursor_x, app.current().cursor_y), (5, 0));
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::CONTROL));
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::CONTROL));
+        assert_eq!(app.current().text, vec!["hello".to_string(), "world".to_string()]);
+        assert_eq!((app.current().cursor_x, app.current().cursor_y), (5, 1));
+    }
+
+    #[test]
+    fn quitting_with_unsaved_changes_asks_first() {
+        let mut app = App::default();
+        type_str(&mut app, "x");
+        assert!(app.current().dirty);
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL));
+        assert_eq!(app.mode, Mode::QuitConfirm);
+        assert!(!app.exit);
+
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.mode, Mode::Editing);
+        assert!(!app.exit);
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL));
+        press(&mut app, KeyCode::Char('q'));
+        assert!(app.exit);
+    }
+
+    #[test]
+    fn tab_indents_at_start_of_line() {
+        let mut app = App::default();
+        type_str(&mut app, "hi");
+        app.current_mut().cursor_x = 0;
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.current().text[0], "    hi");
+        assert_eq!(app.current().cursor_x, 4);
+    }
+
+    #[test]
+    fn shift_tab_dedents_only_leading_spaces() {
+        let mut app = app_with(&["  hi"]);
+        app.current_mut().cursor_x = 3;
+        press(&mut app, KeyCode::BackTab);
+        assert_eq!(app.current().text[0], "hi");
+        assert_eq!(app.current().cursor_x, 1);
+
+        // nothing to remove, so the line stays the same
+        press(&mut app, KeyCode::BackTab);
+        assert_eq!(app.current().text[0], "hi");
+        assert_eq!(app.current().cursor_x, 1);
+    }
+
+    #[test]
+    fn search_jumps_between_matches_and_wraps() {
+        let mut app = app_with(&["foo bar", "bär foo", "foo"]);
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL));
+        type_str(&mut app, "foo");
+        assert_eq!(app.search_matches, vec![(0, 0), (1, 4), (2, 0)]);
+
+        press(&mut app, KeyCode::Enter);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (0, 0));
+        press(&mut app, KeyCode::Enter);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (1, 4));
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Up);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (2, 0)); // wrapped around to the last match
+    }
+
+    #[test]
+    fn replace_all_handles_adjacent_matches_on_one_line() {
+        let mut app = app_with(&["foofoo foo!"]);
+        app.current_mut().cursor_x = 11; // end of the line
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL));
+        type_str(&mut app, "foo");
+        press(&mut app, KeyCode::Tab);
+        type_str(&mut app, "barbar");
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL));
+
+        assert_eq!(app.current().text[0], "barbarbarbar barbar!");
+        assert_eq!(app.current().cursor_x, 20);
+        assert!(app.current().dirty);
+    }
+
+    #[test]
+    fn saving_keeps_crlf_and_missing_trailing_newline() {
+        let folder = temp_folder("crlf");
+        fs::write(format!("{}note.txt", folder), "one\r\ntwo").unwrap();
+        let mut app = app_in(&folder);
+
+        app.open_note("note.txt").unwrap();
+        assert_eq!(app.current().line_ending, LineEnding::CrLf);
+        assert_eq!(app.current().text, vec!["one".to_string(), "two".to_string()]);
+        app.save_note("note.txt").unwrap();
+        app.save_note("note.txt").unwrap();
+        assert_eq!(fs::read_to_string(format!("{}note.txt", folder)).unwrap(), "one\r\ntwo");
+
+        fs::write(format!("{}unix.txt", folder), "a\nb\n").unwrap();
+        app.open_note("unix.txt").unwrap();
+        assert_eq!(app.current().line_ending, LineEnding::Lf);
+        app.save_note("unix.txt").unwrap();
+        assert_eq!(fs::read_to_string(format!("{}unix.txt", folder)).unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    fn cursor_lands_on_text_at_any_terminal_width() {
+        for width in [60, 120, 200] {
+            for explorer_open in [true, false] {
+                let mut app = App { explorer_open, ..app_with(&["hello"]) };
+                app.current_mut().cursor_x = 1;
+                let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(width, 20)).unwrap();
+                terminal.draw(|frame| app.draw(frame)).unwrap();
+
+                let cursor = terminal.get_cursor_position().unwrap();
+                assert_eq!(cursor.x, app.layout(Rect::new(0, 0, width, 20)).editor.x + 2);
+                assert_eq!(terminal.backend().buffer()[(cursor.x, cursor.y)].symbol(), "e");
+            }
+        }
+    }
+
+    #[test]
+    fn buffer_stats_counts_words_and_unicode_chars() {
+        let app = app_with(&["héllo  wörld", "", "  one"]);
+        assert_eq!(app.current().stats(), (3, 3, 17));
+    }
+
+    #[test]
+    fn ctrl_arrows_jump_by_word_and_cross_lines() {
+        let mut app = app_with(&["héllo  wörld", "next"]);
+        let ctrl = |code| KeyEvent::new(code, KeyModifiers::CONTROL);
+        app.handle_key_event(ctrl(KeyCode::Right));
+        assert_eq!(app.current().cursor_x, 5);
+        app.handle_key_event(ctrl(KeyCode::Right));
+        assert_eq!(app.current().cursor_x, 12);
+        app.handle_key_event(ctrl(KeyCode::Right));
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (1, 0));
+
+        app.handle_key_event(ctrl(KeyCode::Left));
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (0, 12));
+        app.handle_key_event(ctrl(KeyCode::Left));
+        assert_eq!(app.current().cursor_x, 7);
+        press(&mut app, KeyCode::End);
+        assert_eq!(app.current().cursor_x, 12);
+        press(&mut app, KeyCode::Home);
+        assert_eq!(app.current().cursor_x, 0);
+    }
+
+    #[test]
+    fn opening_a_path_picks_the_right_folder() {
+        let notes = temp_folder("args-notes");
+        let other = temp_folder("args-other");
+        let mut app = app_in(&notes);
+
+        app.open_path(Path::new(&format!("{}inside.md", notes))).unwrap();
+        assert_eq!(app.current().file_name.as_deref(), Some("inside.md"));
+        assert_eq!(app.root, notes);
+
+        app.open_path(Path::new(&format!("{}outside.md", other))).unwrap();
+        assert_eq!(app.current().file_name.as_deref(), Some("outside.md"));
+        assert_eq!(fs::canonicalize(&app.root).unwrap(), fs::canonicalize(&other).unwrap());
+        assert!(Path::new(&format!("{}outside.md", other)).exists());
+    }
+
+    #[test]
+    fn cursor_follows_wrapped_lines() {
+        let mut app = App { explorer_open: false, wrap_enabled: true, ..app_with(&["abcdefghijKLMNOP", "xyz"]) };
+        app.current_mut().cursor_x = 12;
+        // 12 wide with the borders leaves 10 columns for text
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(12, 10)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let cursor = terminal.get_cursor_position().unwrap();
+        assert_eq!(terminal.backend().buffer()[(cursor.x, cursor.y)].symbol(), "M");
+
+        // the next line starts below both rows of the wrapped one
+        app.current_mut().cursor_y = 1;
+        app.current_mut().cursor_x = 1;
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let cursor = terminal.get_cursor_position().unwrap();
+        assert_eq!(terminal.backend().buffer()[(cursor.x, cursor.y)].symbol(), "y");
+    }
+
+    #[test]
+    fn wrap_line_keeps_styles_and_adds_row_for_full_lines() {
+        let rows = wrap_line(Line::from(vec![Span::raw("abc"), Span::styled("def", Style::default().bold())]), 4);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].spans[0].content, "ef");
+        assert_eq!(rows[1].spans[0].style, Style::default().bold());
+        assert_eq!(wrap_line(Line::from("abcd"), 4).len(), 2);
+        assert_eq!(wrapped_rows("abcd", 4, 4), 2);
+    }
+
+    #[test]
+    fn delete_removes_char_under_cursor() {
+        let mut app = app_with(&["naïve"]);
+        app.current_mut().cursor_x = 2;
+        press(&mut app, KeyCode::Delete);
+        assert_eq!(app.current().text[0], "nave");
+        assert_eq!(app.current().cursor_x, 2);
+    }
+
+    #[test]
+    fn delete_at_end_of_line_joins_next_line() {
+        let mut app = app_with(&["abc", "def", "ghi"]);
+        app.current_mut().cursor_x = 3;
+        press(&mut app, KeyCode::Delete);
+        assert_eq!(app.current().text, vec!["abcdef".to_string(), "ghi".to_string()]);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (0, 3));
+
+        // nothing after the last line, so nothing happens
+        app.current_mut().cursor_y = 1;
+        press(&mut app, KeyCode::Delete);
+        assert_eq!(app.current().text, vec!["abcdef".to_string(), "ghi".to_string()]);
+    }
+
+    #[test]
+    fn backspace_at_line_start_joins_with_previous_line() {
+        let mut app = app_with(&["abc", "def"]);
+        app.current_mut().cursor_y = 1;
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!(app.current().text, vec!["abcdef".to_string()]);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (0, 3));
+    }
+
+    #[test]
+    fn clicking_in_the_editor_moves_the_cursor() {
+        let mut app = App { explorer_open: false, ..app_with(&["hello", "hi"]) };
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(40, 10)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+
+        let click = |column, row| MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        app.handle_mouse_event(click(4, 1));
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (0, 3));
+        // past the end of a line clamps to the end
+        app.handle_mouse_event(click(30, 2));
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (1, 2));
+    }
+
+    #[test]
+    fn cursor_walks_across_multibyte_text() {
+        let mut app = App::default();
+        type_str(&mut app, "café");
+        assert_eq!(app.current().text[0], "café");
+        assert_eq!(app.current().cursor_x, 4);
+
+        for _ in 0..6 {
+            press(&mut app, KeyCode::Left);
+        }
+        assert_eq!(app.current().cursor_x, 0);
+        for _ in 0..6 {
+            press(&mut app, KeyCode::Right);
+        }
+        assert_eq!(app.current().cursor_x, 4);
+
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!(app.current().text[0], "caf");
+        press(&mut app, KeyCode::Char('é'));
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current().text, vec!["caf".to_string(), "é".to_string()]);
+    }
+
+    #[test]
+    fn opening_notes_uses_tabs_and_reuses_open_ones() {
+        let folder = temp_folder("tabs");
+        fs::write(format!("{}a.txt", folder), "first\n").unwrap();
+        fs::write(format!("{}b.txt", folder), "second\n").unwrap();
+        let mut app = app_in(&folder);
+
+        assert!(app.switch_note("a.txt"));
+        type_str(&mut app, "x");
+        assert!(app.switch_note("b.txt"));
+        assert_eq!(app.buffers.len(), 3);
+        assert_eq!(app.current().text, vec!["second".to_string()]);
+
+        // already open, so we switch back without reloading and keep the unsaved edit
+        assert!(app.switch_note("a.txt"));
+        assert_eq!(app.buffers.len(), 3);
+        assert_eq!(app.current().text, vec!["xfirst".to_string()]);
+        assert!(app.current().dirty);
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Tab, KeyModifiers::CONTROL));
+        assert_eq!(app.current().display_name(), "b.txt");
+        app.handle_key_event(KeyEvent::new(KeyCode::Tab, KeyModifiers::CONTROL));
+        assert_eq!(app.current().display_name(), "default.txt"); // wrapped around
+        app.handle_key_event(KeyEvent::new(KeyCode::BackTab, KeyModifiers::CONTROL | KeyModifiers::SHIFT));
+        assert_eq!(app.current().display_name(), "b.txt");
+    }
+
+    #[test]
+    fn create_note_reports_existing_notes() {
+        let folder = temp_folder("create");
+        fs::write(format!("{}old.txt", folder), "keep me\n").unwrap();
+        let mut app = app_in(&folder);
+
+        assert_eq!(app.create_note("old.txt").unwrap(), CreateOutcome::AlreadyExists);
+        assert_eq!(fs::read_to_string(format!("{}old.txt", folder)).unwrap(), "keep me\n");
+        assert_eq!(app.create_note("new.txt").unwrap(), CreateOutcome::Created);
+        assert!(app.note_exists("new.txt"));
+
+        // creating an existing note from the modal opens it instead of a blank buffer
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL));
+        type_str(&mut app, "old.txt");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current().display_name(), "old.txt");
+        assert_eq!(app.current().text, vec!["keep me".to_string()]);
+    }
+
+    #[test]
+    fn explorer_browses_subfolders_and_skips_symlink_loops() {
+        let root = temp_folder("subfolders");
+        fs::create_dir(format!("{}work", root)).unwrap();
+        fs::write(format!("{}work/todo.txt", root), "ship it\n").unwrap();
+        fs::write(format!("{}top.txt", root), "").unwrap();
+        let mut app = app_in(&root);
+        app.get_notes().unwrap();
+        assert_eq!(app.files, vec!["work/".to_string(), "top.txt".to_string()]);
+
+        assert!(!app.open_entry("work/"));
+        assert_eq!(app.files, vec!["..".to_string(), "todo.txt".to_string()]);
+        assert!(app.open_entry("todo.txt"));
+        // the name stays relative to the root, so saving goes to the right place
+        assert_eq!(app.current().display_name(), "work/todo.txt");
+        assert_eq!(app.current().text, vec!["ship it".to_string()]);
+
+        app.open_entry("..");
+        assert!(app.at_root());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(fs::canonicalize(&root).unwrap(), format!("{}work/loop", root)).unwrap();
+            app.open_entry("work/");
+            app.open_entry("loop/");
+            assert_eq!(app.note_name(""), "work/"); // didn't follow the link
+        }
+    }
+
+    #[test]
+    fn restoring_a_session_reopens_the_note_and_clamps_the_cursor() {
+        let folder = temp_folder("session");
+        fs::write(format!("{}last.txt", folder), "one\ntwo\n").unwrap();
+        let mut app = app_in(&folder);
+
+        let session = Session { current_file: Some("last.txt".to_string()), cursor_x: 40, cursor_y: 9, scroll_y: 9, ..Session::default() };
+        app.restore_session(&session).unwrap();
+        assert_eq!(app.current().display_name(), "last.txt");
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (1, 3));
+        assert_eq!(app.session().cursor_y, 1);
+
+        // the note was deleted since, so we land on default.txt at the top
+        let gone = Session { current_file: Some("gone.txt".to_string()), ..session };
+        let mut app = app_in(&folder);
+        app.restore_session(&gone).unwrap();
+        assert_eq!(app.current().display_name(), "default.txt");
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (0, 0));
+    }
+
+    #[test]
+    fn errors_show_up_in_the_status_line() {
+        let mut app = app_in(&temp_folder("status"));
+        app.open_note("missing.txt").unwrap();
+        assert_eq!(app.status_message.as_ref().map(|(message, _)| message.as_str()), Some("File not found: missing.txt"));
+
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(160, 10)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let bottom: String = (0..160).map(|x| terminal.backend().buffer()[(x, 9)].symbol().to_string()).collect();
+        assert!(bottom.contains("File not found: missing.txt"));
+    }
+
+    #[test]
+    fn shift_arrows_select_and_clipboard_keys_copy_cut_paste() {
+        let mut app = app_with(&["hello world"]);
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        for _ in 0..5 {
+            app.handle_key_event(KeyEvent::new(KeyCode::Right, KeyModifiers::SHIFT));
+        }
+        app.handle_key_event(ctrl('c'));
+        assert_eq!(app.clipboard, "hello");
+
+        app.handle_key_event(ctrl('x'));
+        assert_eq!(app.current().text[0], " world");
+        press(&mut app, KeyCode::End);
+        app.handle_key_event(ctrl('v'));
+        assert_eq!(app.current().text[0], " worldhello");
+        assert_eq!(app.current().cursor_x, 11);
+
+        // moving without Shift drops the selection, so copying does nothing
+        app.handle_key_event(KeyEvent::new(KeyCode::Left, KeyModifiers::SHIFT));
+        press(&mut app, KeyCode::Left);
+        assert_eq!(app.current().selection(), None);
+    }
+
+    #[test]
+    fn style_range_splits_spans_at_the_edges() {
+        let line = style_range(Line::from(vec![Span::raw("ab"), Span::raw("cdé")]), 1, 4, Style::default().reversed());
+        let parts: Vec<(&str, bool)> = line
+            .spans
+            .iter()
+            .map(|span| (span.content.as_ref(), span.style.add_modifier.contains(ratatui::style::Modifier::REVERSED)))
+            .collect();
+        assert_eq!(parts, vec![("a", false), ("b", true), ("cd", true), ("é", false)]);
+    }
+
+    #[test]
+    fn goto_line_counts_from_one_and_clamps() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.current_mut().cursor_x = 2;
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL));
+        type_str(&mut app, "2");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (1, 0));
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL));
+        type_str(&mut app, "abc");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::GoTo); // stays open to fix it
+        assert!(app.goto_error.is_some());
+
+        for _ in 0..3 {
+            press(&mut app, KeyCode::Backspace);
+        }
+        type_str(&mut app, "99");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current().cursor_y, 2);
+    }
+
+    #[test]
+    fn the_command_palette_finds_and_runs_commands() {
+        let mut app = App { explorer_open: false, ..app_with(&["one", "two", "three"]) };
+        press(&mut app, KeyCode::F(2));
+        assert_eq!(app.mode, Mode::Commands);
+        assert_eq!(app.command_results().len(), DEFAULT_KEYS.len() - 1); // everything but itself
+
+        type_str(&mut app, "gotol");
+        assert_eq!(app.command_results()[0].0, Action::GoToLine);
+        let area = Rect::new(0, 0, 80, 20);
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        app.render(area, &mut buf);
+        let screen: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("Go To Line  Ctrl+G"));
+
+        // Enter does what the key would, here opening the go to line popup
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::GoTo);
+        type_str(&mut app, "3");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current().cursor_y, 2);
+
+        press(&mut app, KeyCode::F(2));
+        type_str(&mut app, "duplicate");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current().text, vec!["one", "two", "three", "three"]);
+
+        // nothing matching, Enter does nothing and Esc closes it
+        press(&mut app, KeyCode::F(2));
+        type_str(&mut app, "zzz");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::Commands);
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.mode, Mode::Editing);
+
+        // what was typed into the palette has nothing to do with the note finder
+        app.apply_key(KeyCode::Char('p'), KeyModifiers::CONTROL);
+        assert_eq!(app.mode, Mode::FindFile);
+        assert_eq!(app.find_query, "");
+    }
+
+    #[test]
+    fn the_open_note_can_be_deleted_from_the_palette_after_asking() {
+        let store = InMemoryStore::with_notes(&[("a.txt", "one\n"), ("b.txt", "two\n")]);
+        let mut app = App { explorer_open: false, store: Box::new(store), ..Default::default() };
+        assert!(app.switch_note("a.txt"));
+        assert!(app.switch_note("b.txt"));
+
+        press(&mut app, KeyCode::F(2));
+        type_str(&mut app, "delete note");
+        assert_eq!(app.command_results()[0].0, Action::DeleteNote);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::DeleteNoteConfirm);
+        press(&mut app, KeyCode::Char('n'));
+        assert!(app.store.read("b.txt").is_ok()); // said no, so it's still there
+
+        app.apply_key(KeyCode::Delete, KeyModifiers::ALT);
+        press(&mut app, KeyCode::Char('y'));
+        assert_eq!(app.mode, Mode::Editing);
+        assert!(app.store.read("b.txt").is_err());
+        assert_eq!(app.current().display_name(), "a.txt"); // its tab is gone too
+
+        // a note that was never saved isn't in the store, so there's nothing to ask about
+        app.buffers.push(Buffer::new(None));
+        app.active = app.buffers.len() - 1;
+        app.apply_key(KeyCode::Delete, KeyModifiers::ALT);
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(app.store.list("").unwrap().len(), 1);
+
+        // deleting the last tab leaves an empty unnamed note, default.txt isn't made until it's saved
+        app.buffers.retain(|buffer| buffer.file_name.is_some());
+        app.active = 0;
+        assert_eq!(app.current().display_name(), "a.txt");
+        app.apply_key(KeyCode::Delete, KeyModifiers::ALT);
+        press(&mut app, KeyCode::Char('y'));
+        assert_eq!(app.buffers.len(), 1);
+        assert_eq!(app.current().file_name, None);
+        assert_eq!(app.current().text, vec![""]);
+        assert!(app.store.list("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn line_numbers_start_at_one_and_can_be_relative() {
+        let lines: Vec<String> = (0..12).map(|i| i.to_string()).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let mut app = App { explorer_open: false, ..app_with(&lines) };
+        app.current_mut().cursor_y = 2;
+        let area = Rect::new(0, 0, 200, 16);
+        let gutter = app.layout(area).line_numbers;
+        let number_at = |app: &App, row: u16| {
+            let mut buf = ratatui::buffer::Buffer::empty(area);
+            app.render(area, &mut buf);
+            (gutter.x + 1..gutter.right() - 1).map(|x| buf[(x, row)].symbol().to_string()).collect::<String>()
+        };
+        assert_eq!(number_at(&app, 1), " 1"); // right aligned to two digits
+        assert_eq!(number_at(&app, 3), " 3");
+
+        app.relative_line_numbers = true;
+        assert_eq!(number_at(&app, 1), " 2");
+        assert_eq!(number_at(&app, 3), " 3"); // the cursor's line keeps its real number
+    }
+
+    #[test]
+    fn tiny_terminals_render_every_modal_without_panicking() {
+        let mut app = app_with(&["some text that is longer than the screen"]);
+        app.files = vec!["a.txt".to_string(), "b.txt".to_string()];
+        for mode in ALL_MODES {
+            app.mode = mode;
+            for (width, height) in [(1, 1), (5, 3), (20, 6), (40, 10)] {
+                let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(width, height)).unwrap();
+                terminal.draw(|frame| app.draw(frame)).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn read_only_blocks_edits_but_not_movement() {
+        let mut app = app_with(&["abc"]);
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL));
+        type_str(&mut app, "xyz");
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Enter);
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('v'), KeyModifiers::CONTROL));
+        assert_eq!(app.current().text, vec!["abc".to_string()]);
+        assert!(!app.current().dirty);
+
+        press(&mut app, KeyCode::End);
+        assert_eq!(app.current().cursor_x, 3);
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL));
+        assert_eq!(app.mode, Mode::Search);
+        press(&mut app, KeyCode::Esc);
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL));
+        type_str(&mut app, "d");
+        assert_eq!(app.current().text, vec!["abcd".to_string()]);
+    }
+
+    #[test]
+    fn read_only_files_open_in_read_only_mode() {
+        let folder = temp_folder("readonly");
+        let path = format!("{}locked.txt", folder);
+        fs::write(&path, "hands off\n").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        let mut app = app_in(&folder);
+        app.open_note("locked.txt").unwrap();
+        assert!(app.current().read_only);
+    }
+
+    #[test]
+    fn explorer_only_lists_notes() {
+        let folder = temp_folder("extensions");
+        for name in ["note.md", "todo.txt", "image.png", ".hidden", ".secret.md"] {
+            fs::write(format!("{}{}", folder, name), "").unwrap();
+        }
+        let mut app = app_in(&folder);
+        app.get_notes().unwrap();
+        assert_eq!(app.files, vec!["note.md".to_string(), "todo.txt".to_string()]);
+    }
+
+    #[test]
+    fn moving_and_duplicating_lines() {
+        let mut app = app_with(&["one", "two", "three"]);
+        let alt = |code| KeyEvent::new(code, KeyModifiers::ALT);
+
+        // the first line can't go any higher
+        app.handle_key_event(alt(KeyCode::Up));
+        assert_eq!(app.current().text, vec!["one", "two", "three"]);
+        assert!(!app.current().dirty);
+
+        app.handle_key_event(alt(KeyCode::Down));
+        assert_eq!(app.current().text, vec!["two", "one", "three"]);
+        assert_eq!(app.current().cursor_y, 1);
+        assert!(app.current().dirty);
+
+        // duplicating the last line adds a copy at the very end
+        app.current_mut().cursor_y = 2;
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert_eq!(app.current().text, vec!["two", "one", "three", "three"]);
+        assert_eq!(app.current().cursor_y, 2);
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        assert_eq!(app.current().text, vec!["two", "one", "three"]);
+    }
+
+    #[test]
+    fn visible_whitespace_swaps_chars_one_for_one() {
+        let line = expand_tabs(visible_whitespace(Line::from("a b\t "), 3), 0, 4, true);
+        let text: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+        assert_eq!(text, "a·b→·");
+        assert_eq!(line.spans[1].style.bg, None); // the space between words isn't trailing
+        assert_eq!(line.spans[3].style.bg, Some(Color::Red));
+        assert_eq!(line.spans[4].style.bg, Some(Color::Red));
+    }
+
+    #[test]
+    fn files_over_the_size_limit_are_refused() {
+        let folder = temp_folder("too-big");
+        fs::write(format!("{}big.txt", folder), "x".repeat(2048)).unwrap();
+        let mut app = App { max_file_bytes: Some(1024), ..app_in(&folder) };
+
+        assert!(!app.switch_note("big.txt"));
+        assert_eq!(app.buffers.len(), 1); // the tab for it was closed again
+        assert!(app.status_message.as_ref().is_some_and(|(message, _)| message.contains("too big")));
+
+        app.max_file_bytes = None;
+        assert!(app.switch_note("big.txt"));
+    }
+
+    #[test]
+    fn explorer_marks_the_open_note() {
+        let mut app = app_with(&[""]);
+        app.files = vec!["a.txt".to_string(), "default.txt".to_string()];
+        let area = Rect::new(0, 0, 100, 10);
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        (&app).render(area, &mut buf);
+        let row = |y: u16| (1..12).map(|x| buf[(x, y)].symbol().to_string()).collect::<String>();
+        assert!(row(1).starts_with("a.txt"));
+        assert_eq!(row(2), "▸default.t…"); // cut short to fit, instead of wrapping onto the next row
+        assert_eq!(buf[(1, 2)].fg, Color::Green);
+    }
+
+    #[test]
+    fn long_file_lists_scroll_to_the_selection() {
+        let mut app = app_with(&[""]);
+        app.files = (0..100).map(|i| format!("note{:02}.txt", i)).collect();
+        app.mode = Mode::SelectFile;
+        app.file_select_index = 95;
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(120, 20)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+
+        // 18 rows fit in the panel, so the selection ends up on the last one
+        assert_eq!(app.explorer_scroll, 95 + 1 - 18);
+        let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("note95.txt"));
+        assert!(!screen.contains("note00.txt"));
+
+    }
+
+    #[test]
+    fn missing_notes_folder_is_created() {
+        let folder = format!("{}new/notes/", temp_folder("missing-folder"));
+        let mut app = app_in(&folder);
+        app.ensure_folder();
+        assert!(Path::new(&folder).is_dir());
+        assert_eq!(app.status_message, None);
+        app.refresh_notes();
+        assert_eq!(app.files, vec!["default.txt"]); // the welcome note, it's a first run
+
+        // a file in the way can't be turned into a folder, which ends up in the status line
+        let blocked = format!("{}file.txt/notes/", temp_folder("blocked-folder"));
+        fs::write(blocked.trim_end_matches("notes/").trim_end_matches('/'), "").unwrap();
+        let mut app = app_in(&blocked);
+        app.ensure_folder();
+        assert!(app.status_message.is_some_and(|(message, _)| message.starts_with("Failed to create notes folder")));
+    }
+
+    #[test]
+    fn smart_home_end_only_applies_when_wrapping() {
+        // three rows of 10: "0123456789", "abcdefghij", "ABCDE"
+        let mut app = App { smart_home_end: true, wrap_enabled: true, view_width: 10, ..app_with(&["0123456789abcdefghijABCDE"]) };
+        app.current_mut().cursor_x = 14;
+        press(&mut app, KeyCode::End);
+        assert_eq!(app.current().cursor_x, 19);
+        press(&mut app, KeyCode::Home);
+        assert_eq!(app.current().cursor_x, 10);
+
+        // with the option off Home/End go to the ends of the whole line, wrapped or not
+        app.smart_home_end = false;
+        app.current_mut().cursor_x = 14;
+        press(&mut app, KeyCode::End);
+        assert_eq!(app.current().cursor_x, 25);
+        press(&mut app, KeyCode::Home);
+        assert_eq!(app.current().cursor_x, 0);
+
+        app.smart_home_end = true;
+        app.wrap_enabled = false;
+        app.current_mut().cursor_x = 14;
+        press(&mut app, KeyCode::Home);
+        assert_eq!(app.current().cursor_x, 0);
+    }
+
+    #[test]
+    fn tab_follows_the_indentation_of_the_note() {
+        let folder = temp_folder("hard-tabs");
+        fs::write(format!("{}tabs.txt", folder), "list:\n\tone\n\ttwo\n").unwrap();
+        let mut app = App { explorer_open: false, tab_width: 4, ..app_in(&folder) };
+        assert!(app.switch_note("tabs.txt"));
+        assert!(app.current().uses_hard_tabs);
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.current().text[0], "\tlist:");
+
+        // the tab is drawn four columns wide and the cursor sits after it
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(60, 10)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let editor = app.layout(app.last_area).editor;
+        let row: String = (0..editor.width).map(|x| terminal.backend().buffer()[(editor.x + x, editor.y + 1)].symbol().to_string()).collect();
+        assert!(row.starts_with("│    list:"));
+        terminal.backend_mut().assert_cursor_position((editor.x + 1 + 4, editor.y + 1));
+
+        // a new note uses the default from the config
+        assert!(app.switch_note("new.txt"));
+        assert!(!app.current().uses_hard_tabs);
+    }
+
+    #[test]
+    fn ctrl_k_deletes_to_end_of_line_then_joins() {
+        let mut app = app_with(&["hello world", "next"]);
+        app.current_mut().cursor_x = 5;
+        let ctrl_k = KeyEvent::new(KeyCode::Char('k'), KeyModifiers::CONTROL);
+        app.handle_key_event(ctrl_k);
+        assert_eq!(app.current().text, vec!["hello", "next"]);
+        assert_eq!(app.current().cursor_x, 5);
+        assert!(app.current().dirty);
+
+        // nothing left after the cursor, so the next line comes up
+        app.handle_key_event(ctrl_k);
+        assert_eq!(app.current().text, vec!["hellonext"]);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (0, 5));
+
+        // on the last line with nothing after the cursor there's nothing to do
+        app.current_mut().cursor_x = 9;
+        app.handle_key_event(ctrl_k);
+        assert_eq!(app.current().text, vec!["hellonext"]);
+    }
+
+    #[test]
+    fn ctrl_a_selects_everything() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.current_mut().cursor_y = 1;
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL));
+        assert_eq!(app.current().selected_text().as_deref(), Some("one\ntwo\nthree"));
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (2, 5));
+
+        type_str(&mut app, "x");
+        assert_eq!(app.current().text, vec!["x"]);
+        assert!(app.current().dirty);
+    }
+
+    #[test]
+    fn alt_x_exports_the_note_next_to_it() {
+        let folder = temp_folder("export");
+        fs::create_dir_all(format!("{}work", folder)).unwrap();
+        let buffer = Buffer { text: vec!["# Plan".to_string(), "*soon*".to_string()], ..Buffer::new(Some("work/plan.md".to_string())) };
+        let mut app = App { buffers: vec![buffer], ..app_in(&folder) };
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::ALT));
+
+        let html = fs::read_to_string(format!("{}work/plan.html", folder)).unwrap();
+        assert!(html.contains("<title>plan</title>"));
+        assert!(html.contains("<h1>Plan</h1>\n<p><em>soon</em></p>\n"));
+        assert!(app.status_message.is_some_and(|(message, _)| message == "Exported to work/plan.html"));
+    }
+
+    #[test]
+    fn alt_o_bounces_between_the_last_two_notes() {
+        let folder = temp_folder("previous");
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(format!("{}{}", folder, name), "one\ntwo\nthree").unwrap();
+        }
+        let mut app = app_in(&folder);
+        let alt_o = KeyEvent::new(KeyCode::Char('o'), KeyModifiers::ALT);
+        app.handle_key_event(alt_o);
+        assert_eq!(app.status_message.as_ref().map(|(message, _)| message.as_str()), Some("No previous note yet"));
+
+        app.switch_note("a.txt");
+        app.current_mut().cursor_y = 2;
+        app.switch_note("b.txt");
+        app.handle_key_event(alt_o);
+        assert_eq!(app.current().display_name(), "a.txt");
+        assert_eq!(app.current().cursor_y, 2); // still where we left it
+        app.handle_key_event(alt_o);
+        assert_eq!(app.current().display_name(), "b.txt");
+
+        // a note that was renamed is still found under its new name
+        app.switch_note("c.txt");
+        app.rename_note("b.txt", "renamed.txt").unwrap();
+        app.handle_key_event(alt_o);
+        assert_eq!(app.current().display_name(), "renamed.txt");
+    }
+
+    #[test]
+    fn renaming_cleans_up_the_name_like_creating_does() {
+        let store = InMemoryStore::with_notes(&[("a.txt", "one\n")]);
+        let mut app = App { store: Box::new(store), ..Default::default() };
+        assert!(app.switch_note("a.txt"));
+        app.run_action(Action::Rename);
+        app.rename_buffer = "./work//x.txt".to_string();
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(app.current().display_name(), "work/x.txt");
+        assert_eq!(app.all_notes(), vec!["work/x.txt"]);
+
+        // so opening it from the explorer finds the tab that's already open instead of making a second one
+        let tabs = app.buffers.len();
+        assert!(app.switch_note("work/x.txt"));
+        assert_eq!(app.buffers.len(), tabs);
+    }
+
+    #[test]
+    fn format_date_fills_in_the_common_fields() {
+        let date = time::Date::from_calendar_date(2024, time::Month::March, 5).unwrap().with_hms(9, 7, 3).unwrap().assume_utc();
+        assert_eq!(format_date("%Y-%m-%d", &date), "2024-03-05");
+        assert_eq!(format_date("%d/%m/%Y %H:%M:%S", &date), "05/03/2024 09:07:03");
+        assert_eq!(format_date("100%% %q done%", &date), "100% %q done%");
+    }
+
+    #[test]
+    fn ctrl_t_inserts_the_date_at_the_cursor() {
+        let mut app = App { date_format: "[%Y]".to_string(), ..app_with(&["Entry: ."]) };
+        app.current_mut().cursor_x = 7;
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL));
+        let line = &app.current().text[0];
+        assert!(line.starts_with("Entry: [20") && line.ends_with("]."), "{}", line);
+        assert_eq!(app.current().cursor_x, 13); // right after the inserted "[yyyy]"
+        assert!(app.current().dirty);
+    }
+
+    #[test]
+    fn opening_another_note_keeps_unsaved_edits() {
+        let folder = temp_folder("open-dirty");
+        fs::write(format!("{}other.txt", folder), "other").unwrap();
+        let buffer = Buffer { text: vec!["unsaved".to_string()], dirty: true, ..Buffer::new(Some("draft.txt".to_string())) };
+        let mut app = App { buffers: vec![buffer], ..app_in(&folder) };
+        app.get_notes().unwrap();
+
+        // Ctrl+O, Enter on other.txt: it opens in its own tab instead of over the draft
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL));
+        app.file_select_index = app.files.iter().position(|file| file == "other.txt").unwrap();
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current().text, vec!["other"]);
+        assert_eq!(app.buffers[0].text, vec!["unsaved"]);
+        assert!(app.buffers[0].dirty);
+    }
+
+    #[test]
+    fn render_uses_the_theme_colors() {
+        let app = App { theme: Theme::LIGHT, explorer_open: false, ..app_with(&["one", "two"]) };
+        let area = Rect::new(0, 0, 200, 8); // wide enough for the line numbers to get a couple of columns
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        (&app).render(area, &mut buf);
+        let layout = app.layout(area);
+
+        // the cursor's line number, another line number, and the start of the tab bar title
+        let (numbers_x, numbers_y) = (layout.line_numbers.right() - 2, layout.line_numbers.y + 1);
+        assert_eq!(buf[(numbers_x, numbers_y)].fg, Theme::LIGHT.cursor_line);
+        assert_eq!(buf[(numbers_x, numbers_y + 1)].fg, Theme::LIGHT.line_numbers);
+        assert_eq!(buf[(layout.editor.x + 2, layout.editor.y)].fg, Theme::LIGHT.title);
+    }
+
+    #[test]
+    fn enter_on_an_indented_line_indents_the_new_one() {
+        let mut app = app_with(&["    hello"]);
+        press(&mut app, KeyCode::End);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current().text, vec!["    hello", "    "]);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (1, 4));
+
+        app.auto_indent = false;
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current().text[2], "");
+    }
+
+    #[test]
+    fn info_popup_shows_stats_of_the_unsaved_text() {
+        let folder = temp_folder("info");
+        let buffer = Buffer { text: vec!["one two".to_string(), "three".to_string()], ..Buffer::new(Some("draft.txt".to_string())) };
+        let mut app = App { buffers: vec![buffer], ..app_in(&folder) };
+        let alt_i = KeyEvent::new(KeyCode::Char('i'), KeyModifiers::ALT);
+        app.handle_key_event(alt_i);
+        assert_eq!(app.mode, Mode::Info);
+
+        let area = Rect::new(0, 0, 120, 30);
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        (&app).render(area, &mut buf);
+        let screen: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        for expected in ["Note: draft.txt", "Lines: 2", "Words: 3", "Characters: 12", "On disk: not saved yet", "Reading time: ~1 min"] {
+            assert!(screen.contains(expected), "missing {}", expected);
+        }
+
+        // other keys don't get through while it's open
+        type_str(&mut app, "x");
+        assert_eq!(app.current().text[0], "one two");
+        app.handle_key_event(alt_i);
+        assert_eq!(app.mode, Mode::Editing);
+
+        app.mode = Mode::Info;
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(reading_minutes(401), 3);
+    }
+
+    #[test]
+    fn ignore_file_hides_matching_notes() {
+        let folder = temp_folder("ignore");
+        for name in ["keep.txt", "old.bak", "old.txt", "secret.txt"] {
+            fs::write(format!("{}{}", folder, name), "").unwrap();
+        }
+        let mut app = App { extensions: vec!["txt".to_string(), "bak".to_string()], ..app_in(&folder) };
+        app.get_notes().unwrap();
+        assert_eq!(app.files, vec!["keep.txt", "old.bak", "old.txt", "secret.txt"]);
+
+        fs::write(format!("{}.trmnotesignore", folder), "*.bak\nsecret.txt\n[oops\n").unwrap();
+        app.refresh_notes();
+        assert_eq!(app.files, vec!["keep.txt", "old.txt"]);
+        assert!(app.status_message.is_some_and(|(message, _)| message.ends_with(".trmnotesignore: [oops")));
+    }
+
+    #[test]
+    fn typing_session_without_a_terminal() {
+        let folder = temp_folder("session-keys");
+        let mut app = App { buffers: vec![Buffer::new(Some("log.txt".to_string()))], ..app_in(&folder) };
+        type_str(&mut app, "helo");
+        press(&mut app, KeyCode::Left);
+        type_str(&mut app, "l");
+        press(&mut app, KeyCode::End);
+        press(&mut app, KeyCode::Enter);
+        type_str(&mut app, "wrld!");
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Home);
+        press(&mut app, KeyCode::Right);
+        type_str(&mut app, "o");
+        press(&mut app, KeyCode::Up);
+        assert_eq!(app.current().text, vec!["hello", "world"]);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (0, 2));
+        assert_eq!(app.current().display_name(), "log.txt");
+
+        // releases are ignored, only presses count
+        let mut release = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
+        release.kind = KeyEventKind::Release;
+        app.handle_event(Event::Key(release));
+        assert_eq!(app.current().text[0], "hello");
+
+        app.apply_key(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(fs::read_to_string(format!("{}log.txt", folder)).unwrap().lines().collect::<Vec<_>>(), vec!["hello", "world"]);
+        assert!(!app.current().dirty);
+    }
+
+    #[test]
+    fn saving_over_outside_changes_asks_first() {
+        let folder = temp_folder("disk-conflict");
+        let path = format!("{}shared.txt", folder);
+        fs::write(&path, "original").unwrap();
+        let mut app = app_in(&folder);
+        assert!(app.switch_note("shared.txt"));
+        type_str(&mut app, "mine ");
+
+        // pretend another program wrote the file a bit later
+        fs::write(&path, "theirs").unwrap();
+        let later = std::time::SystemTime::now() + Duration::from_secs(5);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+
+        let ctrl_s = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        app.handle_key_event(ctrl_s);
+        assert_eq!(app.mode, Mode::DiskConflict);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "theirs");
+        assert!(app.save_all().is_err()); // autosave leaves it alone too
+        assert_eq!(fs::read_to_string(&path).unwrap(), "theirs");
+        // and says which key saves it anyway, as it's set in the config
+        app.keymap = KeyMap::new(&Config::parse("[keys]\nsave = \"ctrl+j\"").unwrap().keys).0;
+        assert_eq!(app.save_all().unwrap_err().to_string(), "shared.txt changed on disk, save with Ctrl+J");
+        app.keymap = KeyMap::default();
+
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.mode, Mode::Editing);
+        assert!(app.current().dirty);
+
+        app.handle_key_event(ctrl_s);
+        press(&mut app, KeyCode::Char('r'));
+        assert_eq!(app.current().text, vec!["theirs"]);
+        assert!(!app.current().dirty);
+        assert_eq!(app.current().cursor_x, 5); // reloading keeps the cursor, like reverting
+
+        // once reloaded it's in sync again, so saving just works
+        press(&mut app, KeyCode::Home);
+        type_str(&mut app, "and mine ");
+        app.handle_key_event(ctrl_s);
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "and mine theirs");
+
+        // deleted while we were asking, there's nothing to reload so the edits stay
+        type_str(&mut app, "still ");
+        fs::write(&path, "theirs again").unwrap();
+        let later = std::time::SystemTime::now() + Duration::from_secs(10);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        app.handle_key_event(ctrl_s);
+        assert_eq!(app.mode, Mode::DiskConflict);
+        fs::remove_file(&path).unwrap();
+        press(&mut app, KeyCode::Char('r'));
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(app.status_message.as_ref().map(|(message, _)| message.as_str()), Some("Failed to reload note: shared.txt not found"));
+        assert_eq!(app.current().text, vec!["and mine still theirs"]);
+        assert!(app.current().dirty);
+    }
+
+    #[test]
+    fn auto_pairs_close_nest_and_type_over() {
+        let mut app = App { auto_pairs: true, ..app_with(&[""]) };
+        type_str(&mut app, "f([");
+        assert_eq!(app.current().text[0], "f([])");
+        assert_eq!(app.current().cursor_x, 3);
+
+        // typing the closers steps over the ones that are already there
+        type_str(&mut app, "1])");
+        assert_eq!(app.current().text[0], "f([1])");
+        assert_eq!(app.current().cursor_x, 6);
+
+        type_str(&mut app, " \"");
+        assert_eq!(app.current().text[0], "f([1]) \"\"");
+        press(&mut app, KeyCode::Backspace); // an empty pair goes away completely
+        assert_eq!(app.current().text[0], "f([1]) ");
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Backspace); // not an empty pair, so only the 1 goes
+        assert_eq!(app.current().text[0], "f([])");
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        assert_eq!(app.current().text[0], "f([1])");
+
+        let mut app = app_with(&[""]);
+        type_str(&mut app, "(x)");
+        assert_eq!(app.current().text[0], "(x)"); // off by default
+    }
+
+    #[test]
+    fn layout_comes_back_from_the_session() {
+        let mut app = App { explorer_width: 30, ..app_with(&[""]) };
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL));
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::ALT));
+        let session = app.session();
+        assert!(!session.explorer_open);
+        assert!(session.wrap_enabled);
+
+        let mut app = App { explorer_width: 30, ..app_with(&[""]) };
+        assert!(app.explorer_open);
+        app.restore_layout(&session);
+        assert!(!app.explorer_open);
+        assert!(app.wrap_enabled);
+
+        // the explorer gets its configured share of the screen
+        app.explorer_open = true;
+        assert_eq!(app.layout(Rect::new(0, 0, 100, 10)).explorer.width, 30);
+    }
+
+    #[test]
+    fn invalid_utf8_opens_read_only_and_is_never_saved() {
+        let folder = temp_folder("not-utf8");
+        let path = format!("{}binary.txt", folder);
+        fs::write(&path, b"ok\n\xff\xfe bytes\n").unwrap();
+        let mut app = app_in(&folder);
+        assert!(app.switch_note("binary.txt"));
+        assert_eq!(app.current().text, vec!["ok", "\u{fffd}\u{fffd} bytes"]);
+        assert!(app.current().read_only && app.current().not_utf8);
+        assert_eq!(app.status_message.as_ref().map(|(message, _)| message.as_str()), Some("binary.txt isn't valid UTF-8, opened read-only"));
+
+        // Ctrl+L can't unlock it, and even a forced save leaves the file alone
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL));
+        assert!(app.current().read_only);
+        app.current_mut().dirty = true;
+        assert!(app.save_note("binary.txt").is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"ok\n\xff\xfe bytes\n");
+    }
+
+    #[test]
+    fn notes_can_be_sorted_by_modified_time() {
+        let folder = temp_folder("sort-modified");
+        fs::create_dir_all(format!("{}sub", folder)).unwrap();
+        let now = std::time::SystemTime::now();
+        for (name, age_secs) in [("a.txt", 300), ("b.txt", 10), ("c.txt", 100)] {
+            let file = fs::File::create(format!("{}{}", folder, name)).unwrap();
+            file.set_modified(now - Duration::from_secs(age_secs)).unwrap();
+        }
+        let mut app = app_in(&folder);
+        app.get_notes().unwrap();
+        assert_eq!(app.files, vec!["sub/", "a.txt", "b.txt", "c.txt"]);
+
+        // Alt+S goes to newest first, the selection stays on the same file
+        app.file_select_index = 1;
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::ALT));
+        assert_eq!(app.files, vec!["sub/", "b.txt", "c.txt", "a.txt"]);
+        assert_eq!(app.files[app.file_select_index], "a.txt");
+
+        fs::write(format!("{}a.txt", folder), "now the biggest and the newest").unwrap();
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::ALT));
+        assert_eq!(app.files, vec!["sub/", "a.txt", "b.txt", "c.txt"]);
+        assert_eq!(app.sort_order, SortOrder::Size);
+    }
+
+    #[test]
+    fn pasting_keeps_line_breaks_and_undoes_in_one_step() {
+        let mut app = app_with(&["[]"]);
+        app.current_mut().cursor_x = 1;
+        app.handle_event(Event::Paste("a\nb\r\nc".to_string()));
+        assert_eq!(app.current().text, vec!["[a", "b", "c]"]);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (2, 1));
+        press(&mut app, KeyCode::Char('!'));
+        assert_eq!(app.current().text, vec!["[a", "b", "c!]"]);
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        assert_eq!(app.current().text, vec!["[]"]);
+
+        // a prompt only gets the first line
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL));
+        app.handle_event(Event::Paste("needle\nhay".to_string()));
+        assert_eq!(app.search_query, "needle");
+        assert_eq!(app.current().text, vec!["[]"]);
+    }
+
+    #[test]
+    fn saving_rotates_backups_of_the_old_version() {
+        let folder = temp_folder("backups");
+        let path = format!("{}note.txt", folder);
+        fs::write(&path, "first\n").unwrap();
+        let mut app = App { keep_backups: 1, ..app_in(&folder) };
+        app.open_note("note.txt").unwrap();
+
+        type_str(&mut app, "2");
+        app.save_note("note.txt").unwrap();
+        type_str(&mut app, "3");
+        app.save_note("note.txt").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "23first\n");
+        // only one backup is kept, and it's the version right before the last save
+        assert_eq!(fs::read_to_string(format!("{}.bak.1", path)).unwrap(), "2first\n");
+        assert!(!Path::new(&format!("{}.bak.2", path)).exists());
+
+        app.keep_backups = 2;
+        type_str(&mut app, "4");
+        app.save_note("note.txt").unwrap();
+        assert_eq!(fs::read_to_string(format!("{}.bak.1", path)).unwrap(), "23first\n");
+        assert_eq!(fs::read_to_string(format!("{}.bak.2", path)).unwrap(), "2first\n");
+
+        // a save that fails (here the note went read-only) leaves the backups alone, however often autosave tries
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions.clone()).unwrap();
+        type_str(&mut app, "5");
+        assert!(app.save_note("note.txt").is_err());
+        for _ in 0..3 {
+            assert!(app.save_all().is_err());
+        }
+        assert_eq!(fs::read_to_string(format!("{}.bak.1", path)).unwrap(), "23first\n");
+        assert_eq!(fs::read_to_string(format!("{}.bak.2", path)).unwrap(), "2first\n");
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&path, permissions).unwrap();
+    }
+
+    #[test]
+    fn scrollbar_only_shows_when_the_note_does_not_fit() {
+        let area = Rect::new(0, 0, 80, 12);
+        let border_column = |app: &App| {
+            let mut buf = ratatui::buffer::Buffer::empty(area);
+            app.render(area, &mut buf);
+            let editor = app.layout(area).editor;
+            (editor.y + 1..editor.bottom() - 1).map(|y| buf[(editor.right() - 1, y)].symbol().to_string()).collect::<Vec<String>>()
+        };
+
+        let app = App { explorer_open: false, ..app_with(&["short", "note"]) };
+        assert!(border_column(&app).iter().all(|symbol| symbol == "│"));
+
+        let lines: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        let mut app = App { explorer_open: false, ..app_with(&lines.iter().map(String::as_str).collect::<Vec<&str>>()) };
+        let column = border_column(&app);
+        assert_eq!(column.first().map(String::as_str), Some("█")); // at the top
+        assert_ne!(column.last().map(String::as_str), Some("█"));
+        app.current_mut().scroll_y = 99;
+        assert_eq!(border_column(&app).last().map(String::as_str), Some("█"));
+    }
+
+    #[test]
+    fn edited_lines_are_marked_next_to_their_numbers() {
+        let folder = temp_folder("gutter");
+        fs::write(format!("{}note.txt", folder), "one\ntwo\n").unwrap();
+        let mut app = App { explorer_open: false, ..app_in(&folder) };
+        app.open_note("note.txt").unwrap();
+        press(&mut app, KeyCode::Char('!'));
+        press(&mut app, KeyCode::End);
+        press(&mut app, KeyCode::Enter);
+
+        let area = Rect::new(0, 0, 200, 8);
+        let numbers = app.layout(area).line_numbers;
+        let marker = |app: &App, row: u16| {
+            let mut buf = ratatui::buffer::Buffer::empty(area);
+            app.render(area, &mut buf);
+            let cell = &buf[(numbers.x, numbers.y + 1 + row)];
+            (cell.symbol().to_string(), cell.fg)
+        };
+        assert_eq!(marker(&app, 0), ("┃".to_string(), Color::Yellow));
+        assert_eq!(marker(&app, 1), ("┃".to_string(), Color::Green));
+        assert_eq!(marker(&app, 2).0, "│");
+
+        app.save_note("note.txt").unwrap();
+        assert_eq!(marker(&app, 0).0, "│");
+    }
+
+    #[test]
+    fn esc_closes_every_popup_and_nothing_else_gets_through() {
+        for mode in ALL_MODES {
+            let mut app = app_with(&["text"]);
+            app.files = vec!["a.txt".to_string()];
+            app.mode = mode;
+            app.goto_buffer = "12".to_string();
+            press(&mut app, KeyCode::Char('q'));
+            press(&mut app, KeyCode::Esc);
+            assert_eq!(app.mode, Mode::Editing, "{:?} didn't close", mode);
+            assert!(app.goto_buffer.is_empty());
+            assert!(!app.exit || mode == Mode::QuitConfirm, "{:?} let a key through", mode);
+        }
+
+        // the help menu takes the keyboard too now, and Ctrl+H still toggles it
+        let mut app = app_with(&["text"]);
+        let ctrl_h = KeyEvent::new(KeyCode::Char('h'), KeyModifiers::CONTROL);
+        app.handle_key_event(ctrl_h);
+        type_str(&mut app, "x");
+        assert_eq!(app.current().text, vec!["text"]);
+        app.handle_key_event(ctrl_h);
+        assert_eq!(app.mode, Mode::Editing);
+    }
+
+    #[test]
+    fn vim_mode_dd_deletes_the_line() {
+        let mut app = App { vim_mode: true, vim_normal: true, ..app_with(&["one", "two", "three"]) };
+        type_str(&mut app, "jdd");
+        assert_eq!(app.current().text, vec!["one", "three"]);
+        assert_eq!(app.current().cursor_y, 1);
+
+        // a single d followed by something else doesn't delete anything, and plain keys never become text
+        type_str(&mut app, "dkq");
+        assert_eq!(app.current().text, vec!["one", "three"]);
+        assert_eq!(app.current().cursor_y, 0);
+
+        type_str(&mut app, "dd");
+        type_str(&mut app, "dd");
+        assert_eq!(app.current().text, vec![""]);
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        assert_eq!(app.current().text, vec!["three"]);
+    }
+
+    #[test]
+    fn vim_mode_o_opens_a_line_and_esc_goes_back_to_normal() {
+        let mut app = App { vim_mode: true, vim_normal: true, ..app_with(&["  one", "two"]) };
+        type_str(&mut app, "lo");
+        assert!(!app.vim_normal);
+        type_str(&mut app, "new");
+        assert_eq!(app.current().text, vec!["  one", "  new", "two"]); // indented like the line it was opened from
+        press(&mut app, KeyCode::Esc);
+        assert!(app.vim_normal);
+
+        type_str(&mut app, "jOtop");
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.current().text, vec!["  one", "  new", "top", "two"]);
+        type_str(&mut app, "xa!");
+        assert_eq!(app.current().text[2], "to!");
+
+        // with vim_mode off, Esc and letters behave like always
+        let mut app = app_with(&[""]);
+        press(&mut app, KeyCode::Esc);
+        type_str(&mut app, "dd");
+        assert_eq!(app.current().text, vec!["dd"]);
+    }
+
+    #[test]
+    fn creating_a_note_in_a_subfolder_makes_the_folder() {
+        let folder = temp_folder("create-path");
+        let mut app = app_in(&folder);
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL));
+        type_str(&mut app, "sub/a.txt");
+        press(&mut app, KeyCode::Enter);
+        assert!(Path::new(&format!("{}sub/a.txt", folder)).is_file());
+        assert_eq!(app.current().display_name(), "sub/a.txt");
+        assert!(app.files.contains(&"sub/".to_string()));
+
+        // nothing outside the notes folder
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL));
+        type_str(&mut app, "../escaped.txt");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::CreateNote);
+        assert!(!Path::new(&format!("{}../escaped.txt", folder)).exists());
+        assert!(app.status_message.as_ref().is_some_and(|(message, _)| message.ends_with("is outside the notes folder")));
+        assert!(app.create_note("/tmp/absolute.txt").is_err());
+        assert!(app.rename_note("sub/a.txt", "sub/../../b.txt").is_err());
+    }
+
+    #[test]
+    fn status_line_shows_line_column_and_selection() {
+        let area = Rect::new(0, 0, 120, 6);
+        let status = |app: &App| {
+            let mut buf = ratatui::buffer::Buffer::empty(area);
+            app.render(area, &mut buf);
+            (0..area.width).map(|x| buf[(x, area.height - 1)].symbol().to_string()).collect::<String>()
+        };
+        let mut app = App { explorer_open: false, ..app_with(&["héllo", "world"]) };
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Right);
+        assert!(status(&app).contains(" Ln 1, Col 3 "), "{}", status(&app));
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Down, KeyModifiers::SHIFT));
+        assert!(status(&app).contains(" Ln 2, Col 3 (6 selected, 2 lines) "), "{}", status(&app));
+    }
+
+    #[test]
+    fn long_file_names_are_cut_to_one_row() {
+        let name = format!("{}.txt", "a".repeat(56));
+        let short = truncate_name(&name, 20);
+        assert_eq!(short, format!("{}…", "a".repeat(19)));
+        assert_eq!(short.chars().count(), 20);
+        assert_eq!(truncate_name("short.txt", 20), "short.txt");
+        assert_eq!(truncate_name("x.txt", 0), "");
+
+        // in the explorer the next file is still on the very next row
+        let mut app = App { explorer_width: 25, ..app_with(&[""]) };
+        app.files = vec![name, "b.txt".to_string()];
+        let area = Rect::new(0, 0, 88, 10); // 22 columns for the explorer, 20 inside the borders
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        (&app).render(area, &mut buf);
+        let explorer = app.layout(area).explorer;
+        let row = |y: u16| (explorer.x + 1..explorer.right() - 1).map(|x| buf[(x, y)].symbol().to_string()).collect::<String>();
+        assert_eq!(row(explorer.y + 1), format!("{}…", "a".repeat(19)));
+        assert!(row(explorer.y + 2).starts_with("b.txt"));
+    }
+
+    #[test]
+    fn ruler_shades_its_column_and_follows_scrolling() {
+        let area = Rect::new(0, 0, 100, 8);
+        let mut app = App { ruler_column: Some(10), ..app_with(&["a line that goes past the ruler"]) };
+        let editor = app.layout(area).editor;
+        let bg_at = |app: &App, column: u16| {
+            let mut buf = ratatui::buffer::Buffer::empty(area);
+            app.render(area, &mut buf);
+            (buf[(editor.x + 1 + column, editor.y + 1)].bg, buf[(editor.x + 1 + column, editor.y + 3)].bg)
+        };
+        assert_eq!(bg_at(&app, 10), (Theme::DARK.ruler, Theme::DARK.ruler));
+        assert_eq!(bg_at(&app, 9).0, Color::Reset);
+
+        app.current_mut().scroll_x = 4;
+        assert_eq!(bg_at(&app, 6).0, Theme::DARK.ruler);
+        app.ruler_column = None;
+        assert_eq!(bg_at(&app, 6).0, Color::Reset);
+    }
+
+    #[test]
+    fn alt_r_reverts_to_the_saved_note_after_asking() {
+        let folder = temp_folder("revert");
+        fs::write(format!("{}note.txt", folder), "on disk\nsecond\n").unwrap();
+        let mut app = app_in(&folder);
+        app.open_note("note.txt").unwrap();
+        press(&mut app, KeyCode::Down);
+        type_str(&mut app, "edited ");
+        let alt_r = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::ALT);
+
+        app.handle_key_event(alt_r);
+        assert_eq!(app.mode, Mode::RevertConfirm);
+        press(&mut app, KeyCode::Char('n'));
+        assert_eq!(app.current().text[1], "edited second");
+
+        app.handle_key_event(alt_r);
+        press(&mut app, KeyCode::Char('y'));
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(app.current().text, vec!["on disk", "second"]);
+        assert!(!app.current().dirty);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (1, 6)); // as close to where it was as the text allows
+
+        // nothing unsaved, so nothing to ask about
+        fs::write(format!("{}note.txt", folder), "changed elsewhere\n").unwrap();
+        app.handle_key_event(alt_r);
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(app.current().text, vec!["changed elsewhere"]);
+
+        // deleted elsewhere, keep the edits and say so
+        press(&mut app, KeyCode::Home);
+        type_str(&mut app, "kept ");
+        fs::remove_file(format!("{}note.txt", folder)).unwrap();
+        app.handle_key_event(alt_r);
+        press(&mut app, KeyCode::Char('y'));
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(app.status_message.as_ref().map(|(message, _)| message.as_str()), Some("Failed to revert note: note.txt not found"));
+        assert_eq!(app.current().text, vec!["kept changed elsewhere"]);
+        assert!(app.current().dirty);
+    }
+
+    #[test]
+    fn single_trailing_newline_option_applies_on_save() {
+        let folder = temp_folder("single-newline");
+        fs::write(format!("{}note.txt", folder), "text\n\n\n").unwrap();
+        let mut app = App { single_trailing_newline: true, ..app_in(&folder) };
+        app.open_note("note.txt").unwrap();
+        app.save_note("note.txt").unwrap();
+        assert_eq!(fs::read_to_string(format!("{}note.txt", folder)).unwrap(), "text\n");
+        assert_eq!(app.current().text, vec!["text"]);
+    }
+
+    #[test]
+    fn tab_bar_shows_the_title_and_unnamed_notes() {
+        let area = Rect::new(0, 0, 100, 6);
+        let top = |app: &App| {
+            let mut buf = ratatui::buffer::Buffer::empty(area);
+            app.render(area, &mut buf);
+            (0..area.width).map(|x| buf[(x, 0)].symbol().to_string()).collect::<String>()
+        };
+        let mut app = App { explorer_open: false, ..App::default() };
+        assert!(top(&app).contains(" Editor  [No Name] "), "{}", top(&app));
+
+        app.title = "Notes".to_string();
+        app.current_mut().file_name = Some("todo.txt".to_string());
+        app.current_mut().dirty = true;
+        assert!(top(&app).contains(" Notes  todo.txt* "), "{}", top(&app));
+        app.title.clear();
+        assert!(top(&app).starts_with("┌ todo.txt* "), "{}", top(&app));
+    }
+
+    #[test]
+    fn fuzzy_finder_opens_the_best_match_from_any_folder() {
+        let folder = temp_folder("fuzzy-finder");
+        fs::create_dir_all(format!("{}work/old", folder)).unwrap();
+        fs::write(format!("{}ideas.md", folder), "").unwrap();
+        fs::write(format!("{}work/todo.txt", folder), "buy milk").unwrap();
+        fs::write(format!("{}work/old/todo-2023.txt", folder), "").unwrap();
+        fs::write(format!("{}work/picture.png", folder), "").unwrap();
+        fs::write(format!("{}.hidden.txt", folder), "").unwrap();
+        let mut app = app_in(&folder);
+
+        app.apply_key(KeyCode::Char('p'), KeyModifiers::CONTROL);
+        assert_eq!(app.mode, Mode::FindFile);
+        assert_eq!(app.find_notes, vec!["ideas.md", "work/old/todo-2023.txt", "work/todo.txt"]);
+
+        type_str(&mut app, "wtodo");
+        let results: Vec<&str> = app.find_results().into_iter().map(|(note, _)| note).collect();
+        assert_eq!(results, vec!["work/todo.txt", "work/old/todo-2023.txt"]);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(app.current().display_name(), "work/todo.txt");
+        assert_eq!(app.current().text, vec!["buy milk"]);
+        assert!(app.find_query.is_empty());
+
+        // nothing matching, Enter just leaves the finder open
+        app.apply_key(KeyCode::Char('p'), KeyModifiers::CONTROL);
+        type_str(&mut app, "zzz");
+        assert!(app.find_results().is_empty());
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::FindFile);
+    }
+
+    #[test]
+    fn an_empty_text_is_drawn_as_one_empty_line() {
+        let mut app = app_with(&[]);
+        app.current_mut().cursor_y = 4;
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(60, 12)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        assert_eq!(app.current().text, vec![""]);
+        assert_eq!(app.current().cursor_y, 0);
+        press(&mut app, KeyCode::Backspace);
+        type_str(&mut app, "ok");
+        assert_eq!(app.current().text, vec!["ok"]);
+    }
+
+    #[test]
+    fn ctrl_backspace_and_delete_remove_whole_words() {
+        let mut app = app_with(&["some words here"]);
+        app.current_mut().cursor_x = 10;
+        app.apply_key(KeyCode::Backspace, KeyModifiers::CONTROL);
+        assert_eq!(app.current().text, vec!["some  here"]);
+        app.apply_key(KeyCode::Delete, KeyModifiers::CONTROL);
+        assert_eq!(app.current().text, vec!["some "]);
+        app.apply_key(KeyCode::Backspace, KeyModifiers::ALT);
+        assert_eq!(app.current().text, vec![""]);
+        app.apply_key(KeyCode::Char('z'), KeyModifiers::CONTROL);
+        assert_eq!(app.current().text, vec!["some "]);
+    }
+
+    #[test]
+    fn first_run_starts_on_a_welcome_note() {
+        let folder = temp_folder("first-run");
+        let mut app = app_in(&folder);
+        app.ensure_folder();
+        app.restore_session(&Session::default()).unwrap();
+        assert_eq!(app.current().file_name.as_deref(), Some("default.txt"));
+        assert_eq!(app.current().text[0], "Welcome to trmnotes!");
+        assert!(app.current().text.contains(&"Ctrl+O / Ctrl+P: open one, from a list or by typing part of its name".to_string()));
+        assert!(app.current().text.contains(&"Ctrl+S: save (autosave does it for you too)".to_string()));
+        assert_eq!(app.status_message, None);
+
+        // it tells you the keys you actually have
+        let folder = temp_folder("first-run-keys");
+        let mut app = app_in(&folder);
+        app.keymap = KeyMap::new(&Config::parse("[keys]\nsave = \"ctrl+j\"").unwrap().keys).0;
+        app.ensure_folder();
+        let welcome = fs::read_to_string(format!("{}default.txt", folder)).unwrap();
+        assert!(welcome.contains("\nCtrl+J: save (autosave does it for you too)\n"), "{}", welcome);
+
+        // the folder isn't empty anymore, so an edited (or emptied) default.txt is left alone next time
+        fs::write(format!("{}default.txt", folder), "mine").unwrap();
+        app_in(&folder).ensure_folder();
+        assert_eq!(fs::read_to_string(format!("{}default.txt", folder)).unwrap(), "mine");
+
+        // without the welcome note there's no file, just an unnamed note with a hint on what to do
+        let folder = temp_folder("first-run-quiet");
+        let mut app = App { welcome_note: false, ..app_in(&folder) };
+        app.ensure_folder();
+        app.restore_session(&Session::default()).unwrap();
+        assert!(!Path::new(&folder).join("default.txt").exists());
+        assert_eq!(app.current().file_name, None);
+        assert_eq!(app.status_message, None);
+        let area = Rect::new(0, 0, 100, 12);
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        app.render(area, &mut buf);
+        let screen: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("No note open yet"));
+        assert!(screen.contains("Ctrl+N: new note"));
+
+        type_str(&mut app, "x");
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        app.render(area, &mut buf);
+        let screen: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(!screen.contains("No note open yet"));
+    }
+
+    #[test]
+    fn only_what_is_in_view_gets_drawn() {
+        let area = Rect::new(0, 0, 60, 12);
+        let screen = |app: &mut App| {
+            let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(area.width, area.height)).unwrap();
+            terminal.draw(|frame| app.draw(frame)).unwrap();
+            let buffer = terminal.backend().buffer().clone();
+            (0..area.height).map(|y| (0..area.width).map(|x| buffer[(x, y)].symbol().to_string()).collect::<String>()).collect::<Vec<_>>()
+        };
+
+        // far more lines than fit in a u16 scroll offset, the cursor's line still shows up
+        let lines: Vec<String> = (1..=100_000).map(|i| format!("line {}", i)).collect();
+        let mut app = App { explorer_open: false, ..app_with(&lines.iter().map(String::as_str).collect::<Vec<_>>()) };
+        app.current_mut().cursor_y = 69_999;
+        let rows = screen(&mut app);
+        assert!(rows.iter().any(|row| row.contains("│line 70000 ")), "{:#?}", rows);
+
+        // a code fence far above the view still counts
+        app.current_mut().text[10] = "```".to_string();
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        app.render(area, &mut buf);
+        let row = (0..area.height).find(|&y| (0..area.width).map(|x| buf[(x, y)].symbol()).collect::<String>().contains("line 70000")).unwrap();
+        let x = (0..area.width).find(|&x| buf[(x, row)].symbol() == "l").unwrap();
+        assert_eq!(buf[(x, row)].fg, Color::DarkGray);
+
+        // one long line, scrolled sideways it shows the part around the cursor
+        let line: String = (0..100_000).map(|i| char::from(b'a' + (i % 26) as u8)).collect();
+        let mut app = App { explorer_open: false, ..app_with(&[&line]) };
+        app.current_mut().cursor_x = 50_000; // 50_000 % 26 is 2, so that's a 'c'
+        let rows = screen(&mut app);
+        assert!(rows.iter().any(|row| row.contains("abcdefghijklmnopqrstuvwxyzab")));
+        let (row, col) = (1 + app.current().cursor_y - app.current().scroll_y, app.current().cursor_x - app.current().scroll_x);
+        assert_eq!(rows[row].chars().filter(|c| c.is_ascii_lowercase()).nth(col), Some('c'));
+
+        // and wrapped, the rows in view are the ones around the cursor too
+        app.wrap_enabled = true;
+        let rows = screen(&mut app);
+        let text_rows: Vec<&String> = rows[1..rows.len() - 1].iter().filter(|row| row.contains("abc")).collect();
+        assert!(text_rows.len() >= 8, "{:#?}", rows);
+    }
+
+    #[test]
+    fn cut_columns_keeps_styles_across_the_cut() {
+        let line = Line::from(vec!["abc".red(), "def".into(), "ghi".blue()]);
+        let cut = cut_columns(line, 2, 5);
+        assert_eq!(cut.spans, vec!["c".red(), "def".into(), "g".blue()]);
+        assert_eq!(cut_columns(Line::from("abc"), 5, 3).spans, vec![]);
+        assert_eq!(cut_columns(Line::from("abc"), 0, 0).spans, vec![]);
+    }
+
+    #[test]
+    fn long_lines_only_style_whats_on_screen_but_look_the_same() {
+        // the tab ends at column 2000, and the editor shows from the middle of it
+        let line = format!("{}\t**bold** {}", "x".repeat(1996), "y".repeat(2000));
+        let mut app = app_with(&[&line]);
+        app.current_mut().scroll_x = 1998;
+        let area = Rect::new(0, 0, 80, 6);
+        let text = app.layout(area).text;
+        let render = |app: &App| {
+            let mut buf = ratatui::buffer::Buffer::empty(area);
+            app.render(area, &mut buf);
+            (0..12).map(|x| buf[(text.x + x, text.y)].clone()).collect::<Vec<_>>()
+        };
+        let cells = render(&app);
+        assert_eq!(cells.iter().map(|cell| cell.symbol()).collect::<String>(), "  **bold** y");
+        assert!(cells[4].modifier.contains(ratatui::style::Modifier::BOLD));
+        assert!(!cells[11].modifier.contains(ratatui::style::Modifier::BOLD));
+
+        // search matches are found in the whole line but drawn where they are on screen
+        app.mode = Mode::Search;
+        app.search_query = "bold".to_string();
+        app.update_search_matches();
+        let cells = render(&app);
+        assert_eq!((cells[3].bg, cells[4].bg, cells[8].bg), (Color::Reset, Color::DarkGray, Color::Reset));
+    }
+
+    /*
+    Drawing a note shouldn't get slower with the length of its lines, only what's on screen is styled and drawn.
+    A 100k char line is timed against a 5k char one (which fills the screen too, wrapped) and has to stay within 2.5 times of it.
+    Some things still go over the whole line, like the word count, and typing (String::insert moves the rest of the line along),
+    so it isn't completely flat. Those are quick scans though, typing in the middle of the long line has to stay well inside a frame.
+    A note with 100x the lines is still slower to draw, the counts and the changed-lines bar go over every line.
+    It's timing, so it's left out of normal runs: `cargo test --release -- --ignored long_text_benchmark --nocapture`.
+     */
+    #[test]
+    #[ignore]
+    fn long_text_benchmark() {
+        let time = |what: &str, runs: u32, f: &mut dyn FnMut()| {
+            let start = Instant::now();
+            for _ in 0..runs {
+                f();
+            }
+            let took = start.elapsed() / runs;
+            eprintln!("{}: {:?}", what, took);
+            took
+        };
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(120, 40)).unwrap();
+        let mut draw = |app: &mut App, what: &str| time(what, 100, &mut || {
+            terminal.draw(|frame| app.draw(frame)).unwrap();
+        });
+        // with some Markdown in it, so there's something to style
+        let line = |len: usize| "some **bold** and `code` words ".chars().cycle().take(len).collect::<String>();
+        let (mut short, mut long) = (app_with(&[&line(5_000)]), app_with(&[&line(100_000)]));
+        short.current_mut().cursor_x = 2_500;
+        long.current_mut().cursor_x = 50_000;
+
+        let typing = time("typing in the middle of a 100k char line", 100, &mut || press(&mut long, KeyCode::Char('x')));
+        assert!(typing < Duration::from_millis(1), "typing took {:?}", typing);
+
+        for wrap in [false, true] {
+            short.wrap_enabled = wrap;
+            long.wrap_enabled = wrap;
+            let (short_time, long_time) = (draw(&mut short, "drawing a 5k char line"), draw(&mut long, "drawing a 100k char line"));
+            assert!(long_time < short_time.mul_f64(2.5), "wrapping {}: {:?} against {:?}", wrap, long_time, short_time);
+        }
+
+        let (mut short, mut long) = (app_with(&vec!["a short line"; 1_000]), app_with(&vec!["a short line"; 100_000]));
+        short.current_mut().cursor_y = 500;
+        long.current_mut().cursor_y = 50_000;
+        let (short_time, long_time) = (draw(&mut short, "drawing 1k lines"), draw(&mut long, "drawing 100k lines"));
+        assert!(long_time < short_time * 30, "{:?} against {:?}", long_time, short_time);
+    }
+
+    #[test]
+    fn outline_lists_headings_and_jumps_to_them() {
+        let mut app = app_with(&["# Notes", "intro", "## Todo", "- milk", "### Later", "", "## Done", "all of it"]);
+        app.current_mut().cursor_y = 3; // in the Todo section
+        app.apply_key(KeyCode::Char('t'), KeyModifiers::ALT);
+        assert_eq!(app.mode, Mode::Outline);
+        assert_eq!(app.outline.iter().map(|(line, level, _)| (*line, *level)).collect::<Vec<_>>(), vec![(0, 1), (2, 2), (4, 3), (6, 2)]);
+        assert_eq!(app.outline_index, 1);
+
+        let area = Rect::new(0, 0, 80, 20);
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        app.render(area, &mut buf);
+        let screen: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("│Notes"));
+        assert!(screen.contains("│    Later"));
+
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down); // already on the last one
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (6, 0));
+
+        // a note without headings doesn't open an empty list
+        let mut app = app_with(&["just text"]);
+        app.apply_key(KeyCode::Char('t'), KeyModifiers::ALT);
+        assert_eq!(app.mode, Mode::Editing);
+        assert!(app.status_message.is_some());
+    }
+
+    #[test]
+    fn keys_from_the_config_run_their_commands() {
+        let config = Config::parse("[keys]\nsave = \"ctrl+j\"\nhelp = \"f1\"\ninfo = \"f3\"").unwrap();
+        let folder = temp_folder("keymap");
+        let mut app = App { root: folder.clone(), folder: folder.clone(), store: Box::new(FsNoteStore::new(&folder)), ..App::new(&config) };
+        type_str(&mut app, "hi");
+        app.apply_key(KeyCode::Char('s'), KeyModifiers::CONTROL); // not save anymore, and not bound to anything else
+        assert!(!Path::new(&folder).join("default.txt").exists());
+        app.apply_key(KeyCode::Char('j'), KeyModifiers::CONTROL);
+        assert!(Path::new(&folder).join("default.txt").exists());
+
+        // the help and the status line show the keys that are actually set
+        app.apply_key(KeyCode::F(1), KeyModifiers::NONE);
+        assert_eq!(app.mode, Mode::Help);
+        let area = Rect::new(0, 0, 120, 30);
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        app.render(area, &mut buf);
+        let screen: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("Ctrl+J: Save"));
+        assert!(screen.contains("Ctrl+Shift+Tab / Alt+Left: Previous Tab"));
+        app.apply_key(KeyCode::F(1), KeyModifiers::NONE);
+        assert_eq!(app.mode, Mode::Editing);
+        app.status_message = None; // the "Saved" message would push the keys off the end
+        app.render(area, &mut buf);
+        let screen: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("<F1>  Quit <Ctrl+Q>"));
+
+        // a popup's own key closes it again, whatever that key was set to
+        app.apply_key(KeyCode::F(3), KeyModifiers::NONE);
+        assert_eq!(app.mode, Mode::Info);
+        app.apply_key(KeyCode::F(3), KeyModifiers::NONE);
+        assert_eq!(app.mode, Mode::Editing);
+    }
+
+    #[test]
+    fn save_as_writes_a_new_note_and_leaves_the_old_one() {
+        let folder = temp_folder("save-as");
+        fs::write(format!("{}a.txt", folder), "first\n").unwrap();
+        let mut app = app_in(&folder);
+        assert!(app.switch_note("a.txt"));
+        type_str(&mut app, "new ");
+
+        app.apply_key(KeyCode::Char('a'), KeyModifiers::ALT);
+        assert_eq!(app.mode, Mode::CreateNote);
+        type_str(&mut app, "b.txt");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(fs::read_to_string(format!("{}a.txt", folder)).unwrap(), "first\n");
+        assert_eq!(fs::read_to_string(format!("{}b.txt", folder)).unwrap(), "new first\n");
+        assert_eq!(app.current().display_name(), "b.txt");
+        assert!(!app.current().dirty);
+        assert!(app.files.contains(&"b.txt".to_string()));
+
+        // saving over a note that's there asks first, N goes back to picking a name
+        type_str(&mut app, "newer ");
+        app.apply_key(KeyCode::Char('a'), KeyModifiers::ALT);
+        type_str(&mut app, "a.txt");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::OverwriteConfirm);
+        press(&mut app, KeyCode::Char('n'));
+        assert_eq!(app.mode, Mode::CreateNote);
+        assert_eq!(fs::read_to_string(format!("{}a.txt", folder)).unwrap(), "first\n");
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Char('y'));
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(fs::read_to_string(format!("{}a.txt", folder)).unwrap(), "new newer first\n");
+        assert_eq!(fs::read_to_string(format!("{}b.txt", folder)).unwrap(), "new first\n");
+        assert_eq!(app.current().display_name(), "a.txt");
+
+        // a note open in another tab is refused, that tab would be left showing the old text
+        assert!(app.switch_note("b.txt"));
+        app.apply_key(KeyCode::Char('a'), KeyModifiers::ALT);
+        type_str(&mut app, "default.txt");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::CreateNote);
+        assert_eq!(app.current().display_name(), "b.txt");
+
+        // names are cleaned up first, so "./a.txt" is the a.txt that's open in the other tab
+        press(&mut app, KeyCode::Esc);
+        app.apply_key(KeyCode::Char('a'), KeyModifiers::ALT);
+        type_str(&mut app, "./a.txt");
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Char('y'));
+        assert_eq!(app.mode, Mode::CreateNote);
+        assert_eq!(fs::read_to_string(format!("{}a.txt", folder)).unwrap(), "new newer first\n");
+        press(&mut app, KeyCode::Esc);
+        app.apply_key(KeyCode::Char('a'), KeyModifiers::ALT);
+        type_str(&mut app, ".//c.txt");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current().display_name(), "c.txt");
+        assert!(app.files.contains(&"c.txt".to_string()));
+    }
+
+    #[test]
+    fn long_notes_get_a_warning_past_the_limits() {
+        let mut app = app_with(&["one", "two", "thrée"]);
+        assert_eq!(app.size_warning(), None); // no limits by default
+
+        app.warn_lines = Some(3);
+        app.warn_chars = Some(11);
+        assert_eq!(app.size_warning(), None); // right at the limits is fine, é is one char even though it's two bytes
+
+        type_str(&mut app, "x");
+        assert_eq!(app.size_warning(), Some("long note: 12/11 chars".to_string()));
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.size_warning(), Some("long note: 4/3 lines, 12/11 chars".to_string()));
+
+        // it's only a warning, typing carries on and it's in the status line
+        type_str(&mut app, "more");
+        assert_eq!(app.current().text.len(), 4);
+        app.explorer_open = false;
+        let area = Rect::new(0, 0, 109, 10);
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        app.render(area, &mut buf);
+        let screen: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("long note: 4/3 lines, 16/11 chars"));
+    }
+
+    #[test]
+    fn unbound_ctrl_and_alt_chords_type_nothing() {
+        let mut app = app_with(&["text"]);
+        app.apply_key(KeyCode::Char('b'), KeyModifiers::CONTROL);
+        app.apply_key(KeyCode::Char('j'), KeyModifiers::ALT);
+        app.apply_key(KeyCode::Char('B'), KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+        assert_eq!(app.current().text, vec!["text".to_string()]);
+        assert!(!app.current().dirty);
+
+        // Shift is just a capital, and AltGr (Ctrl+Alt) chars still type
+        app.apply_key(KeyCode::Char('B'), KeyModifiers::SHIFT);
+        app.apply_key(KeyCode::Char('@'), KeyModifiers::CONTROL | KeyModifiers::ALT);
+        assert_eq!(app.current().text, vec!["B@text".to_string()]);
+    }
+
+    #[test]
+    fn writing_mode_centers_a_narrow_column_of_text() {
+        let mut app = App { explorer_open: false, writing_width: 10, ..app_with(&["hello world again"]) };
+        app.apply_key(KeyCode::Char('c'), KeyModifiers::ALT);
+        assert!(app.writing_mode);
+        app.current_mut().cursor_x = 1;
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(42, 10)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+
+        // 40 columns inside the border, 15 of margin on each side
+        let row: String = (0..42).map(|x| terminal.backend().buffer()[(x, 1)].symbol().to_string()).collect();
+        assert_eq!(row, format!("│{}hello worl{}│", " ".repeat(15), " ".repeat(15)));
+        assert_eq!(terminal.get_cursor_position().unwrap(), (17, 1).into());
+        assert_eq!(app.view_width, 10);
+
+        // clicks in the margins land on the nearest end of the row
+        let click = |column| MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column, row: 1, modifiers: KeyModifiers::NONE };
+        app.handle_mouse_event(click(2));
+        assert_eq!(app.current().cursor_x, 0);
+        app.handle_mouse_event(click(40));
+        assert_eq!(app.current().cursor_x, 9);
+
+        // a screen narrower than the column just uses all of it
+        app.writing_width = 80;
+        assert_eq!(app.layout(Rect::new(0, 0, 42, 10)).text, Rect::new(1, 1, 40, 8));
+        app.apply_key(KeyCode::Char('c'), KeyModifiers::ALT);
+        assert!(!app.writing_mode);
+    }
+
+    #[test]
+    fn an_empty_folder_can_be_browsed_without_crashing() {
+        let folder = temp_folder("empty");
+        let mut app = app_in(&folder);
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL));
+        assert_eq!(app.mode, Mode::SelectFile);
+        assert!(app.files.is_empty());
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Char('s'));
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Char('d'));
+        assert_eq!(app.mode, Mode::SelectFile);
+        assert_eq!(app.file_select_index, 0);
+
+        // both the explorer and the popup say how to make the first note
+        let area = Rect::new(0, 0, 100, 20);
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        app.render(area, &mut buf);
+        let screen: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("No notes yet — Ctrl+N to create one"));
+        let explorer = app.layout(area).explorer;
+        let explorer_text: String = (explorer.y + 1..explorer.bottom() - 1)
+            .flat_map(|y| (explorer.x + 1..explorer.right() - 1).map(move |x| (x, y)))
+            .map(|position| buf[position].symbol().to_string())
+            .collect();
+        assert!(explorer_text.contains("No notes"));
+    }
+
+    #[test]
+    fn typing_a_name_in_the_file_selection_opens_or_creates_it() {
+        let folder = temp_folder("open-typed");
+        fs::create_dir_all(format!("{}work", folder)).unwrap();
+        fs::write(format!("{}work/todo.txt", folder), "milk\n").unwrap();
+        let mut app = app_in(&folder);
+
+        // from inside a subfolder the name starts there
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL));
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter); // into work/
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.mode, Mode::CreateNote);
+        assert_eq!(app.new_file_name, "work/");
+        type_str(&mut app, "todo.txt");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::Editing);
+        assert_eq!(app.current().display_name(), "work/todo.txt");
+        assert_eq!(app.current().text, vec!["milk".to_string()]);
+
+        // a name that isn't there yet is made, folders and all, and ./ is ignored
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL));
+        press(&mut app, KeyCode::Tab);
+        for _ in 0..5 {
+            press(&mut app, KeyCode::Backspace);
+        }
+        type_str(&mut app, "./ideas//new.md");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current().display_name(), "ideas/new.md");
+        assert!(Path::new(&format!("{}ideas/new.md", folder)).is_file());
+
+        // a folder can't be opened as a note
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL));
+        type_str(&mut app, "work");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::CreateNote);
+        assert_eq!(app.current().display_name(), "ideas/new.md");
+    }
+
+    #[test]
+    fn keys_that_cant_do_anything_flash_the_border() {
+        let mut app = App { visual_bell: true, ..app_with(&["ab"]) };
+        press(&mut app, KeyCode::Right);
+        assert_eq!(app.flash_until, None); // that one moved
+
+        app.current_mut().cursor_x = 0;
+        for code in [KeyCode::Left, KeyCode::Up, KeyCode::Backspace, KeyCode::PageUp] {
+            app.flash_until = None;
+            press(&mut app, code);
+            assert!(app.flash_until.is_some(), "{:?} at the start of the note", code);
+        }
+        app.flash_until = None;
+        app.apply_key(KeyCode::Char('z'), KeyModifiers::CONTROL); // nothing to undo
+        assert!(app.flash_until.is_some());
+
+        // dropping a selection counts as doing something
+        app.flash_until = None;
+        app.current_mut().selection_anchor = Some((0, 1));
+        press(&mut app, KeyCode::Left);
+        assert_eq!(app.flash_until, None);
+
+        // it shows as an inverted editor border
+        app.flash_until = Some(Instant::now() + Duration::from_secs(60));
+        let area = Rect::new(0, 0, 60, 10);
+        let mut buf = ratatui::buffer::Buffer::empty(area);
+        app.render(area, &mut buf);
+        let editor = app.layout(area).editor;
+        assert!(buf[(editor.x, editor.y + 2)].modifier.contains(ratatui::style::Modifier::REVERSED));
+
+        // and can be turned off
+        app.visual_bell = false;
+        app.flash_until = None;
+        press(&mut app, KeyCode::Left);
+        assert_eq!(app.flash_until, None);
+    }
+
+    #[test]
+    fn notes_open_where_the_cursor_was_last_time() {
+        let folder = temp_folder("positions");
+        fs::write(format!("{}a.txt", folder), "one\ntwo\nthree\n").unwrap();
+        fs::write(format!("{}b.txt", folder), "short\n").unwrap();
+        let mut app = app_in(&folder);
+        assert!(app.switch_note("a.txt"));
+        app.current_mut().set_cursor(2, 4);
+        assert!(app.switch_note("b.txt"));
+        app.current_mut().set_cursor(0, 5);
+        let session = app.session();
+        assert_eq!(session.positions["a.txt"], (4, 2, 0));
+        assert_eq!(session.positions["b.txt"], (5, 0, 0));
+
+        // next time, b.txt got shorter on disk in the meantime
+        fs::write(format!("{}b.txt", folder), "ab\n").unwrap();
+        let mut app = app_in(&folder);
+        app.restore_layout(&session);
+        assert!(app.switch_note("a.txt"));
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (2, 4));
+        assert!(app.switch_note("b.txt"));
+        assert_eq!((app.current().cursor_y, app.current().cursor_x), (0, 2));
+
+        // still remembered even if it's not opened this time, but not once the note is gone
+        let mut app = app_in(&folder);
+        app.restore_layout(&session);
+        fs::remove_file(format!("{}a.txt", folder)).unwrap();
+        let session = app.session();
+        assert!(!session.positions.contains_key("a.txt"));
+        assert_eq!(session.positions["b.txt"], (5, 0, 0));
+    }
+
+    #[test]
+    fn saving_says_so_and_clears_the_dirty_flag() {
+        let folder = temp_folder("save-feedback");
+        let mut app = app_in(&folder);
+        app.buffers = vec![Buffer::new(None)];
+        app.active = 0;
+
+        // an untouched first-run note has nothing to save
+        app.run_action(Action::Save);
+        assert!(!Path::new(&format!("{}default.txt", folder)).exists());
+        assert_eq!(app.status_message.as_ref().map(|(message, _)| message.as_str()), Some("Nothing to save yet, type something first"));
+
+        type_str(&mut app, "one");
+        press(&mut app, KeyCode::Enter);
+        type_str(&mut app, "two");
+        assert!(app.current().dirty);
+        app.run_action(Action::Save);
+        assert!(!app.current().dirty);
+        assert!(app.current().disk_modified.is_some());
+        assert_eq!(app.current().file_name.as_deref(), Some("default.txt"));
+        assert_eq!(fs::read_to_string(format!("{}default.txt", folder)).unwrap(), "one\ntwo\n");
+        assert_eq!(app.status_message.as_ref().map(|(message, _)| message.as_str()), Some("Saved default.txt (2 lines)"));
+    }
+
+    #[test]
+    fn autosave_names_an_unnamed_note_but_never_writes_over_another_default_txt() {
+        let folder = temp_folder("save-unnamed");
+        let mut app = app_in(&folder);
+        app.buffers = vec![Buffer::new(None)];
+        app.active = 0;
+        type_str(&mut app, "first");
+        app.save_all().unwrap();
+        assert_eq!(app.current().tab_name(), "default.txt");
+        assert!(app.current().disk_modified.is_some());
+        assert_eq!(fs::read_to_string(format!("{}default.txt", folder)).unwrap(), "first\n");
+
+        // another new note would be default.txt too, but that's taken now
+        app.buffers.push(Buffer::new(None));
+        app.active = 1;
+        type_str(&mut app, "second");
+        assert_eq!(app.save_all().unwrap_err().to_string(), "default.txt already exists, save the new note with Ctrl+Shift+S / Alt+A");
+        app.run_action(Action::Save);
+        assert_eq!(fs::read_to_string(format!("{}default.txt", folder)).unwrap(), "first\n");
+        assert!(app.current().dirty);
+        assert_eq!(app.current().tab_name(), "[No Name]");
+    }
+
+    #[test]
+    fn relative_numbers_count_from_the_cursor_line() {
+        // 5 lines with the cursor on the second one
+        let numbers = |relative| (0..5).map(|i| line_number(i, 1, relative)).collect::<Vec<_>>();
+        assert_eq!(numbers(false), vec![1, 2, 3, 4, 5]);
+        assert_eq!(numbers(true), vec![1, 2, 1, 2, 3]);
+
+        // the config picks how it starts, Alt+N switches and an older session doesn't override it
+        let config = Config::parse("relative_line_numbers = true").unwrap();
+        let mut app = App::new(&config);
+        app.restore_layout(&Session::default());
+        assert!(app.relative_line_numbers);
+        app.apply_key(KeyCode::Char('n'), KeyModifiers::ALT);
+        assert!(!app.relative_line_numbers);
+        assert_eq!(app.session().relative_line_numbers, Some(false));
+    }
+
+    #[test]
+    fn the_app_works_the_same_on_a_store_in_memory() {
+        let store = InMemoryStore::with_notes(&[("a.txt", "one\n"), ("work/plan.md", "# Plan\n"), ("skip.png", "")]);
+        let mut app = App { root: "nowhere/".to_string(), folder: "nowhere/".to_string(), store: Box::new(store), ..Default::default() };
+        app.ensure_folder(); // there's no folder to make
+        app.get_notes().unwrap();
+        assert_eq!(app.files, vec!["work/", "a.txt"]);
+        assert_eq!(app.all_notes(), vec!["a.txt", "work/plan.md"]);
+
+        assert!(app.switch_note("a.txt"));
+        type_str(&mut app, "zero ");
+        app.save_note("a.txt").unwrap();
+        assert!(!app.current().dirty);
+        assert_eq!(app.store.read("a.txt").unwrap(), b"zero one\n");
+
+        assert_eq!(app.create_note("work/todo.txt").unwrap(), CreateOutcome::Created);
+        assert_eq!(app.create_note("work/todo.txt").unwrap(), CreateOutcome::AlreadyExists);
+        assert!(app.is_folder("work"));
+        app.rename_note("a.txt", "b.txt").unwrap();
+        assert_eq!(app.current().display_name(), "b.txt");
+        assert!(app.rename_note("b.txt", "work/plan.md").is_err());
+        app.delete_note("work/todo.txt").unwrap();
+        assert_eq!(app.all_notes(), vec!["b.txt", "work/plan.md"]);
+        assert!(!Path::new("nowhere").exists()); // nothing went to disk
+    }
+
+    #[test]
+    fn the_autosave_timer_saves_every_changed_note() {
+        let store = InMemoryStore::with_notes(&[("a.txt", "one\n"), ("b.txt", "two\n")]);
+        let mut app = App { autosave_secs: Some(30), store: Box::new(store), ..Default::default() };
+        assert!(app.switch_note("a.txt"));
+        type_str(&mut app, "new ");
+        assert!(app.switch_note("b.txt"));
+        type_str(&mut app, "more ");
+
+        app.next_autosave = Instant::now() + Duration::from_secs(30);
+        app.check_autosave_timer();
+        assert_eq!(app.store.read("a.txt").unwrap(), b"one\n"); // not time yet
+
+        app.next_autosave = Instant::now();
+        app.check_autosave_timer();
+        assert_eq!(app.store.read("a.txt").unwrap(), b"new one\n");
+        assert_eq!(app.store.read("b.txt").unwrap(), b"more two\n");
+        assert!(app.buffers.iter().all(|buffer| !buffer.dirty));
+        assert!(app.autosaved_at.is_some());
+        assert!(app.next_autosave > Instant::now() + Duration::from_secs(29)); // and the timer starts over
+    }
+
+    #[test]
+    fn losing_focus_saves_every_changed_note_unless_autosave_is_off() {
+        let store = InMemoryStore::with_notes(&[("a.txt", "one\n"), ("b.txt", "two\n")]);
+        let mut app = App { autosave_secs: None, store: Box::new(store), ..Default::default() };
+        assert!(app.switch_note("a.txt"));
+        type_str(&mut app, "new ");
+        assert!(app.switch_note("b.txt"));
+        type_str(&mut app, "more ");
+
+        app.handle_event(Event::FocusLost);
+        assert_eq!(app.store.read("a.txt").unwrap(), b"one\n");
+        assert!(app.current().dirty);
+
+        app.autosave_secs = Some(30);
+        app.handle_event(Event::FocusLost);
+        assert_eq!(app.store.read("a.txt").unwrap(), b"new one\n");
+        assert_eq!(app.store.read("b.txt").unwrap(), b"more two\n");
+        assert!(app.buffers.iter().all(|buffer| !buffer.dirty));
+    }
+
+    #[test]
+    fn a_split_view_shows_two_notes_and_keys_go_to_the_focused_one() {
+        let store = InMemoryStore::with_notes(&[("left.txt", "left side\n"), ("right.txt", "right side\n")]);
+        let mut app = App { explorer_open: false, store: Box::new(store), ..Default::default() };
+        app.apply_key(KeyCode::Char('v'), KeyModifiers::ALT);
+        assert_eq!(app.split, None); // nothing to put next to it yet
+        assert!(app.switch_note("right.txt"));
+        assert!(app.switch_note("left.txt"));
+        app.apply_key(KeyCode::Char('v'), KeyModifiers::ALT);
+        assert_eq!(app.split.map(|other| app.buffers[other].display_name()), Some("right.txt")); // the note from before
+
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 10)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let row: String = (0..80).map(|x| terminal.backend().buffer()[(x, 1)].symbol().to_string()).collect();
+        assert!(row.find("left side").unwrap() < row.find("right side").unwrap());
+        assert!(terminal.get_cursor_position().unwrap().x < 40);
+
+        // typing and saving only touch the focused pane, Alt+P moves the focus with its own cursor
+        type_str(&mut app, "the ");
+        app.apply_key(KeyCode::Char('p'), KeyModifiers::ALT);
+        assert_eq!(app.current().display_name(), "right.txt");
+        type_str(&mut app, "other ");
+        app.run_action(Action::Save);
+        assert_eq!(app.store.read("right.txt").unwrap(), b"other right side\n");
+        assert_eq!(app.store.read("left.txt").unwrap(), b"left side\n");
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        assert!(terminal.get_cursor_position().unwrap().x > 40);
+        assert_eq!(app.buffers[app.split.unwrap()].cursor(), (0, 4));
+
+        // switching tabs onto the other pane's note swaps the panes instead of showing it twice
+        app.switch_tab(true);
+        assert_ne!(app.split, Some(app.active));
+        let click = MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column: 5, row: 1, modifiers: KeyModifiers::NONE };
+        app.handle_mouse_event(click); // back in the left pane
+        assert!(!app.focus_right);
+        app.apply_key(KeyCode::Char('v'), KeyModifiers::ALT);
+        assert_eq!(app.split, None);
+    }
+
+    #[test]
+    fn reloading_a_note_that_got_shorter_keeps_the_cursor_on_it() {
+        let folder = temp_folder("clamp-cursor");
+        fs::write(format!("{}note.txt", folder), "one\ntwo\nthree\n").unwrap();
+        let mut app = app_in(&folder);
+        assert!(app.switch_note("note.txt"));
+        app.current_mut().set_cursor(2, 5);
+
+        // cut down by something else, then reverted to what's on disk
+        fs::write(format!("{}note.txt", folder), "ab\n").unwrap();
+        app.revert_current();
+        assert_eq!(app.current().text, vec!["ab"]);
+        assert_eq!(app.current().cursor(), (0, 2));
+        app.apply_key(KeyCode::Char('x'), KeyModifiers::NONE); // and it's safe to type there
+        assert_eq!(app.current().text, vec!["abx"]);
+    }
 }
@@ -0,0 +1,424 @@
+use std::ops::Range;
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+
+/*
+Explanation of the code:
+Lightweight Markdown highlighting for the editor.
+This isn't a real Markdown parser, it just looks at one line at a time for the common stuff:
+headings, **bold**, *italic*, `code`, ``` fenced code blocks and checked off tasks ("- [x] done").
+The only thing carried between lines is whether we're inside a fence,
+so only the lines on screen get styled, the ones above are just checked for fences (`fence_after`).
+The same rules (plus lists and links) are used by `to_html` to export a note as a web page.
+*/
+
+/*
+Styles one line of text.
+`in_fence` is whether the line is inside a fenced code block,
+and the returned bool is whether the next line is (so the caller can pass it along).
+The spans borrow from `line`, nothing is copied.
+ */
+pub fn highlight_line(line: &str, in_fence: bool) -> (Line<'_>, bool) {
+    highlight_part(line, 0..line.len(), in_fence)
+}
+
+/*
+Like `highlight_line` but only styles the bytes in `part` of the line, for lines too long to style all of every frame.
+Fences, headings and done tasks still go by the start of the line, so they look the same.
+**bold** and the like are only looked for inside `part`, one that starts before it shows up plain.
+ */
+pub fn highlight_part(line: &str, part: Range<usize>, in_fence: bool) -> (Line<'_>, bool) {
+    let text = &line[part];
+    if is_fence(line) {
+        // the fence markers themselves are dimmed like the code they wrap
+        return (Line::from(Span::styled(text, fence_style())), !in_fence);
+    }
+    if in_fence {
+        return (Line::from(Span::styled(text, fence_style())), true);
+    }
+    if let Some(level) = heading_level(line) {
+        return (Line::from(Span::styled(text, heading_style(level))), false);
+    }
+    if checkbox(line).is_some_and(|(_, checked)| checked) {
+        return (Line::from(Span::styled(text, done_style())), false);
+    }
+    (Line::from(inline_spans(text)), false)
+}
+
+// Whether the line after `line` is inside a fence, without styling anything (for lines that aren't on screen)
+pub fn fence_after(line: &str, in_fence: bool) -> bool {
+    in_fence != is_fence(line)
+}
+
+fn is_fence(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+/*
+Every heading in a note as (line, level, title), for the outline (Alt+T).
+Lines inside code fences are skipped, a # there is a comment in some code, not a heading.
+ */
+pub fn headings(lines: &[String]) -> Vec<(usize, u8, String)> {
+    let mut headings = vec![];
+    let mut in_fence = false;
+    for (y, line) in lines.iter().enumerate() {
+        if !in_fence
+            && let Some(level) = heading_level(line)
+        {
+            headings.push((y, level as u8, line[level..].trim().to_string()));
+        }
+        in_fence = fence_after(line, in_fence);
+    }
+    headings
+}
+
+// "## Title" is a level 2 heading, there has to be a space (or nothing) after the #'s
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..]; // # is one byte
+    if (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' ')) {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+fn heading_style(level: usize) -> Style {
+    let color = match level {
+        1 => Color::Magenta,
+        2 => Color::Cyan,
+        3 => Color::Green,
+        _ => Color::Yellow,
+    };
+    Style::default().fg(color).add_modifier(Modifier::BOLD)
+}
+
+fn fence_style() -> Style {
+    Style::default().fg(Color::DarkGray)
+}
+
+// a task that's done is dimmed and struck through, it's out of the way but still there
+fn done_style() -> Style {
+    Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT)
+}
+
+/*
+The checkbox of a task, a list item that starts with one like "- [ ] buy milk" or "  1. [x] done".
+Gives the byte offset of its `[` in the line and whether it's checked ([x] or [X]), None if the line isn't a task.
+ */
+pub fn checkbox(line: &str) -> Option<(usize, bool)> {
+    let (_, item) = list_item(line.trim_start_matches([' ', '\t']))?;
+    let checked = match item.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    if item.len() > 3 && !item[3..].starts_with(' ') {
+        return None; // "[x]y" is just text
+    }
+    Some((line.len() - item.len(), checked))
+}
+
+/*
+Splits a line into plain and styled spans for `code`, **bold** and *italic*.
+We go left to right, and when we find an opening marker we look for the closing one.
+If there isn't one, the marker is just a normal character.
+Code spans win over everything else, so `**not bold**` stays as code.
+ */
+fn inline_spans(line: &str) -> Vec<Span<'_>> {
+    let mut spans = vec![];
+    let mut plain = 0; // byte offset of where the unstyled text starts
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        // (how many bytes the styled span takes up, its style)
+        let found = if let Some(after) = rest.strip_prefix('`') {
+            after.find('`').map(|end| (end + 2, Style::default().fg(Color::Yellow).bg(Color::DarkGray)))
+        } else if let Some(after) = rest.strip_prefix("**") {
+            closing(after, "**").map(|end| (end + 4, Style::default().add_modifier(Modifier::BOLD)))
+        } else if let Some(after) = rest.strip_prefix('*') {
+            closing(after, "*").map(|end| (end + 2, Style::default().add_modifier(Modifier::ITALIC)))
+        } else {
+            None
+        };
+
+        match found {
+            Some((len, style)) => {
+                if plain < i {
+                    spans.push(Span::raw(&line[plain..i]));
+                }
+                spans.push(Span::styled(&line[i..i + len], style));
+                i += len;
+                plain = i;
+            }
+            None => i += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    if plain < line.len() {
+        spans.push(Span::raw(&line[plain..]));
+    }
+    spans
+}
+
+/*
+Where the closing `marker` is in `after` (the text right after the opening one).
+Emphasis has to hug the text, so "* list item" or "2 * 3 * 4" aren't treated as italic.
+ */
+fn closing(after: &str, marker: &str) -> Option<usize> {
+    if after.is_empty() || after.starts_with(char::is_whitespace) {
+        return None;
+    }
+    after.find(marker).filter(|end| *end > 0 && !after[..*end].ends_with(char::is_whitespace))
+}
+
+/*
+Turns a whole note into a standalone HTML page, for sharing it outside the app.
+Like the highlighting this goes line by line: headings, `-`/`*`/`+` and `1.` lists, ``` fences,
+and paragraphs (lines in a row that aren't any of those) which end at a blank line.
+Everything that isn't markup is escaped, so a note about HTML stays readable.
+ */
+pub fn to_html(title: &str, lines: &[String]) -> String {
+    let mut html = HtmlWriter::default();
+    let mut in_fence = false;
+    for line in lines {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            if in_fence {
+                html.body.push_str("</code></pre>\n");
+            } else {
+                html.close_blocks();
+                html.body.push_str("<pre><code>");
+            }
+            in_fence = !in_fence;
+        } else if in_fence {
+            html.body.push_str(&escape(line));
+            html.body.push('\n');
+        } else if trimmed.is_empty() {
+            html.close_blocks();
+        } else if let Some(level) = heading_level(line) {
+            html.close_blocks();
+            let text = line[level..].trim();
+            html.body.push_str(&format!("<h{level}>{}</h{level}>\n", inline_html(text)));
+        } else if let Some((list, item)) = list_item(trimmed) {
+            html.close_paragraph();
+            if html.list != Some(list) {
+                html.close_list();
+                html.body.push_str(&format!("<{}>\n", list));
+                html.list = Some(list);
+            }
+            html.body.push_str(&format!("<li>{}</li>\n", inline_html(item)));
+        } else {
+            html.close_list();
+            html.paragraph.push(inline_html(trimmed));
+        }
+    }
+    if in_fence {
+        html.body.push_str("</code></pre>\n"); // an unclosed fence runs to the end of the note
+    }
+    html.close_blocks();
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        html.body
+    )
+}
+
+// What's been written so far, and the paragraph or list that's still open
+#[derive(Default)]
+struct HtmlWriter {
+    body: String,
+    paragraph: Vec<String>,     // lines of the current paragraph, already turned into HTML
+    list: Option<&'static str>, // "ul" or "ol" if we're in a list
+}
+
+impl HtmlWriter {
+    fn close_paragraph(&mut self) {
+        if !self.paragraph.is_empty() {
+            self.body.push_str(&format!("<p>{}</p>\n", self.paragraph.join("\n")));
+            self.paragraph.clear();
+        }
+    }
+
+    fn close_list(&mut self) {
+        if let Some(list) = self.list.take() {
+            self.body.push_str(&format!("</{}>\n", list));
+        }
+    }
+
+    fn close_blocks(&mut self) {
+        self.close_paragraph();
+        self.close_list();
+    }
+}
+
+// "- item" is ("ul", "item") and "3. item" is ("ol", "item"), anything else isn't a list item
+pub fn list_item(line: &str) -> Option<(&'static str, &str)> {
+    if let Some(item) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")).or_else(|| line.strip_prefix("+ ")) {
+        return Some(("ul", item));
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let item = line[digits..].strip_prefix(". ")?; // digits are one byte each
+    if digits > 0 { Some(("ol", item)) } else { None }
+}
+
+/*
+The HTML version of `inline_spans`, with [links](url) on top.
+Unclosed markers are left as they are, just like in the editor.
+ */
+fn inline_html(text: &str) -> String {
+    let mut html = String::new();
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        if let Some(after) = rest.strip_prefix('`')
+            && let Some(end) = after.find('`')
+        {
+            html.push_str(&format!("<code>{}</code>", escape(&after[..end])));
+            i += end + 2;
+        } else if let Some(after) = rest.strip_prefix("**")
+            && let Some(end) = closing(after, "**")
+        {
+            html.push_str(&format!("<strong>{}</strong>", inline_html(&after[..end])));
+            i += end + 4;
+        } else if let Some(after) = rest.strip_prefix('*')
+            && let Some(end) = closing(after, "*")
+        {
+            html.push_str(&format!("<em>{}</em>", inline_html(&after[..end])));
+            i += end + 2;
+        } else if let Some(after) = rest.strip_prefix('[')
+            && let Some(label_end) = after.find("](")
+            && let Some(url_end) = after[label_end + 2..].find(')')
+        {
+            let url = &after[label_end + 2..label_end + 2 + url_end];
+            html.push_str(&format!("<a href=\"{}\">{}</a>", escape(url), inline_html(&after[..label_end])));
+            i += label_end + url_end + 4; // the [, ]( and )
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            html.push_str(&escape(&c.to_string()));
+            i += c.len_utf8();
+        }
+    }
+    html
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the text and style of each span, easier to compare than whole spans
+    fn parts(line: Line<'_>) -> Vec<(String, Style)> {
+        line.spans.into_iter().map(|span| (span.content.to_string(), span.style)).collect()
+    }
+
+    #[test]
+    fn headings_need_a_space_after_the_hashes() {
+        assert_eq!(heading_level("# Title"), Some(1));
+        assert_eq!(heading_level("### Title"), Some(3));
+        assert_eq!(heading_level("#hashtag"), None);
+        assert_eq!(heading_level("####### too deep"), None);
+        assert_eq!(parts(highlight_line("## Notes", false).0), vec![("## Notes".to_string(), heading_style(2))]);
+    }
+
+    #[test]
+    fn inline_markers_are_styled_and_unclosed_ones_are_plain() {
+        let spans = parts(highlight_line("a **bold** and *it* `x*y*`", false).0);
+        let text: Vec<&str> = spans.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(text, vec!["a ", "**bold**", " and ", "*it*", " ", "`x*y*`"]);
+        assert!(spans[1].1.add_modifier.contains(Modifier::BOLD));
+        assert!(spans[3].1.add_modifier.contains(Modifier::ITALIC));
+
+        assert_eq!(parts(highlight_line("* list item", false).0), vec![("* list item".to_string(), Style::default())]);
+        assert_eq!(parts(highlight_line("ünclosed `tick", false).0).len(), 1);
+    }
+
+    #[test]
+    fn fences_carry_over_between_lines() {
+        let (_, in_fence) = highlight_line("```rust", false);
+        assert!(in_fence);
+        let (line, in_fence) = highlight_line("# not a heading", in_fence);
+        assert!(in_fence);
+        assert_eq!(parts(line), vec![("# not a heading".to_string(), fence_style())]);
+        let (_, in_fence) = highlight_line("```", in_fence);
+        assert!(!in_fence);
+    }
+
+    #[test]
+    fn exports_the_common_markdown_to_html() {
+        let note: Vec<String> = [
+            "# Shopping & stuff",
+            "Get **milk** and *eggs*,",
+            "see [the list](https://example.com/?a=1&b=2).",
+            "",
+            "- one",
+            "- `two`",
+            "1. first",
+            "```",
+            "<tag> # not a heading",
+            "```",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+        let html = to_html("shopping", &note);
+        assert!(html.contains("<title>shopping</title>"));
+        assert!(html.contains(
+            "<h1>Shopping &amp; stuff</h1>\n\
+             <p>Get <strong>milk</strong> and <em>eggs</em>,\n\
+             see <a href=\"https://example.com/?a=1&amp;b=2\">the list</a>.</p>\n\
+             <ul>\n<li>one</li>\n<li><code>two</code></li>\n</ul>\n\
+             <ol>\n<li>first</li>\n</ol>\n\
+             <pre><code>&lt;tag&gt; # not a heading\n</code></pre>\n"
+        ));
+    }
+
+    #[test]
+    fn headings_are_listed_outside_of_fences() {
+        let lines: Vec<String> = ["# Notes", "text", "## Todo ", "```", "# not a heading", "```", "###", "#nope", "### Done"]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(
+            headings(&lines),
+            vec![
+                (0, 1, "Notes".to_string()),
+                (2, 2, "Todo".to_string()),
+                (6, 3, String::new()),
+                (8, 3, "Done".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tasks_are_found_at_any_indent_and_done_ones_are_struck_through() {
+        assert_eq!(checkbox("- [ ] milk"), Some((2, false)));
+        assert_eq!(checkbox("    * [x] eggs"), Some((6, true)));
+        assert_eq!(checkbox("\t12. [X]"), Some((5, true)));
+        assert_eq!(checkbox("- [x]done"), None);
+        assert_eq!(checkbox("[ ] not a list item"), None);
+        assert_eq!(checkbox("- [y] nope"), None);
+
+        let (done, _) = highlight_line("- [x] **eggs**", false);
+        assert_eq!(parts(done), vec![("- [x] **eggs**".to_string(), done_style())]);
+        let (todo, _) = highlight_line("- [ ] **eggs**", false);
+        assert_eq!(parts(todo).len(), 2); // still styled like any other line
+    }
+
+    #[test]
+    fn part_of_a_line_keeps_the_style_of_the_whole_line() {
+        let line = "## a long heading";
+        assert_eq!(parts(highlight_part(line, 5..9, false).0), vec![("long".to_string(), heading_style(2))]);
+        let (part, in_fence) = highlight_part("```rust", 3..7, false);
+        assert_eq!(parts(part), vec![("rust".to_string(), fence_style())]);
+        assert!(in_fence);
+        // bold that starts before the part isn't seen, what's inside it is still styled
+        let spans = parts(highlight_part("**cut** off and *in* view", 4..25, false).0);
+        assert_eq!(spans[0], ("t** off and ".to_string(), Style::default()));
+        assert_eq!(spans[1], ("*in*".to_string(), Style::default().add_modifier(Modifier::ITALIC)));
+    }
+}
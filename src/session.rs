@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/*
+Explanation of the code:
+The session is where we left off last time: which note was open and where the cursor was,
+plus how the panels and view were toggled (explorer, wrapping, ...) so the layout comes back the same.
+Every other note we know a cursor position for is in `positions`, so going back to one picks up where it was.
+It's written next to the config file (`<config dir>/trmnotes/session.toml`) when quitting,
+and read back on startup so the app opens right where you were.
+Unlike the config this isn't meant to be edited by hand, so a broken file is just ignored.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub current_file: Option<String>,
+    pub cursor_x: usize,
+    pub cursor_y: usize,
+    pub scroll_y: usize,
+    pub explorer_open: bool,
+    pub wrap_enabled: bool,
+    pub markdown_enabled: bool,
+    pub relative_line_numbers: Option<bool>, // None (like an older session) keeps the config's default
+    pub show_whitespace: bool,
+    pub writing_mode: bool,
+    pub positions: BTreeMap<String, (usize, usize, usize)>, // note -> (cursor_x, cursor_y, scroll_y)
+}
+
+impl Default for Session {
+    // A first run, nothing open yet and the view the way the app starts out
+    fn default() -> Self {
+        Session {
+            current_file: None,
+            cursor_x: 0,
+            cursor_y: 0,
+            scroll_y: 0,
+            explorer_open: true,
+            wrap_enabled: false,
+            markdown_enabled: true,
+            relative_line_numbers: None,
+            show_whitespace: false,
+            writing_mode: false,
+            positions: BTreeMap::new(),
+        }
+    }
+}
+
+impl Session {
+    // Where the session file goes, None if we can't figure out the config dir
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("trmnotes").join("session.toml"))
+    }
+
+    // The last session, or None if there isn't one (first run, or the file is unreadable)
+    pub fn load() -> Option<Session> {
+        Session::load_from(&Session::path()?)
+    }
+
+    pub fn load_from(path: &Path) -> Option<Session> {
+        let content = fs::read_to_string(path).ok()?;
+        toml::from_str(&content).ok()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Session::path() else {
+            return Ok(()); // nowhere to put it, so nothing to remember
+        };
+        self.save_to(&path)
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_and_loads_back_the_same_session() {
+        let path = std::env::temp_dir()
+            .join(format!("trmnotes-test-session-{}", std::process::id()))
+            .join("session.toml");
+        let session = Session {
+            current_file: Some("work/todo.txt".to_string()),
+            cursor_x: 3,
+            cursor_y: 12,
+            scroll_y: 5,
+            explorer_open: false,
+            wrap_enabled: true,
+            positions: BTreeMap::from([("notes/a b.txt".to_string(), (1, 2, 0))]),
+            ..Session::default()
+        };
+        session.save_to(&path).unwrap();
+        assert_eq!(Session::load_from(&path), Some(session));
+    }
+
+    #[test]
+    fn broken_or_missing_session_is_ignored() {
+        let path = std::env::temp_dir().join("trmnotes-test-no-session.toml");
+        assert_eq!(Session::load_from(&path), None);
+        assert_eq!(toml::from_str::<Session>("cursor_x = \"far\"").ok(), None);
+    }
+
+    #[test]
+    fn older_sessions_keep_the_default_layout() {
+        let session: Session = toml::from_str("current_file = \"a.txt\"\ncursor_y = 2").unwrap();
+        assert_eq!(session.current_file.as_deref(), Some("a.txt"));
+        assert!(session.explorer_open);
+        assert!(session.markdown_enabled);
+        assert_eq!(session.relative_line_numbers, None);
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::buffer::write_atomically;
+
+/*
+Explanation of the code:
+Where the notes are kept. The app goes through a `NoteStore` for listing, opening, saving, creating,
+deleting and renaming notes (and for making the notes folder at startup), so that can be swapped out.
+A couple of things only make sense for a folder on disk and still look at the file system themselves:
+following a path given on the command line, not going into a symlinked folder that loops back up,
+and handing a note to $EDITOR (only when `path_on_disk` says where it is, other stores can't do that).
+Backups of old versions are notes like any other (`note.txt.bak.1`), so they go through the store too.
+`FsNoteStore` is the notes folder on disk (what the app has always done), and `InMemoryStore` keeps
+everything in a map, which is handy for tests that shouldn't need a temp folder.
+Notes are named by their path relative to the store, with `/` between folders (like "work/todo.txt").
+Folders only exist as far as there are notes in them, the store makes them when a note needs one.
+*/
+pub trait NoteStore {
+    // What's directly in `folder` ("" is the top), notes and subfolders, hidden ones included
+    fn list(&self, folder: &str) -> io::Result<Vec<(String, NoteInfo)>>;
+    fn read(&self, name: &str) -> io::Result<Vec<u8>>;
+    // Replaces the note's contents, making it (and its folders) if it isn't there
+    fn write(&mut self, name: &str, content: &[u8]) -> io::Result<()>;
+    // Makes an empty note, an error if there's already one with that name
+    fn create(&mut self, name: &str) -> io::Result<()>;
+    fn delete(&mut self, name: &str) -> io::Result<()>;
+    // Moves a note (or a folder with everything in it), an error if `new` is taken
+    fn rename(&mut self, old: &str, new: &str) -> io::Result<()>;
+    // What we know about a note or folder, a NotFound error if there's nothing called `name`
+    fn info(&self, name: &str) -> io::Result<NoteInfo>;
+
+    // Gets the store ready before anything else, like making the notes folder, nothing to do for most stores
+    fn prepare(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // When the note was last changed, if the store knows
+    fn modified(&self, name: &str) -> Option<SystemTime> {
+        self.info(name).ok().and_then(|info| info.modified)
+    }
+
+    // Where the note is on disk, for handing it to another program. None if the store isn't a folder on disk
+    fn path_on_disk(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+// What list and info say about an entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoteInfo {
+    pub is_folder: bool,
+    pub is_link: bool,                  // a symlink (to a folder, that's how going into it can loop)
+    pub size: u64,                      // bytes
+    pub modified: Option<SystemTime>,   // None if the store doesn't keep track
+    pub read_only: bool,
+}
+
+fn not_found(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} not found", name))
+}
+
+fn already_exists(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", name))
+}
+
+// The notes folder on disk, this is what the app uses normally
+pub struct FsNoteStore {
+    root: PathBuf,
+}
+
+impl FsNoteStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsNoteStore { root: root.into() }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    // Makes the folders a note at `path` goes in
+    fn make_parent(path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+    }
+}
+
+impl NoteStore for FsNoteStore {
+    fn list(&self, folder: &str) -> io::Result<Vec<(String, NoteInfo)>> {
+        let mut entries = vec![];
+        for entry in fs::read_dir(self.path(folder))? {
+            let entry = entry?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue; // a name that isn't UTF-8 can't be a note name
+            };
+            // a broken symlink (or something we can't look at) isn't a note or a folder we could open
+            if let Ok(info) = info_at(&entry.path()) {
+                entries.push((name, info));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(name))
+    }
+
+    fn write(&mut self, name: &str, content: &[u8]) -> io::Result<()> {
+        let path = self.path(name);
+        FsNoteStore::make_parent(&path)?;
+        write_atomically(&path, content)
+    }
+
+    fn create(&mut self, name: &str) -> io::Result<()> {
+        let path = self.path(name);
+        FsNoteStore::make_parent(&path)?;
+        // create_new, so it can never write over a note that's there after all
+        fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+        Ok(())
+    }
+
+    fn delete(&mut self, name: &str) -> io::Result<()> {
+        fs::remove_file(self.path(name))
+    }
+
+    fn rename(&mut self, old: &str, new: &str) -> io::Result<()> {
+        // fs::rename would silently replace a note that's already there
+        let new_path = self.path(new);
+        if new_path.exists() {
+            return Err(already_exists(new));
+        }
+        FsNoteStore::make_parent(&new_path)?;
+        fs::rename(self.path(old), new_path)
+    }
+
+    fn info(&self, name: &str) -> io::Result<NoteInfo> {
+        info_at(&self.path(name))
+    }
+
+    fn prepare(&mut self) -> io::Result<()> {
+        fs::create_dir_all(&self.root)
+    }
+
+    fn path_on_disk(&self, name: &str) -> Option<PathBuf> {
+        Some(self.path(name))
+    }
+}
+
+// What the file system says about `path`, following a symlink to what it points at
+fn info_at(path: &Path) -> io::Result<NoteInfo> {
+    let metadata = fs::metadata(path)?;
+    Ok(NoteInfo {
+        is_folder: metadata.is_dir(),
+        is_link: fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink()),
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        read_only: metadata.permissions().readonly(),
+    })
+}
+
+/*
+Keeps `old` (what the note `name` held before it was saved over) as `<name>.bak.1`, keeping at most `keep` old versions.
+The older backups move up a slot first (.bak.1 -> .bak.2 and so on) and the one past `keep` is dropped.
+This is only called once the new version is written, so a save that fails leaves the backups alone,
+otherwise autosave trying again and again would push every older version out.
+ */
+pub fn back_up(store: &mut dyn NoteStore, name: &str, old: &[u8], keep: usize) -> io::Result<()> {
+    if keep == 0 {
+        return Ok(());
+    }
+    for slot in (1..keep).rev() {
+        let (older, newer) = (backup_name(name, slot), backup_name(name, slot + 1));
+        if store.info(&older).is_ok() {
+            // rename won't replace a note, so the oldest one makes room first
+            if store.info(&newer).is_ok() {
+                store.delete(&newer)?;
+            }
+            store.rename(&older, &newer)?;
+        }
+    }
+    store.write(&backup_name(name, 1), old)
+}
+
+// What backup number `slot` of a note is called (note.txt -> note.txt.bak.1), 1 is the newest
+pub fn backup_name(name: &str, slot: usize) -> String {
+    format!("{}.bak.{}", name, slot)
+}
+
+// Notes kept in memory only, name -> contents. Nothing is ever written anywhere
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryStore {
+    pub notes: BTreeMap<String, Vec<u8>>,
+}
+
+impl InMemoryStore {
+    // A store with these notes already in it
+    pub fn with_notes(notes: &[(&str, &str)]) -> Self {
+        InMemoryStore { notes: notes.iter().map(|(name, content)| (name.to_string(), content.as_bytes().to_vec())).collect() }
+    }
+
+    // true if there are notes somewhere under `folder`
+    fn is_folder(&self, folder: &str) -> bool {
+        let prefix = format!("{}/", folder.trim_end_matches('/'));
+        self.notes.keys().any(|name| name.starts_with(&prefix))
+    }
+}
+
+impl NoteStore for InMemoryStore {
+    fn list(&self, folder: &str) -> io::Result<Vec<(String, NoteInfo)>> {
+        let folder = folder.trim_end_matches('/');
+        if !folder.is_empty() && !self.is_folder(folder) {
+            return Err(not_found(folder));
+        }
+        let prefix = if folder.is_empty() { String::new() } else { format!("{}/", folder) };
+        let mut entries: Vec<(String, NoteInfo)> = vec![];
+        for (name, content) in &self.notes {
+            let Some(rest) = name.strip_prefix(&prefix) else {
+                continue;
+            };
+            match rest.split_once('/') {
+                // something deeper down, so its first folder is what's in here
+                Some((subfolder, _)) => {
+                    if !entries.iter().any(|(entry, _)| entry == subfolder) {
+                        entries.push((subfolder.to_string(), NoteInfo { is_folder: true, ..NoteInfo::default() }));
+                    }
+                }
+                None => entries.push((rest.to_string(), NoteInfo { size: content.len() as u64, ..NoteInfo::default() })),
+            }
+        }
+        Ok(entries)
+    }
+
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        self.notes.get(name).cloned().ok_or_else(|| not_found(name))
+    }
+
+    fn write(&mut self, name: &str, content: &[u8]) -> io::Result<()> {
+        if self.is_folder(name) {
+            return Err(already_exists(name));
+        }
+        self.notes.insert(name.to_string(), content.to_vec());
+        Ok(())
+    }
+
+    fn create(&mut self, name: &str) -> io::Result<()> {
+        if self.info(name).is_ok() {
+            return Err(already_exists(name));
+        }
+        self.notes.insert(name.to_string(), vec![]);
+        Ok(())
+    }
+
+    fn delete(&mut self, name: &str) -> io::Result<()> {
+        self.notes.remove(name).map(|_| ()).ok_or_else(|| not_found(name))
+    }
+
+    fn rename(&mut self, old: &str, new: &str) -> io::Result<()> {
+        if self.info(new).is_ok() {
+            return Err(already_exists(new));
+        }
+        if let Some(content) = self.notes.remove(old) {
+            self.notes.insert(new.to_string(), content);
+            return Ok(());
+        }
+        // a folder, so everything in it moves along
+        let (old, new) = (format!("{}/", old.trim_end_matches('/')), format!("{}/", new.trim_end_matches('/')));
+        let moved: Vec<String> = self.notes.keys().filter(|name| name.starts_with(&old)).cloned().collect();
+        if moved.is_empty() {
+            return Err(not_found(&old));
+        }
+        for name in moved {
+            let content = self.notes.remove(&name).unwrap_or_default();
+            self.notes.insert(format!("{}{}", new, &name[old.len()..]), content);
+        }
+        Ok(())
+    }
+
+    fn info(&self, name: &str) -> io::Result<NoteInfo> {
+        if let Some(content) = self.notes.get(name) {
+            Ok(NoteInfo { size: content.len() as u64, ..NoteInfo::default() })
+        } else if name.trim_end_matches('/').is_empty() || self.is_folder(name) {
+            Ok(NoteInfo { is_folder: true, ..NoteInfo::default() })
+        } else {
+            Err(not_found(name))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The same things have to hold for every store
+    fn behaves_like_a_store(store: &mut dyn NoteStore) {
+        store.create("a.txt").unwrap();
+        assert_eq!(store.create("a.txt").unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        store.write("work/plan.md", b"# Plan\n").unwrap();
+        assert_eq!(store.read("work/plan.md").unwrap(), b"# Plan\n");
+        assert_eq!(store.read("a.txt").unwrap(), b"");
+        assert_eq!(store.info("work/plan.md").unwrap().size, 7);
+        assert!(store.info("work").unwrap().is_folder);
+        assert_eq!(store.info("missing.txt").unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        let mut listed: Vec<(String, bool)> = store.list("").unwrap().into_iter().map(|(name, info)| (name, info.is_folder)).collect();
+        listed.sort();
+        assert_eq!(listed, vec![("a.txt".to_string(), false), ("work".to_string(), true)]);
+        assert_eq!(store.list("work").unwrap().len(), 1);
+
+        assert_eq!(store.rename("a.txt", "work/plan.md").unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        store.rename("a.txt", "b.txt").unwrap();
+        assert!(store.read("a.txt").is_err());
+        store.rename("work", "done").unwrap();
+        assert_eq!(store.read("done/plan.md").unwrap(), b"# Plan\n");
+        store.delete("b.txt").unwrap();
+        assert!(store.delete("b.txt").is_err());
+        assert_eq!(store.list("").unwrap().len(), 1);
+
+        // backups are notes too, the newest is .bak.1 and the oldest falls off the end
+        for version in ["one", "two", "three"] {
+            back_up(store, "done/plan.md", version.as_bytes(), 2).unwrap();
+        }
+        assert_eq!(store.read("done/plan.md.bak.1").unwrap(), b"three");
+        assert_eq!(store.read("done/plan.md.bak.2").unwrap(), b"two");
+        assert!(store.info("done/plan.md.bak.3").is_err());
+    }
+
+    #[test]
+    fn both_stores_behave_the_same() {
+        let folder = std::env::temp_dir().join(format!("trmnotes-test-store-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        behaves_like_a_store(&mut FsNoteStore::new(&folder));
+        behaves_like_a_store(&mut InMemoryStore::default());
+        // only a folder on disk can hand a note to another program
+        assert_eq!(FsNoteStore::new(&folder).path_on_disk("a.txt"), Some(folder.join("a.txt")));
+        assert_eq!(InMemoryStore::default().path_on_disk("a.txt"), None);
+    }
+}
@@ -0,0 +1,66 @@
+use ratatui::style::{Color, Style};
+
+/*
+Explanation of the code:
+The colors the editor is drawn with, picked by name with `theme` in the config file.
+There are two built in: "dark" (the original colors) and "light" for terminals with a light background,
+where bright blue and yellow on white are hard to read.
+Colors that mean something (green for ok, red for errors) stay the same in every theme.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub title: Color,        // panel and popup titles, and the tab bar
+    pub line_numbers: Color, // line numbers, and the cursor position in the status line
+    pub cursor_line: Color,  // the cursor's own line number (and the position when it's at the end)
+    pub selection: Style,    // drawn on top of the selected text
+    pub status: Color,       // messages and key hints in the status line
+    pub ruler: Color,        // background of the ruler column (`ruler_column` in the config)
+}
+
+impl Theme {
+    pub const DARK: Theme = Theme {
+        title: Color::Blue,
+        line_numbers: Color::Blue,
+        cursor_line: Color::Red,
+        selection: Style::new().add_modifier(ratatui::style::Modifier::REVERSED),
+        status: Color::Yellow,
+        ruler: Color::DarkGray,
+    };
+
+    pub const LIGHT: Theme = Theme {
+        title: Color::Magenta,
+        line_numbers: Color::DarkGray,
+        cursor_line: Color::Red,
+        selection: Style::new().bg(Color::Gray),
+        status: Color::Magenta,
+        ruler: Color::Gray,
+    };
+
+    // The theme called `name` (ignoring case), None if there's no such theme
+    pub fn named(name: &str) -> Option<Theme> {
+        match name.to_ascii_lowercase().as_str() {
+            "dark" => Some(Theme::DARK),
+            "light" => Some(Theme::LIGHT),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::DARK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes_are_found_by_name() {
+        assert_eq!(Theme::named("dark"), Some(Theme::DARK));
+        assert_eq!(Theme::named("Light").map(|theme| theme.title), Some(Color::Magenta));
+        assert_eq!(Theme::named("light").map(|theme| theme.selection.bg), Some(Some(Color::Gray)));
+        assert_eq!(Theme::named("solarized"), None);
+    }
+}
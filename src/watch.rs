@@ -0,0 +1,136 @@
+use std::path::Path;
+#[cfg(feature = "watch")]
+use std::path::PathBuf;
+#[cfg(feature = "watch")]
+use std::time::{Duration, Instant};
+
+/*
+Explanation of the code:
+Watches the folder the explorer is showing, so notes made, deleted or renamed outside the app show up
+without pressing F5. The watcher runs on its own thread and only tells us something happened,
+the main loop checks `changed` every time it wakes up (at least every POLL_TIMEOUT) and lists the notes again.
+Saving a bunch of files at once sends a burst of events, so we wait until it's been quiet for DEBOUNCE
+and refresh once for all of them.
+This needs the `notify` crate, which is behind the `watch` feature (on by default). Without it, or if the
+watcher can't be made (like running out of inotify watches), `new` fails and F5 is the way to refresh.
+*/
+
+// how long the folder has to be quiet before the explorer is refreshed
+#[cfg(feature = "watch")]
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+#[cfg(feature = "watch")]
+pub struct FolderWatch {
+    watcher: notify::RecommendedWatcher,
+    events: std::sync::mpsc::Receiver<()>,
+    folder: PathBuf,
+    last_event: Option<Instant>,
+}
+
+#[cfg(feature = "watch")]
+impl FolderWatch {
+    pub fn new(folder: &Path) -> Result<FolderWatch, String> {
+        use notify::Watcher;
+
+        let (sender, events) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            // just reading the folder (which refreshing does) counts as access, that would refresh forever
+            if event.is_ok_and(|event| !event.kind.is_access()) {
+                let _ = sender.send(());
+            }
+        })
+        .map_err(|e| e.to_string())?;
+        watcher.watch(folder, notify::RecursiveMode::NonRecursive).map_err(|e| e.to_string())?;
+        Ok(FolderWatch { watcher, events, folder: folder.to_path_buf(), last_event: None })
+    }
+
+    // Moves the watch over to `folder` when the explorer goes into another one
+    pub fn follow(&mut self, folder: &Path) -> Result<(), String> {
+        use notify::Watcher;
+
+        if self.folder == folder {
+            return Ok(());
+        }
+        let _ = self.watcher.unwatch(&self.folder); // it might be gone already
+        self.watcher.watch(folder, notify::RecursiveMode::NonRecursive).map_err(|e| e.to_string())?;
+        self.folder = folder.to_path_buf();
+        self.last_event = None;
+        Ok(())
+    }
+
+    // true once something changed in the folder and it's been quiet since, so the notes should be listed again
+    pub fn changed(&mut self) -> bool {
+        if self.events.try_iter().count() > 0 {
+            self.last_event = Some(Instant::now());
+        }
+        if self.last_event.is_some_and(|at| at.elapsed() >= DEBOUNCE) {
+            self.last_event = None;
+            return true;
+        }
+        false
+    }
+}
+
+// Built without the `watch` feature, so there's nothing to watch with
+#[cfg(not(feature = "watch"))]
+pub struct FolderWatch;
+
+#[cfg(not(feature = "watch"))]
+impl FolderWatch {
+    pub fn new(_folder: &Path) -> Result<FolderWatch, String> {
+        Err("built without the watch feature".to_string())
+    }
+
+    pub fn follow(&mut self, _folder: &Path) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn changed(&mut self) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, feature = "watch"))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // waits a couple seconds at most for the watch to notice something
+    fn wait_for_change(watch: &mut FolderWatch) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(2) {
+            if watch.changed() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn notices_notes_made_and_removed_outside_the_app() {
+        let folder = std::env::temp_dir().join(format!("trmnotes-test-watch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(folder.join("sub")).unwrap();
+        let mut watch = FolderWatch::new(&folder).unwrap();
+        assert!(!watch.changed());
+
+        // a burst of changes is one refresh
+        fs::write(folder.join("a.txt"), "a").unwrap();
+        fs::write(folder.join("b.txt"), "b").unwrap();
+        assert!(wait_for_change(&mut watch));
+        std::thread::sleep(DEBOUNCE * 2);
+        assert!(!watch.changed());
+
+        // reading the folder isn't a change
+        let _ = fs::read_dir(&folder).unwrap().count();
+        std::thread::sleep(DEBOUNCE * 2);
+        assert!(!watch.changed());
+
+        watch.follow(&folder.join("sub")).unwrap();
+        fs::write(folder.join("sub").join("c.txt"), "c").unwrap();
+        assert!(wait_for_change(&mut watch));
+        fs::remove_file(folder.join("sub").join("c.txt")).unwrap();
+        assert!(wait_for_change(&mut watch));
+    }
+}